@@ -1,6 +1,17 @@
+-- Current names and steamids rank above past names.
 SELECT $table_fields
 FROM users
-WHERE username LIKE CONCAT('%', $1::text, '%')
-	OR steamid LIKE CONCAT('%', $1::text, '%')
-ORDER BY id ASC
+WHERE users.username LIKE CONCAT('%', $1::text, '%')
+	OR users.steamid LIKE CONCAT('%', $1::text, '%')
+	OR EXISTS (
+		SELECT 1
+		FROM username_history
+		WHERE username_history.userid = users.id
+			AND username_history.username LIKE CONCAT('%', $1::text, '%')
+	)
+ORDER BY (
+		users.username LIKE CONCAT('%', $1::text, '%')
+		OR users.steamid LIKE CONCAT('%', $1::text, '%')
+	) DESC,
+	users.id ASC
 LIMIT $2 OFFSET $3;
\ No newline at end of file
@@ -1,3 +1,5 @@
-SELECT $fields
+SELECT $table_fields
 FROM authorizations
-WHERE userid = $1;
\ No newline at end of file
+WHERE userid = $1
+ORDER BY expires DESC
+LIMIT 1;
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT COUNT(DISTINCT s.userid)
+FROM player_division_stats s
+WHERE s.leagueid = (SELECT leagueid FROM divisions WHERE id = $1)
+	AND s.divisionid = $1;
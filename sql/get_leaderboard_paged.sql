@@ -0,0 +1,20 @@
+SELECT s.userid,
+	SUM(s.games_played)::BIGINT AS games_played,
+	SUM(s.goals)::BIGINT AS goals,
+	SUM(s.assists)::BIGINT AS assists,
+	SUM(s.intercepts)::BIGINT AS intercepts,
+	SUM(s.steals)::BIGINT AS steals,
+	SUM(s.passes)::BIGINT AS passes,
+	SUM(s.deaths)::BIGINT AS deaths
+FROM player_division_stats s
+WHERE s.leagueid = (SELECT leagueid FROM divisions WHERE id = $1)
+	AND s.divisionid = $1
+GROUP BY s.userid
+ORDER BY CASE $2
+		WHEN 'goals' THEN SUM(s.goals)
+		WHEN 'assists' THEN SUM(s.assists)
+		WHEN 'intercepts' THEN SUM(s.intercepts)
+		WHEN 'steals' THEN SUM(s.steals)
+		WHEN 'passes' THEN SUM(s.passes)
+	END DESC, s.userid
+LIMIT $4 OFFSET $3;
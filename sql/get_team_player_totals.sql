@@ -0,0 +1,13 @@
+SELECT s.userid,
+	SUM(s.games_played)::BIGINT AS games_played,
+	SUM(s.goals)::BIGINT AS goals,
+	SUM(s.assists)::BIGINT AS assists,
+	SUM(s.intercepts)::BIGINT AS intercepts,
+	SUM(s.steals)::BIGINT AS steals,
+	SUM(s.passes)::BIGINT AS passes,
+	SUM(s.deaths)::BIGINT AS deaths
+FROM player_league_stats s
+JOIN leagues l ON l.id = s.leagueid
+WHERE s.teamid = $1 AND NOT l.is_hidden
+GROUP BY s.userid
+ORDER BY goals DESC, s.userid;
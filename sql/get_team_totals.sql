@@ -0,0 +1,15 @@
+SELECT (
+		SELECT COUNT(*) FROM game_logs gl
+		JOIN games g ON g.id = gl.gameid
+		JOIN leagues league ON league.id = g.leagueid
+		WHERE (gl.red_teamid = $1 OR gl.blue_teamid = $1) AND NOT league.is_hidden
+	) AS games_played,
+	COALESCE(SUM(s.goals), 0)::BIGINT AS goals,
+	COALESCE(SUM(s.assists), 0)::BIGINT AS assists,
+	COALESCE(SUM(s.intercepts), 0)::BIGINT AS intercepts,
+	COALESCE(SUM(s.steals), 0)::BIGINT AS steals,
+	COALESCE(SUM(s.passes), 0)::BIGINT AS passes,
+	COALESCE(SUM(s.deaths), 0)::BIGINT AS deaths
+FROM player_league_stats s
+JOIN leagues l ON l.id = s.leagueid
+WHERE s.teamid = $1 AND NOT l.is_hidden;
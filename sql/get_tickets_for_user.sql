@@ -0,0 +1,19 @@
+SELECT $table_fields
+FROM tickets
+WHERE ($3::VARCHAR IS NULL OR tickets.status = $3)
+	AND (
+		tickets.reported_userid = $1
+		OR tickets.leagueid = ANY($2)
+		OR EXISTS (
+			SELECT 1
+			FROM userTeamAssociation
+			INNER JOIN teamDivAssociations ON teamDivAssociations.id = userTeamAssociation.teamdivid
+			INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid
+			LEFT JOIN games ON games.id = tickets.gameid
+			WHERE userTeamAssociation.userid = $1
+				AND userTeamAssociation.ended_at IS NULL
+				AND divisions.leagueid = tickets.leagueid
+				AND teamDivAssociations.teamid IN (tickets.teamid, games.teamhomeid, games.teamawayid)
+		)
+	)
+ORDER BY tickets.updated_at DESC, tickets.id DESC;
@@ -1,3 +1,4 @@
-SELECT *
-FROM authorizations
-WHERE token = $1;
\ No newline at end of file
+SELECT $table_fields
+FROM users
+	INNER JOIN authorizations ON authorizations.userid = users.id
+WHERE authorizations.token = $1;
\ No newline at end of file
@@ -5,14 +5,54 @@ CREATE TABLE IF NOT EXISTS users (
 	avatarurl VARCHAR(200) NOT NULL,
 	permissions BIGINT DEFAULT 0 NOT NULL,
 	created_at TIMESTAMPTZ NOT NULL,
+	rating DOUBLE PRECISION NOT NULL DEFAULT 1500,
 	UNIQUE (steamid)
 );
+-- Every Steam name a user has been seen with, refreshed by the profile sync task.
+CREATE TABLE IF NOT EXISTS username_history (
+	id BIGSERIAL PRIMARY KEY,
+	userid BIGINT NOT NULL,
+	username VARCHAR(50) NOT NULL,
+	first_seen TIMESTAMPTZ NOT NULL,
+	last_seen TIMESTAMPTZ NOT NULL,
+	UNIQUE (userid, username),
+	CONSTRAINT FK_username_history_user FOREIGN KEY (userid) references users(id)
+);
+-- Accounts that failed the eligibility rules at signup, waiting on an admin.
+CREATE TABLE IF NOT EXISTS user_review_flags (
+	id BIGSERIAL PRIMARY KEY,
+	userid BIGSERIAL NOT NULL,
+	reasons TEXT[] NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	reviewed_at TIMESTAMPTZ,
+	reviewed_by BIGINT,
+	CONSTRAINT FK_user_review_flags_user FOREIGN KEY (userid) references users(id),
+	CONSTRAINT FK_user_review_flags_reviewed_by FOREIGN KEY (reviewed_by) references users(id)
+);
+-- A season groups the leagues that run at the same time.
+-- Its status only ever moves forward: draft, registration, active, playoffs, completed.
+CREATE TABLE IF NOT EXISTS seasons (
+	id BIGSERIAL PRIMARY KEY,
+	name VARCHAR(50) NOT NULL,
+	status VARCHAR(20) NOT NULL DEFAULT 'draft'
+		CHECK (status IN ('draft', 'registration', 'active', 'playoffs', 'completed')),
+	starts_at TIMESTAMPTZ,
+	ends_at TIMESTAMPTZ,
+	registration_opens_at TIMESTAMPTZ,
+	registration_closes_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL,
+	-- When it moved to completed, which archived every league still in it.
+	completed_at TIMESTAMPTZ
+);
 CREATE TABLE IF NOT EXISTS leagues (
 	id BIGSERIAL PRIMARY KEY,
 	name VARCHAR(50) NOT NULL,
 	accepting_teams BOOLEAN DEFAULT FALSE NOT NULL,
 	is_hidden BOOLEAN DEFAULT FALSE NOT NULL,
-	created_at TIMESTAMPTZ NOT NULL
+	created_at TIMESTAMPTZ NOT NULL,
+	seasonid BIGINT,
+	archived_at TIMESTAMPTZ,
+	CONSTRAINT FK_leagues_season FOREIGN KEY (seasonid) references seasons(id)
 );
 CREATE TABLE IF NOT EXISTS divisions (
 	id BIGSERIAL PRIMARY KEY,
@@ -36,8 +76,21 @@ CREATE TABLE IF NOT EXISTS teams (
 	team_name VARCHAR(200) NOT NULL DEFAULT 'Unnamed',
 	owner_id BIGSERIAL NOT NULL,
 	created_at TIMESTAMPTZ NOT NULL,
+	logo_url VARCHAR(200),
+	-- Disbanded teams are hidden from listings, but kept for their match history.
+	disbanded_at TIMESTAMPTZ,
+	rating DOUBLE PRECISION NOT NULL DEFAULT 1500,
 	CONSTRAINT FK_teams_owner_id FOREIGN KEY (owner_id) references users(id)
 );
+-- Every name and tag a team has had, from when it started using it.
+CREATE TABLE IF NOT EXISTS team_name_history (
+	id BIGSERIAL PRIMARY KEY,
+	teamid BIGINT NOT NULL,
+	team_name VARCHAR(200) NOT NULL,
+	team_tag VARCHAR(6) NOT NULL,
+	started_at TIMESTAMPTZ NOT NULL,
+	CONSTRAINT FK_team_name_history_team FOREIGN KEY (teamid) references teams(id)
+);
 -- A team can be in multiple leagues at the same time, and have different rosters, while having the same (base) team.
 CREATE TABLE IF NOT EXISTS teamDivAssociations (
 	id BIGSERIAL PRIMARY KEY,
@@ -48,8 +101,17 @@ CREATE TABLE IF NOT EXISTS teamDivAssociations (
 	points_down BIGINT NOT NULL DEFAULT 0,
 	created_at TIMESTAMPTZ NOT NULL,
 	is_private BOOLEAN NOT NULL,
+	-- Sign ups wait in the division they asked for until an admin places them.
+	requested_divisionid BIGINT,
+	placement_status VARCHAR(20) NOT NULL DEFAULT 'placed'
+		CHECK (placement_status IN ('pending', 'placed')),
+	placement_note TEXT,
+	placed_at TIMESTAMPTZ,
+	placed_by BIGINT,
 	CONSTRAINT FK_teamDivAssociation_team FOREIGN KEY (teamid) references teams(id),
-	CONSTRAINT FK_teamDivAssociation_division FOREIGN KEY (divisionid) references divisions(id)
+	CONSTRAINT FK_teamDivAssociation_division FOREIGN KEY (divisionid) references divisions(id),
+	CONSTRAINT FK_teamDivAssociation_requested_division FOREIGN KEY (requested_divisionid) references divisions(id),
+	CONSTRAINT FK_teamDivAssociation_placed_by FOREIGN KEY (placed_by) references users(id)
 );
 CREATE TABLE IF NOT EXISTS userTeamAssociation (
 	id BIGSERIAL PRIMARY KEY,
@@ -69,10 +131,206 @@ CREATE TABLE IF NOT EXISTS games (
 	teamawayid BIGSERIAL NOT NULL,
 	created_at TIMESTAMPTZ NOT NULL,
 	played_at TIMESTAMPTZ NOT NULL,
+	home_score INT,
+	away_score INT,
+	-- Set once an admin confirms the score, which is when ratings change.
+	confirmed_at TIMESTAMPTZ,
+	-- Leaders can agree on a time within the window before the deadline,
+	-- otherwise the game is played at played_at.
+	window_starts_at TIMESTAMPTZ,
+	window_ends_at TIMESTAMPTZ,
+	schedule_deadline TIMESTAMPTZ,
+	time_agreed_at TIMESTAMPTZ,
 	CONSTRAINT FK_game_league FOREIGN KEY (leagueid) references leagues(id),
 	CONSTRAINT FK_game_home FOREIGN KEY (teamhomeid) references teams(id),
 	CONSTRAINT FK_game_away FOREIGN KEY (teamawayid) references teams(id)
 );
+-- A time one team suggested for a game. Only the latest one is pending.
+CREATE TABLE IF NOT EXISTS game_time_proposals (
+	id BIGSERIAL PRIMARY KEY,
+	gameid BIGINT NOT NULL,
+	teamid BIGINT NOT NULL,
+	proposed_by BIGINT NOT NULL,
+	proposed_time TIMESTAMPTZ NOT NULL,
+	status VARCHAR(20) NOT NULL DEFAULT 'pending'
+		CHECK (status IN ('pending', 'accepted', 'superseded', 'expired')),
+	created_at TIMESTAMPTZ NOT NULL,
+	responded_by BIGINT,
+	responded_at TIMESTAMPTZ,
+	CONSTRAINT FK_game_time_proposals_game FOREIGN KEY (gameid) references games(id) ON DELETE CASCADE,
+	CONSTRAINT FK_game_time_proposals_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_game_time_proposals_proposed_by FOREIGN KEY (proposed_by) references users(id),
+	CONSTRAINT FK_game_time_proposals_responded_by FOREIGN KEY (responded_by) references users(id)
+);
+-- Who actually played for each team in a game.
+CREATE TABLE IF NOT EXISTS game_lineups (
+	id BIGSERIAL PRIMARY KEY,
+	gameid BIGINT NOT NULL,
+	teamid BIGINT NOT NULL,
+	userid BIGINT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	UNIQUE (gameid, userid),
+	CONSTRAINT FK_game_lineups_game FOREIGN KEY (gameid) references games(id) ON DELETE CASCADE,
+	CONSTRAINT FK_game_lineups_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_game_lineups_user FOREIGN KEY (userid) references users(id)
+);
+-- A team asking to play someone from outside its roster in one game.
+CREATE TABLE IF NOT EXISTS mercenary_requests (
+	id BIGSERIAL PRIMARY KEY,
+	gameid BIGINT NOT NULL,
+	teamid BIGINT NOT NULL,
+	userid BIGINT NOT NULL,
+	requested_by BIGINT NOT NULL,
+	note TEXT,
+	status VARCHAR(20) NOT NULL DEFAULT 'pending'
+		CHECK (status IN ('pending', 'approved', 'denied')),
+	created_at TIMESTAMPTZ NOT NULL,
+	reviewed_by BIGINT,
+	reviewed_at TIMESTAMPTZ,
+	CONSTRAINT FK_mercenary_requests_game FOREIGN KEY (gameid) references games(id) ON DELETE CASCADE,
+	CONSTRAINT FK_mercenary_requests_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_mercenary_requests_user FOREIGN KEY (userid) references users(id),
+	CONSTRAINT FK_mercenary_requests_requested_by FOREIGN KEY (requested_by) references users(id),
+	CONSTRAINT FK_mercenary_requests_reviewed_by FOREIGN KEY (reviewed_by) references users(id)
+);
+-- The logs.tf log a game's stats were imported from, and which side each team played on.
+CREATE TABLE IF NOT EXISTS game_logs (
+	id BIGSERIAL PRIMARY KEY,
+	gameid BIGINT NOT NULL UNIQUE,
+	-- Only set for logs fetched from logs.tf, rather than uploaded.
+	logid BIGINT,
+	map VARCHAR(100) NOT NULL,
+	title VARCHAR(200) NOT NULL,
+	red_teamid BIGINT NOT NULL,
+	blue_teamid BIGINT NOT NULL,
+	imported_by BIGINT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	CONSTRAINT FK_game_logs_game FOREIGN KEY (gameid) references games(id) ON DELETE CASCADE,
+	CONSTRAINT FK_game_logs_red_team FOREIGN KEY (red_teamid) references teams(id),
+	CONSTRAINT FK_game_logs_blue_team FOREIGN KEY (blue_teamid) references teams(id),
+	CONSTRAINT FK_game_logs_imported_by FOREIGN KEY (imported_by) references users(id)
+);
+-- What each player did in a game, from its log.
+CREATE TABLE IF NOT EXISTS player_game_stats (
+	id BIGSERIAL PRIMARY KEY,
+	gameid BIGINT NOT NULL,
+	userid BIGINT NOT NULL,
+	teamid BIGINT NOT NULL,
+	goals INT NOT NULL DEFAULT 0,
+	assists INT NOT NULL DEFAULT 0,
+	intercepts INT NOT NULL DEFAULT 0,
+	steals INT NOT NULL DEFAULT 0,
+	passes INT NOT NULL DEFAULT 0,
+	deaths INT NOT NULL DEFAULT 0,
+	UNIQUE (gameid, userid),
+	CONSTRAINT FK_player_game_stats_game FOREIGN KEY (gameid) references games(id) ON DELETE CASCADE,
+	CONSTRAINT FK_player_game_stats_user FOREIGN KEY (userid) references users(id),
+	CONSTRAINT FK_player_game_stats_team FOREIGN KEY (teamid) references teams(id)
+);
+-- player_game_stats summed up per player, team and league, so aggregates don't scan every game.
+-- Rebuilt for a game's teams whenever a log is imported for it.
+-- The player_division_stats view in migrate.sql adds the division each row counts in.
+CREATE TABLE IF NOT EXISTS player_league_stats (
+	id BIGSERIAL PRIMARY KEY,
+	userid BIGINT NOT NULL,
+	teamid BIGINT NOT NULL,
+	leagueid BIGINT NOT NULL,
+	games_played INT NOT NULL DEFAULT 0,
+	goals INT NOT NULL DEFAULT 0,
+	assists INT NOT NULL DEFAULT 0,
+	intercepts INT NOT NULL DEFAULT 0,
+	steals INT NOT NULL DEFAULT 0,
+	passes INT NOT NULL DEFAULT 0,
+	deaths INT NOT NULL DEFAULT 0,
+	UNIQUE (userid, teamid, leagueid),
+	CONSTRAINT FK_player_league_stats_user FOREIGN KEY (userid) references users(id),
+	CONSTRAINT FK_player_league_stats_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_player_league_stats_league FOREIGN KEY (leagueid) references leagues(id) ON DELETE CASCADE
+);
+-- STV demos teams uploaded for a game. The files live in DEMO_DIR until they expire.
+CREATE TABLE IF NOT EXISTS game_demos (
+	id BIGSERIAL PRIMARY KEY,
+	gameid BIGINT NOT NULL,
+	teamid BIGINT NOT NULL,
+	uploaded_by BIGINT NOT NULL,
+	-- The name it was uploaded with, and the one it's stored under.
+	file_name VARCHAR(100) NOT NULL,
+	stored_name VARCHAR(100) NOT NULL UNIQUE,
+	size_bytes BIGINT NOT NULL,
+	sha256 CHAR(64) NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	CONSTRAINT FK_game_demos_game FOREIGN KEY (gameid) references games(id) ON DELETE CASCADE,
+	CONSTRAINT FK_game_demos_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_game_demos_uploaded_by FOREIGN KEY (uploaded_by) references users(id)
+);
+-- Disputes and support requests a team opened in a league, about a game, a player, or both.
+CREATE TABLE IF NOT EXISTS tickets (
+	id BIGSERIAL PRIMARY KEY,
+	leagueid BIGINT NOT NULL,
+	-- The team that opened it, and who on it did.
+	teamid BIGINT NOT NULL,
+	opened_by BIGINT NOT NULL,
+	gameid BIGINT,
+	reported_userid BIGINT,
+	category VARCHAR(20) NOT NULL
+		CHECK (category IN ('cheating', 'no_show', 'ringer', 'other')),
+	subject VARCHAR(100) NOT NULL,
+	status VARCHAR(20) NOT NULL DEFAULT 'open'
+		CHECK (status IN ('open', 'in_review', 'resolved', 'closed')),
+	-- Set when an admin resolves it.
+	outcome VARCHAR(20)
+		CHECK (outcome IN ('result_override', 'forfeit', 'ban', 'warning', 'no_action')),
+	resolution_note TEXT,
+	resolved_by BIGINT,
+	resolved_at TIMESTAMPTZ,
+	created_at TIMESTAMPTZ NOT NULL,
+	updated_at TIMESTAMPTZ NOT NULL,
+	CONSTRAINT FK_tickets_league FOREIGN KEY (leagueid) references leagues(id) ON DELETE CASCADE,
+	CONSTRAINT FK_tickets_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_tickets_opened_by FOREIGN KEY (opened_by) references users(id),
+	CONSTRAINT FK_tickets_game FOREIGN KEY (gameid) references games(id) ON DELETE SET NULL,
+	CONSTRAINT FK_tickets_reported_user FOREIGN KEY (reported_userid) references users(id),
+	CONSTRAINT FK_tickets_resolved_by FOREIGN KEY (resolved_by) references users(id)
+);
+-- The thread of a ticket, oldest first. The first message is the one it was opened with.
+CREATE TABLE IF NOT EXISTS ticket_messages (
+	id BIGSERIAL PRIMARY KEY,
+	ticketid BIGINT NOT NULL,
+	userid BIGINT NOT NULL,
+	body TEXT NOT NULL,
+	-- Links to evidence, like demos, logs or clips.
+	evidence TEXT[] NOT NULL DEFAULT '{}',
+	created_at TIMESTAMPTZ NOT NULL,
+	CONSTRAINT FK_ticket_messages_ticket FOREIGN KEY (ticketid) references tickets(id) ON DELETE CASCADE,
+	CONSTRAINT FK_ticket_messages_user FOREIGN KEY (userid) references users(id)
+);
+-- Players banned from playing in any league, usually by resolving a ticket about them.
+CREATE TABLE IF NOT EXISTS user_bans (
+	id BIGSERIAL PRIMARY KEY,
+	userid BIGINT NOT NULL,
+	leagueid BIGINT NOT NULL,
+	ticketid BIGINT,
+	banned_by BIGINT NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	CONSTRAINT FK_user_bans_user FOREIGN KEY (userid) references users(id),
+	CONSTRAINT FK_user_bans_league FOREIGN KEY (leagueid) references leagues(id) ON DELETE CASCADE,
+	CONSTRAINT FK_user_bans_ticket FOREIGN KEY (ticketid) references tickets(id) ON DELETE SET NULL,
+	CONSTRAINT FK_user_bans_banned_by FOREIGN KEY (banned_by) references users(id)
+);
+-- Every change to a player's or a team's rating, and the game that caused it.
+CREATE TABLE IF NOT EXISTS rating_history (
+	id BIGSERIAL PRIMARY KEY,
+	userid BIGINT,
+	teamid BIGINT,
+	gameid BIGINT,
+	rating_before DOUBLE PRECISION NOT NULL,
+	rating_after DOUBLE PRECISION NOT NULL,
+	created_at TIMESTAMPTZ NOT NULL,
+	CHECK ((userid IS NULL) <> (teamid IS NULL)),
+	CONSTRAINT FK_rating_history_user FOREIGN KEY (userid) references users(id),
+	CONSTRAINT FK_rating_history_team FOREIGN KEY (teamid) references teams(id),
+	CONSTRAINT FK_rating_history_game FOREIGN KEY (gameid) references games(id) ON DELETE SET NULL
+);
 CREATE TABLE IF NOT EXISTS authorizations (
 	id BIGSERIAL PRIMARY KEY,
 	userid BIGSERIAL NOT NULL,
@@ -0,0 +1,51 @@
+-- Brings a database made by an older initdb.sql up to date. Runs on every startup, after initdb.sql
+-- has made any tables that are missing, so every statement here has to be safe to run again.
+ALTER TABLE users
+	ADD COLUMN IF NOT EXISTS rating DOUBLE PRECISION NOT NULL DEFAULT 1500;
+ALTER TABLE seasons
+	ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ;
+ALTER TABLE leagues
+	ADD COLUMN IF NOT EXISTS seasonid BIGINT CONSTRAINT FK_leagues_season references seasons(id),
+	ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+ALTER TABLE teams
+	ADD COLUMN IF NOT EXISTS logo_url VARCHAR(200),
+	ADD COLUMN IF NOT EXISTS disbanded_at TIMESTAMPTZ,
+	ADD COLUMN IF NOT EXISTS rating DOUBLE PRECISION NOT NULL DEFAULT 1500;
+ALTER TABLE teamDivAssociations
+	ADD COLUMN IF NOT EXISTS requested_divisionid BIGINT
+		CONSTRAINT FK_teamDivAssociation_requested_division references divisions(id),
+	ADD COLUMN IF NOT EXISTS placement_status VARCHAR(20) NOT NULL DEFAULT 'placed'
+		CHECK (placement_status IN ('pending', 'placed')),
+	ADD COLUMN IF NOT EXISTS placement_note TEXT,
+	ADD COLUMN IF NOT EXISTS placed_at TIMESTAMPTZ,
+	ADD COLUMN IF NOT EXISTS placed_by BIGINT
+		CONSTRAINT FK_teamDivAssociation_placed_by references users(id);
+ALTER TABLE games
+	ADD COLUMN IF NOT EXISTS home_score INT,
+	ADD COLUMN IF NOT EXISTS away_score INT,
+	ADD COLUMN IF NOT EXISTS confirmed_at TIMESTAMPTZ,
+	ADD COLUMN IF NOT EXISTS window_starts_at TIMESTAMPTZ,
+	ADD COLUMN IF NOT EXISTS window_ends_at TIMESTAMPTZ,
+	ADD COLUMN IF NOT EXISTS schedule_deadline TIMESTAMPTZ,
+	ADD COLUMN IF NOT EXISTS time_agreed_at TIMESTAMPTZ;
+-- username_history.userid used to be a BIGSERIAL, which filled itself in when left out.
+ALTER TABLE username_history ALTER COLUMN userid DROP DEFAULT;
+DROP SEQUENCE IF EXISTS username_history_userid_seq;
+-- Views go last, so they can use any of the columns above.
+-- Each player's stats for a team count in one division: the one of the team's rosters they joined
+-- last, or the team's first roster in the league for mercenaries.
+CREATE OR REPLACE VIEW player_division_stats AS
+SELECT s.id, s.userid, s.teamid, s.leagueid, s.games_played, s.goals, s.assists, s.intercepts,
+	s.steals, s.passes, s.deaths,
+	COALESCE(
+		(SELECT tda.divisionid FROM userTeamAssociation uta
+		JOIN teamDivAssociations tda ON tda.id = uta.teamdivid
+		JOIN divisions d ON d.id = tda.divisionid
+		WHERE uta.userid = s.userid AND tda.teamid = s.teamid AND d.leagueid = s.leagueid
+		ORDER BY uta.created_at DESC, uta.id DESC LIMIT 1),
+		(SELECT tda.divisionid FROM teamDivAssociations tda
+		JOIN divisions d ON d.id = tda.divisionid
+		WHERE tda.teamid = s.teamid AND d.leagueid = s.leagueid
+		ORDER BY tda.id LIMIT 1)
+	) AS divisionid
+FROM player_league_stats s;
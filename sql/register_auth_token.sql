@@ -1,3 +1,3 @@
 INSERT INTO authorizations(userid, token, created_at, expires)
 VALUES ($1, $2, $3, $4)
-RETURNING $fields;
\ No newline at end of file
+RETURNING $table_fields;
\ No newline at end of file
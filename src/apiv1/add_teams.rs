@@ -1,7 +1,8 @@
 use actix_web::{post, web, HttpResponse};
 use serde::{Deserialize, Serialize};
 
-use crate::db::team_div_assocs::MiniTeamDivAssociation;
+use crate::db::leagues::Visibility;
+use crate::db::teams::MiniTeamDivAssociation;
 use crate::grab_pool;
 use crate::models::TeamDivAssociation;
 use crate::permission::UserPermission;
@@ -9,6 +10,8 @@ use crate::AppState;
 use crate::{db, errors::MyError};
 use chrono::{DateTime, Utc};
 
+use super::session::SessionToken;
+use super::validation::{Checker, Valid, Validate};
 use super::HttpResult;
 
 #[derive(Serialize, Deserialize)]
@@ -17,72 +20,121 @@ struct TeamDivAssocInfo {
     pub teamid: i64,
     pub leagueid: i64,
     pub is_private: bool,
+    /// The division the team wants to play in. Defaults to the league's highest priority one.
+    #[serde(default)]
+    pub requested_divisionid: Option<i64>,
+}
+
+impl Validate for TeamDivAssocInfo {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .optional_text("roster_name", &mut self.roster_name)
+            .length(1, 50)
+            .printable()
+            .allowed_name();
+    }
 }
 #[post("/api/v1/leagues/{id}/teams")]
 pub async fn post_team_to_league(
     state: web::Data<AppState>,
-    auth_header: web::Header<super::admin::AuthHeader>,
-    new_team: web::Json<TeamDivAssocInfo>,
+    session: SessionToken,
+    new_team: Valid<TeamDivAssocInfo>,
 ) -> HttpResult {
-    let client = grab_pool(&state).await?;
-    let token = auth_header.0 .0;
-
-    let user = match db::get_user_from_auth_token(&client, &token).await {
-        Ok(user) => user,
-        Err(MyError::NotFound) => {
-            return Ok(HttpResponse::Unauthorized().body("Invalid authorization token"))
-        }
-        Err(err) => return Ok(HttpResponse::InternalServerError().body(format!("{err:?}"))),
-    };
+    let mut client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
 
     let leagueid = new_team.leagueid;
-    let league = match db::leagues::get_league_from_id(&client, leagueid).await {
-        Ok(league) => league,
-        Err(MyError::NotFound) => {
-            return Ok(HttpResponse::NotFound().body("League not found with id {leagueid}"))
-        }
-        Err(err) => return Ok(HttpResponse::InternalServerError().body(format!("{err:?}"))),
-    };
+    let league = db::leagues::get_league_from_id(&client, leagueid).await?;
 
+    if league.archived_at.is_some() {
+        return Err(MyError::Conflict(
+            "This league has been archived".to_owned(),
+        ));
+    }
     if !user.admin_or_perm(UserPermission::CreateTeam) && !league.accepting_teams {
-        return Ok(HttpResponse::BadRequest().body("League not accepting new teams"));
+        return Err(MyError::Conflict(
+            "League not accepting new teams".to_owned(),
+        ));
+    }
+    if let Some(seasonid) = league.seasonid {
+        let season = db::seasons::get_season_from_id(&client, seasonid).await?;
+        if !user.admin_or_perm(UserPermission::CreateTeam)
+            && !season.is_registering(chrono::offset::Utc::now())
+        {
+            return Err(MyError::Conflict(
+                "Registration for this season is closed".to_owned(),
+            ));
+        }
     }
 
     // authorized to sign this team up, check if they actually own the specified team
-    let team = match db::get_team_from_id(&client, new_team.teamid).await {
+    let team = match db::teams::get_team_from_id(&client, new_team.teamid).await {
         Ok(team) => team,
-        Err(MyError::NotFound) => return Ok(HttpResponse::BadRequest().body("Team not found")),
-        Err(err) => return Ok(HttpResponse::InternalServerError().body(format!("{err:?}"))),
+        Err(MyError::NotFound) => return Err(MyError::invalid_field("teamid", "no such team")),
+        Err(err) => return Err(err),
     };
 
     if user.id != team.owner_id {
-        return Ok(HttpResponse::BadRequest().body("you don't own this team?"));
+        return Err(MyError::Forbidden("You don't own this team"));
+    }
+    if team.disbanded_at.is_some() {
+        return Err(MyError::Conflict("This team has been disbanded".to_owned()));
     }
 
-    let divs = db::leagues::get_divs_for_league_id(&client, leagueid).await?;
+    // The roster and its leader go in together, so we never leave a roster with no leader.
+    // The tag is checked under the team's and the league's locks, so nothing can take it meanwhile.
+    let transaction = client.transaction().await?;
+    let team = db::teams::lock_team(&transaction, team.id).await?;
+    db::leagues::lock_league(&transaction, leagueid).await?;
 
-    // get the div with the highest priority to add teams to
-    let div = match divs.iter().reduce(|previous, current| {
-        if current.prio > previous.prio {
-            return current;
-        } else {
-            return previous;
-        }
-    }) {
-        Some(div) => div,
-        None => {
-            return Ok(HttpResponse::BadRequest()
-                .body("The specified league is not accepting new team registrations"))
-        }
+    if db::teams::is_tag_taken_in_league(&transaction, leagueid, &team.team_tag, team.id).await? {
+        return Err(MyError::invalid_field(
+            "teamid",
+            format!(
+                "another team in this league already uses the tag {0}",
+                team.team_tag
+            ),
+        ));
+    }
+
+    let divs = db::leagues::get_divs_for_league_id(&transaction, leagueid).await?;
+
+    // Sign ups wait in the division they asked for, or the one with the highest priority.
+    let div = match new_team.requested_divisionid {
+        Some(divid) => match divs.iter().find(|div| div.id == divid) {
+            Some(div) => div,
+            None => {
+                return Err(MyError::invalid_field(
+                    "requested_divisionid",
+                    "not a division in this league",
+                ))
+            }
+        },
+        None => match divs.iter().reduce(|previous, current| {
+            if current.prio > previous.prio {
+                current
+            } else {
+                previous
+            }
+        }) {
+            Some(div) => div,
+            None => {
+                return Err(MyError::Conflict(
+                    "The specified league is not accepting new team registrations".to_owned(),
+                ))
+            }
+        },
     };
 
-    for roster in db::get_rosters_for_user_id(&client, user.id)
+    for roster in db::teams::get_rosters_for_user_id(&transaction, user.id, &Visibility::All)
         .await?
         .into_iter()
     {
-        if roster.team.association_info.divisionid == div.id {
-            return Ok(HttpResponse::BadRequest()
-                .body("You are currently signed up to a team in this league"));
+        let divisionid = roster.team.association_info.divisionid;
+        if roster.user.ended_at.is_none() && divs.iter().any(|div| div.id == divisionid) {
+            return Err(MyError::Conflict(
+                "You are currently signed up to a team in this league".to_owned(),
+            ));
         }
     }
 
@@ -91,18 +143,20 @@ pub async fn post_team_to_league(
         teamid: team.id,
         is_private: new_team.is_private,
         roster_name: new_team.roster_name.clone(),
+        requested_divisionid: Some(div.id),
     };
 
-    let assoc = db::team_div_assocs::add_team_div_assoc(&client, final_assoc).await?;
+    let assoc = db::teams::add_team_div_assoc(&transaction, final_assoc).await?;
 
     db::teams::add_user_team_id(
-        &client,
+        &transaction,
         user.id,
         assoc.id,
         db::teams::UserTeamAffiliation::Leader,
     )
-    .await
-    .expect("should be able to add user team id");
+    .await?;
+
+    transaction.commit().await?;
 
     Ok(HttpResponse::Created().json(assoc))
 }
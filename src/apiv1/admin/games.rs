@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use actix_web::{get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use super::placements::require_league_admin;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::apiv1::HttpResult;
+use crate::db;
+use crate::db::leagues::Visibility;
+use crate::errors::MyError;
+use crate::models::{MercenaryStatus, MiniGame, PlacementStatus, Team};
+use crate::permission::UserPermission;
+use crate::ratings::{self, Outcome, Standing};
+use crate::AppState;
+
+impl Validate for MiniGame {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .optional_text("title", &mut self.title)
+            .length(1, 50)
+            .printable()
+            .allowed_name();
+        if self.teamhomeid == self.teamawayid {
+            check.error("teamawayid", "must not be the home team");
+        }
+        match (self.window_starts_at, self.window_ends_at) {
+            (Some(starts), Some(ends)) => {
+                if ends < starts {
+                    check.error("window_ends_at", "must not be before window_starts_at");
+                } else if self.played_at < starts || ends < self.played_at {
+                    check.error("played_at", "must be within the window");
+                }
+                if self
+                    .schedule_deadline
+                    .is_some_and(|deadline| ends < deadline)
+                {
+                    check.error("schedule_deadline", "must not be after window_ends_at");
+                }
+            }
+            (None, None) => {
+                if self.schedule_deadline.is_some() {
+                    check.error("schedule_deadline", "needs a window to schedule in");
+                }
+            }
+            _ => check.error("window_ends_at", "must be set along with window_starts_at"),
+        }
+    }
+}
+
+#[post("/api/v1/admin/games")]
+pub async fn post_game(
+    game: Valid<MiniGame>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/games");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateGame)?;
+
+    let game = game.into_inner();
+    let mut check = Checker::new(state.name_filter.as_ref());
+    match db::leagues::get_league_from_id(&client, game.leagueid).await {
+        Ok(_) => (),
+        Err(MyError::NotFound) => check.error("leagueid", "no such league"),
+        Err(err) => return Err(err),
+    }
+    for (field, teamid) in [
+        ("teamhomeid", game.teamhomeid),
+        ("teamawayid", game.teamawayid),
+    ] {
+        match db::teams::get_team_from_id(&client, teamid).await {
+            Ok(_) => (),
+            Err(MyError::NotFound) => check.error(field, "no such team"),
+            Err(err) => return Err(err),
+        }
+    }
+    check.finish()?;
+
+    let game = db::games::add_game(&client, &game).await?;
+    Ok(HttpResponse::Created().json(game))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GameResult {
+    pub home_score: i32,
+    pub away_score: i32,
+}
+
+impl Validate for GameResult {
+    fn validate(&mut self, check: &mut Checker) {
+        if self.home_score < 0 {
+            check.error("home_score", "must not be negative");
+        }
+        if self.away_score < 0 {
+            check.error("away_score", "must not be negative");
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Pairing {
+    pub home: Team,
+    pub away: Team,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SwissRound {
+    pub pairings: Vec<Pairing>,
+    /// The team that sits this round out, if there's an odd number of them.
+    pub bye: Option<Team>,
+}
+
+/// Who should play whom in the next round of a Swiss system in a division, seeded by the
+/// points from the league's confirmed games and then by rating. Doesn't schedule anything.
+#[get("/api/v1/admin/divisions/{division_id}/pairings")]
+pub async fn get_swiss_pairings(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    division_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("GET /api/v1/admin/divisions/{division_id}/pairings");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateGame)?;
+
+    let division = db::leagues::get_division_from_id(&client, *division_id).await?;
+    let mut teams: HashMap<i64, Team> =
+        db::teams::get_teams_for_div_ids(&client, &[division.id], &Visibility::All)
+            .await?
+            .into_iter()
+            .filter(|roster| {
+                roster.association_info.placement_status == PlacementStatus::Placed.as_str()
+            })
+            .map(|roster| (roster.team_info.id, roster.team_info))
+            .collect();
+
+    let mut points: HashMap<i64, f64> = teams.keys().map(|&teamid| (teamid, 0.0)).collect();
+    let mut played = Vec::new();
+    for game in db::games::get_games_for_league_id(&client, division.leagueid).await? {
+        played.push((game.teamhomeid, game.teamawayid));
+        let (Some(home_score), Some(away_score)) = (game.home_score, game.away_score) else {
+            continue;
+        };
+        for (teamid, ours, theirs) in [
+            (game.teamhomeid, home_score, away_score),
+            (game.teamawayid, away_score, home_score),
+        ] {
+            if let Some(points) = points.get_mut(&teamid) {
+                *points += Outcome::from_scores(ours, theirs).score();
+            }
+        }
+    }
+    let standings = teams
+        .values()
+        .map(|team| Standing {
+            teamid: team.id,
+            points: points[&team.id],
+            rating: team.rating,
+        })
+        .collect();
+
+    let round = ratings::swiss_pairings(standings, &played);
+    let mut take = |teamid| teams.remove(&teamid).ok_or(MyError::NotFound);
+    let mut pairings = Vec::with_capacity(round.pairings.len());
+    for (home, away) in round.pairings {
+        pairings.push(Pairing {
+            home: take(home)?,
+            away: take(away)?,
+        });
+    }
+    let bye = round.bye.map(&mut take).transpose()?;
+    Ok(HttpResponse::Ok().json(SwissRound { pairings, bye }))
+}
+
+/// Confirm the score of a game, and update the ratings of both teams and their players.
+///
+/// A result can only be confirmed once, since ratings build on each other.
+#[post("/api/v1/admin/games/{game_id}/result")]
+pub async fn post_game_result(
+    result: Valid<GameResult>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/games/{game_id}/result");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateGame)?;
+
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+    if game.confirmed_at.is_some() {
+        return Err(MyError::Conflict(
+            "This game's result was already confirmed".to_owned(),
+        ));
+    }
+
+    let transaction = client.transaction().await?;
+    let game =
+        db::games::confirm_result(&transaction, game.id, result.home_score, result.away_score)
+            .await?;
+    db::ratings::apply_game_result(&transaction, &game).await?;
+    transaction.commit().await?;
+
+    log::info!("Result of game {0} confirmed by {1}", game.id, user.id);
+    Ok(HttpResponse::Ok().json(game))
+}
+
+async fn review_mercenary(
+    state: &AppState,
+    session: &SessionToken,
+    request_id: i64,
+    status: MercenaryStatus,
+) -> HttpResult {
+    let client = crate::grab_pool(state).await?;
+
+    let user = session.user(&client).await?;
+    let request = db::lineups::get_mercenary_request_from_id(&client, request_id).await?;
+    let game = db::games::get_game_from_id(&client, request.gameid).await?;
+    require_league_admin(&client, &user, game.leagueid).await?;
+    if request.status != MercenaryStatus::Pending.as_str() {
+        return Err(MyError::Conflict(
+            "This request was already reviewed".to_owned(),
+        ));
+    }
+
+    let request =
+        db::lineups::review_mercenary_request(&client, request.id, status, user.id).await?;
+    log::info!(
+        "Mercenary request {0} {1} by {2}",
+        request.id,
+        request.status,
+        user.id
+    );
+    Ok(HttpResponse::Ok().json(request))
+}
+
+/// Let a team play the mercenary it asked for. Only league admins can.
+#[post("/api/v1/admin/mercenaries/{request_id}/approve")]
+pub async fn approve_mercenary(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    request_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/mercenaries/{request_id}/approve");
+    review_mercenary(&state, &session, *request_id, MercenaryStatus::Approved).await
+}
+
+#[post("/api/v1/admin/mercenaries/{request_id}/deny")]
+pub async fn deny_mercenary(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    request_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/mercenaries/{request_id}/deny");
+    review_mercenary(&state, &session, *request_id, MercenaryStatus::Denied).await
+}
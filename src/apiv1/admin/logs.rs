@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use actix_web::{post, web, HttpResponse};
+use deadpool_postgres::GenericClient;
+use futures::StreamExt;
+
+use crate::apiv1::games::GameStats;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::Checker;
+use crate::apiv1::HttpResult;
+use crate::db;
+use crate::errors::MyError;
+use crate::logstf::{self, Log, LogsError, SideError, SIDES};
+use crate::models::{Game, MercenaryStatus, MiniGameLog, User};
+use crate::permission::UserPermission;
+use crate::AppState;
+
+/// The biggest log we accept as an upload.
+const MAX_LOG_BYTES: usize = 4 * 1024 * 1024;
+
+/// Everyone who could have played for `teamid` in `game`: its lineup, or its roster when
+/// the game was played if it never declared one, and its approved mercenaries.
+async fn eligible_players(
+    client: &impl GenericClient,
+    game: &Game,
+    teamid: i64,
+) -> Result<Vec<i64>, MyError> {
+    let mut userids = db::lineups::get_player_ids_for_game(client, game, teamid).await?;
+    for request in db::lineups::get_mercenary_requests_for_game_id(client, game.id).await? {
+        if request.teamid == teamid
+            && request.status == MercenaryStatus::Approved.as_str()
+            && !userids.contains(&request.userid)
+        {
+            userids.push(request.userid);
+        }
+    }
+    Ok(userids)
+}
+
+/// Check `log` against the rosters of `game`'s teams, then store it and everyone's stats.
+/// Replaces whatever was imported for the game before.
+async fn import_log(
+    state: &AppState,
+    client: &mut deadpool_postgres::Client,
+    game: &Game,
+    log: Log,
+    logid: Option<i64>,
+    user: &User,
+) -> Result<GameStats, MyError> {
+    let sides = log.sides().map_err(|err| match err {
+        SideError::BadSteamId(key) => {
+            MyError::invalid_field(&format!("players.{key}"), "not a SteamID")
+        }
+        SideError::UnknownSide(key) => MyError::invalid_field(
+            &format!("players.{key}.team"),
+            format!("must be one of {SIDES:?}"),
+        ),
+        SideError::EmptySide(side) => {
+            MyError::Validation(format!("Nobody played on {side} in this log"))
+        }
+    })?;
+
+    let steamids: Vec<String> = sides
+        .iter()
+        .flatten()
+        .map(|player| player.steamid.to_string())
+        .collect();
+    let users: HashMap<String, i64> = db::users::mass_get_users_from_steamids(&*client, &steamids)
+        .await?
+        .into_iter()
+        .map(|user| (user.steamid, user.id))
+        .collect();
+    let home = eligible_players(&*client, game, game.teamhomeid).await?;
+    let away = eligible_players(&*client, game, game.teamawayid).await?;
+
+    // Whichever way round puts more players on their own team's roster.
+    let count = |side: usize, members: &[i64]| {
+        sides[side]
+            .iter()
+            .filter_map(|player| users.get(&player.steamid.to_string()))
+            .filter(|userid| members.contains(userid))
+            .count()
+    };
+    let home_red = count(0, &home) + count(1, &away);
+    let home_blue = count(0, &away) + count(1, &home);
+    let teamids = match home_red.cmp(&home_blue) {
+        std::cmp::Ordering::Greater => [game.teamhomeid, game.teamawayid],
+        std::cmp::Ordering::Less => [game.teamawayid, game.teamhomeid],
+        std::cmp::Ordering::Equal => {
+            return Err(MyError::Validation(
+                "The teams in this log don't match the game's rosters".to_owned(),
+            ))
+        }
+    };
+
+    let mut check = Checker::new(state.name_filter.as_ref());
+    let mut players = Vec::with_capacity(steamids.len());
+    for (side, side_players) in sides.iter().enumerate() {
+        let members = match teamids[side] == game.teamhomeid {
+            true => &home,
+            false => &away,
+        };
+        for player in side_players {
+            let field = format!("players.{0}", player.key);
+            match users.get(&player.steamid.to_string()) {
+                None => check.error(&field, "not a registered user"),
+                Some(userid) if !members.contains(userid) => check.error(
+                    &field,
+                    format!("not on the roster of the team that played {0}", SIDES[side]),
+                ),
+                Some(userid) => players.push((*userid, teamids[side], player.stats)),
+            }
+        }
+    }
+    check.finish()?;
+
+    let transaction = client.transaction().await?;
+    db::teams::lock_teams(&transaction, &[game.teamhomeid, game.teamawayid]).await?;
+    let game_log = db::stats::replace_log(
+        &transaction,
+        &MiniGameLog {
+            gameid: game.id,
+            logid,
+            map: log.info.map.clone(),
+            title: log.info.title.clone(),
+            red_teamid: teamids[0],
+            blue_teamid: teamids[1],
+            imported_by: user.id,
+        },
+    )
+    .await?;
+    let mut stats = Vec::with_capacity(players.len());
+    for (userid, teamid, player) in players {
+        stats.push(
+            db::stats::add_player_stats(
+                &transaction,
+                game.id,
+                userid,
+                teamid,
+                &player.passtime,
+                player.deaths,
+            )
+            .await?,
+        );
+    }
+    db::stats::refresh_rollups(
+        &transaction,
+        game.leagueid,
+        &[game.teamhomeid, game.teamawayid],
+    )
+    .await?;
+    transaction.commit().await?;
+
+    log::info!(
+        "Imported a log with {0} players for game {1}",
+        stats.len(),
+        game.id
+    );
+    Ok(GameStats {
+        log: Some(game_log),
+        players: stats,
+    })
+}
+
+/// Import the stats of a game from a log uploaded as the raw request body.
+#[post("/api/v1/admin/games/{game_id}/log")]
+pub async fn post_game_log(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+    mut payload: web::Payload,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/games/{game_id}/log");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateGame)?;
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+
+    let mut bytes = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| MyError::Validation(err.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_LOG_BYTES {
+            return Err(MyError::invalid_field(
+                "log",
+                format!("must be at most {0} MiB", MAX_LOG_BYTES / 1024 / 1024),
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let log = logstf::parse_log(&bytes)
+        .map_err(|err| MyError::invalid_field("log", format!("isn't a logs.tf log: {err}")))?;
+
+    let stats = import_log(&state, &mut client, &game, log, None, &user).await?;
+    Ok(HttpResponse::Created().json(stats))
+}
+
+/// Import the stats of a game from a log on logs.tf.
+#[post("/api/v1/admin/games/{game_id}/log/{log_id}")]
+pub async fn post_game_log_from_logstf(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<(i64, i64)>,
+) -> HttpResult {
+    let (game_id, log_id) = path.into_inner();
+    log::info!("POST /api/v1/admin/games/{game_id}/log/{log_id}");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateGame)?;
+    let game = db::games::get_game_from_id(&client, game_id).await?;
+
+    let log = match state.logs.get_log(log_id).await {
+        Ok(log) => log,
+        Err(LogsError::NotFound) => {
+            return Err(MyError::invalid_field("log_id", "logs.tf has no such log"))
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let stats = import_log(&state, &mut client, &game, log, Some(log_id), &user).await?;
+    Ok(HttpResponse::Created().json(stats))
+}
@@ -2,9 +2,11 @@ use std::str::FromStr;
 
 use actix_http::header::TryIntoHeaderValue;
 use actix_web::http::header;
-use actix_web::{http, post, web, Error, HttpResponse};
+use actix_web::{delete, http, patch, post, web, Error, HttpResponse};
 use derive_more::derive::{Debug, Display};
 
+use super::session::SessionToken;
+use super::validation::{Checker, Valid, Validate};
 use super::HttpResult;
 use crate::apiv1::apimodels::*;
 use crate::db;
@@ -14,6 +16,13 @@ use crate::permission::UserPermission;
 use crate::AppState;
 use deadpool_postgres::Client;
 
+pub mod games;
+pub mod logs;
+pub mod placements;
+pub mod review_flags;
+pub mod seasons;
+pub mod tickets;
+
 #[derive(Debug, Display)]
 pub struct AuthHeader(pub String);
 
@@ -58,38 +67,102 @@ impl http::header::Header for AuthHeader {
 
 //     let client: Client = state.pool.get().await.map_err(MyError::PoolError)?;
 
-//     let new_user = db::add_user(&client, user_info).await?;
+//     let new_user = db::users::add_user(&client, user_info).await?;
 
 //     Ok(HttpResponse::Created().json(new_user))
 // }
 
+#[derive(serde::Deserialize, Debug)]
+pub struct NewLeague {
+    #[serde(flatten)]
+    pub league: MiniLeague,
+    /// Names of divisions to create along with the league.
+    #[serde(default)]
+    pub divisions: Vec<String>,
+}
+
+impl Validate for NewLeague {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .text("name", &mut self.league.name)
+            .length(1, 50)
+            .printable()
+            .allowed_name();
+        for (i, name) in self.divisions.iter_mut().enumerate() {
+            check
+                .text(&format!("divisions[{i}]"), name)
+                .length(1, 50)
+                .printable()
+                .allowed_name();
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct LeagueWithDivisions {
+    #[serde(flatten)]
+    pub league: League,
+    pub divisions: Vec<Division>,
+}
+
 #[post("/api/v1/admin/leagues")]
 pub async fn post_league(
-    league: web::Json<MiniLeague>,
+    league: Valid<NewLeague>,
     state: web::Data<AppState>,
-    authorization: web::Header<AuthHeader>,
+    session: SessionToken,
 ) -> HttpResult {
     log::info!("POST /api/v1/leagues");
-    log::debug!("Authorization header: {0}", authorization.0 .0);
+    log::debug!("Session token: {0}", session.0);
 
     log::trace!("Grabbing pool");
-    let client = state.pool.get().await.unwrap();
+    let mut client = crate::grab_pool(&state).await?;
 
-    let user = match db::get_user_from_auth_token(&client, &authorization.0 .0).await {
-        Ok(user) => user,
-        Err(_) => return Ok(HttpResponse::Unauthorized().body("Error processing permissions")),
-    };
+    let user = session.user(&client).await?;
 
-    // if not admin / can't create league
-    if !user.admin_or_perm(UserPermission::CreateLeague) {
-        return Ok(HttpResponse::Forbidden().body("Insufficient permissions"));
-    }
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
 
     // Actually create the new league
     log::info!("Authorization succeeded, creating a new league");
-    let league = league.into_inner();
-    log::debug!("Adding league from: {0:?}", league);
-    let response = db::add_league(&client, league).await?;
+    let NewLeague { league, divisions } = league.into_inner();
+    if let Some(seasonid) = league.seasonid {
+        match db::seasons::get_season_from_id(&client, seasonid).await {
+            Ok(season) if season.status() == SeasonStatus::Completed => {
+                return Err(MyError::invalid_field(
+                    "seasonid",
+                    "this season has been archived",
+                ))
+            }
+            Ok(_) => (),
+            Err(MyError::NotFound) => {
+                return Err(MyError::invalid_field("seasonid", "no such season"))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    log::debug!(
+        "Adding league from: {0:?}, divisions {1:?}",
+        league,
+        divisions
+    );
+
+    // Either the league and all its divisions get made, or none of them do.
+    let transaction = client.transaction().await?;
+    let league = db::leagues::add_league(&transaction, league).await?;
+
+    let mut new_divisions = Vec::with_capacity(divisions.len());
+    for name in divisions {
+        let division = MiniDivision {
+            leagueid: league.id,
+            name,
+        };
+        new_divisions.push(db::leagues::add_division(&transaction, division).await?);
+    }
+    transaction.commit().await?;
+
+    let response = LeagueWithDivisions {
+        league,
+        divisions: new_divisions,
+    };
     log::trace!("OK response, {response:?}");
 
     Ok(HttpResponse::Created().json(response))
@@ -101,37 +174,258 @@ pub struct MiniDivision {
     pub name: String,
 }
 
+impl Validate for MiniDivision {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .text("name", &mut self.name)
+            .length(1, 50)
+            .printable()
+            .allowed_name();
+    }
+}
+
 #[post("/api/v1/admin/divisions")]
 pub async fn post_league_divisions(
-    division: web::Json<MiniDivision>,
+    division: Valid<MiniDivision>,
     state: web::Data<AppState>,
-    auth: web::Header<AuthHeader>,
+    session: SessionToken,
 ) -> HttpResult {
     log::info!("POST /api/v1/divisions");
-    log::debug!("Authorization header: {0}", auth.0 .0);
+    log::debug!("Session token: {0}", session.0);
 
     log::trace!("Grabbing pool");
     let client = crate::grab_pool(&state).await?;
 
-    let user = match db::get_user_from_auth_token(&client, &auth.0 .0).await {
-        Ok(user) => user,
-        Err(_) => return Ok(HttpResponse::Unauthorized().body("Error processing permissions")),
-    };
+    let user = session.user(&client).await?;
 
-    // if not admin / can't create div
-    if !user.admin_or_perm(UserPermission::CreateLeague) {
-        return Ok(HttpResponse::Forbidden().body("Insufficient permissions"));
-    }
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
 
     // Actually create the new div
     log::info!("Authorization succeeded, creating a new division");
     let division = division.into_inner();
+    match db::leagues::get_league_from_id(&client, division.leagueid).await {
+        Ok(league) if league.archived_at.is_some() => {
+            return Err(MyError::Conflict(
+                "This league has been archived".to_owned(),
+            ))
+        }
+        Ok(_) => (),
+        Err(MyError::NotFound) => return Err(MyError::invalid_field("leagueid", "no such league")),
+        Err(err) => return Err(err),
+    }
     log::debug!("Adding division: {0:?}", division);
-    let response = db::divisions::add_division(&client, division).await?;
+    let response = db::leagues::add_division(&client, division).await?;
     log::trace!("OK response, {response:?}");
 
     Ok(HttpResponse::Created().json(response))
 }
+/// Fields left out aren't changed.
+#[derive(serde::Deserialize, Debug)]
+pub struct LeaguePatch {
+    pub name: Option<String>,
+    pub accepting_teams: Option<bool>,
+    pub is_hidden: Option<bool>,
+    /// Archive the league, or bring it back.
+    pub archived: Option<bool>,
+}
+
+impl Validate for LeaguePatch {
+    fn validate(&mut self, check: &mut Checker) {
+        if let Some(name) = &mut self.name {
+            check
+                .text("name", name)
+                .length(1, 50)
+                .printable()
+                .allowed_name();
+        }
+    }
+}
+
+#[patch("/api/v1/admin/leagues/{league_id}")]
+pub async fn patch_league(
+    patch: Valid<LeaguePatch>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    league_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("PATCH /api/v1/admin/leagues/{league_id}");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let mut league = db::leagues::get_league_from_id(&client, *league_id).await?;
+    let patch = patch.into_inner();
+    if let Some(name) = patch.name {
+        league.name = name;
+    }
+    if let Some(is_hidden) = patch.is_hidden {
+        league.is_hidden = is_hidden;
+    }
+    if let Some(accepting_teams) = patch.accepting_teams {
+        league.accepting_teams = accepting_teams;
+    }
+    match patch.archived {
+        Some(true) if league.archived_at.is_none() => {
+            league.archived_at = Some(chrono::offset::Utc::now());
+            league.accepting_teams = false;
+        }
+        Some(false) if league.archived_at.is_some() => {
+            if let Some(seasonid) = league.seasonid {
+                let season = db::seasons::get_season_from_id(&client, seasonid).await?;
+                if season.status() == SeasonStatus::Completed {
+                    return Err(MyError::Conflict(
+                        "This league's season has been archived".to_owned(),
+                    ));
+                }
+            }
+            league.archived_at = None;
+        }
+        _ => (),
+    }
+    if league.archived_at.is_some() && patch.accepting_teams == Some(true) {
+        return Err(MyError::Conflict(
+            "An archived league can't take sign ups".to_owned(),
+        ));
+    }
+
+    let league = db::leagues::update_league(&client, &league).await?;
+    Ok(HttpResponse::Ok().json(league))
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DeleteParams {
+    /// Also delete anything that still refers to it.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Refuse to delete something that games or rosters still refer to, unless forced.
+fn check_unreferenced(force: bool, games: i64, rosters: i64) -> Result<(), MyError> {
+    if force || games + rosters == 0 {
+        return Ok(());
+    }
+    Err(MyError::Conflict(format!(
+        "Still used by {games} games and {rosters} rosters, pass force=true to delete them too"
+    )))
+}
+
+#[delete("/api/v1/admin/leagues/{league_id}")]
+pub async fn delete_league(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    league_id: web::Path<i64>,
+    params: web::Query<DeleteParams>,
+) -> HttpResult {
+    log::info!("DELETE /api/v1/admin/leagues/{league_id}");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let league = db::leagues::get_league_from_id(&client, *league_id).await?;
+    let divids: Vec<i64> = db::leagues::get_divs_for_league_id(&client, league.id)
+        .await?
+        .iter()
+        .map(|div| div.id)
+        .collect();
+    check_unreferenced(
+        params.force,
+        db::leagues::count_games_for_league_id(&client, league.id).await?,
+        db::leagues::count_rosters_for_div_ids(&client, &divids).await?,
+    )?;
+
+    let transaction = client.transaction().await?;
+    // The demos would go with their games anyway, but their files wouldn't.
+    let demos = db::demos::delete_demos_for_league_id(&transaction, league.id).await?;
+    db::leagues::delete_league(&transaction, league.id).await?;
+    transaction.commit().await?;
+    crate::demos::remove_files(&state.demo_dir, &demos).await;
+
+    log::info!("League {0} deleted by {1}", league.id, user.id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Fields left out aren't changed.
+#[derive(serde::Deserialize, Debug)]
+pub struct DivisionPatch {
+    pub name: Option<String>,
+    /// New sign ups go to the division with the highest `prio`.
+    pub prio: Option<i32>,
+}
+
+impl Validate for DivisionPatch {
+    fn validate(&mut self, check: &mut Checker) {
+        if let Some(name) = &mut self.name {
+            check
+                .text("name", name)
+                .length(1, 50)
+                .printable()
+                .allowed_name();
+        }
+    }
+}
+
+#[patch("/api/v1/admin/divisions/{division_id}")]
+pub async fn patch_division(
+    patch: Valid<DivisionPatch>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    division_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("PATCH /api/v1/admin/divisions/{division_id}");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let mut division = db::leagues::get_division_from_id(&client, *division_id).await?;
+    let league = db::leagues::get_league_from_id(&client, division.leagueid).await?;
+    if league.archived_at.is_some() {
+        return Err(MyError::Conflict(
+            "This league has been archived".to_owned(),
+        ));
+    }
+    let patch = patch.into_inner();
+    if let Some(name) = patch.name {
+        division.name = name;
+    }
+    if let Some(prio) = patch.prio {
+        division.prio = prio;
+    }
+
+    let division = db::leagues::update_division(&client, &division).await?;
+    Ok(HttpResponse::Ok().json(division))
+}
+
+#[delete("/api/v1/admin/divisions/{division_id}")]
+pub async fn delete_division(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    division_id: web::Path<i64>,
+    params: web::Query<DeleteParams>,
+) -> HttpResult {
+    log::info!("DELETE /api/v1/admin/divisions/{division_id}");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let division = db::leagues::get_division_from_id(&client, *division_id).await?;
+    // Games belong to the league, so only rosters keep a division around.
+    check_unreferenced(
+        params.force,
+        0,
+        db::leagues::count_rosters_for_div_ids(&client, &[division.id]).await?,
+    )?;
+
+    let transaction = client.transaction().await?;
+    db::leagues::delete_divisions(&transaction, &[division.id]).await?;
+    transaction.commit().await?;
+
+    log::info!("Division {0} deleted by {1}", division.id, user.id);
+    Ok(HttpResponse::NoContent().finish())
+}
 // /// Set a user or multiple users to a team.
 // #[post("/api/v1/admin/setuserteam")]
 // pub async fn post_users_team(
@@ -142,7 +436,7 @@ pub async fn post_league_divisions(
 //     let client = state.pool.get().await.map_err(MyError::PoolError)?;
 //     let user_team = user_team.into_inner();
 //     // fetch the team to get its id
-//     let team = db::get_team_from_id(&client, user_team.team_id).await?;
+//     let team = db::teams::get_team_from_id(&client, user_team.team_id).await?;
 //     todo!();
 //     Ok(HttpResponse::Ok().finish())
 // }
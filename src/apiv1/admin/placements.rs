@@ -0,0 +1,164 @@
+use actix_web::{get, post, web, HttpResponse};
+use deadpool_postgres::GenericClient;
+use serde::Deserialize;
+
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::apiv1::HttpResult;
+use crate::db;
+use crate::db::leagues::Visibility;
+use crate::errors::MyError;
+use crate::models::{PlacementStatus, User};
+use crate::permission::UserPermission;
+use crate::AppState;
+
+/// Anyone who can manage leagues, or who runs a division in league `leagueid`.
+pub(in crate::apiv1) async fn require_league_admin(
+    client: &impl GenericClient,
+    user: &User,
+    leagueid: i64,
+) -> Result<(), MyError> {
+    if user.admin_or_perm(UserPermission::CreateLeague) {
+        return Ok(());
+    }
+    let leagueids = db::leagues::get_admin_league_ids_for_user_id(client, user.id).await?;
+    match leagueids.contains(&leagueid) {
+        true => Ok(()),
+        false => Err(MyError::Forbidden("You don't run this league")),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PlacementParams {
+    /// List the highest rated teams first, instead of the earliest sign ups.
+    #[serde(default)]
+    pub by_rating: bool,
+}
+
+/// Every roster in the league still waiting to be placed, oldest sign up first.
+#[get("/api/v1/admin/leagues/{league_id}/placements")]
+pub async fn get_pending_placements(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    league_id: web::Path<i64>,
+    params: web::Query<PlacementParams>,
+) -> HttpResult {
+    log::info!("GET /api/v1/admin/leagues/{league_id}/placements");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let league = db::leagues::get_league_from_id(&client, *league_id).await?;
+    require_league_admin(&client, &user, league.id).await?;
+
+    let divids: Vec<i64> = db::leagues::get_divs_for_league_id(&client, league.id)
+        .await?
+        .iter()
+        .map(|div| div.id)
+        .collect();
+    let mut pending = db::teams::get_teams_for_div_ids(&client, &divids, &Visibility::All).await?;
+    pending.retain(|roster| {
+        roster.association_info.placement_status == PlacementStatus::Pending.as_str()
+    });
+    pending.sort_by_key(|roster| roster.association_info.created_at);
+    if params.by_rating {
+        pending.sort_by(|a, b| b.team_info.rating.total_cmp(&a.team_info.rating));
+    }
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Placement {
+    pub teamdivid: i64,
+    pub divisionid: i64,
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Placements {
+    pub placements: Vec<Placement>,
+}
+
+impl Validate for Placements {
+    fn validate(&mut self, check: &mut Checker) {
+        if self.placements.is_empty() {
+            check.error("placements", "must not be empty");
+        }
+        for (i, placement) in self.placements.iter_mut().enumerate() {
+            check
+                .optional_text(&format!("placements[{i}].note"), &mut placement.note)
+                .length(1, 500)
+                .printable();
+        }
+    }
+}
+
+/// Place any number of the league's rosters in their final divisions, all at once.
+///
+/// Rosters that were placed already can be moved again.
+#[post("/api/v1/admin/leagues/{league_id}/placements")]
+pub async fn post_placements(
+    body: Valid<Placements>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    league_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/leagues/{league_id}/placements");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let league = db::leagues::get_league_from_id(&client, *league_id).await?;
+    require_league_admin(&client, &user, league.id).await?;
+
+    let divids: Vec<i64> = db::leagues::get_divs_for_league_id(&client, league.id)
+        .await?
+        .iter()
+        .map(|div| div.id)
+        .collect();
+    let rosters = db::teams::get_teams_for_div_ids(&client, &divids, &Visibility::All).await?;
+
+    let Placements { placements } = body.into_inner();
+    let mut check = Checker::new(state.name_filter.as_ref());
+    for (i, placement) in placements.iter().enumerate() {
+        if !rosters
+            .iter()
+            .any(|roster| roster.association_info.id == placement.teamdivid)
+        {
+            check.error(
+                &format!("placements[{i}].teamdivid"),
+                "not a roster in this league",
+            );
+        }
+        if !divids.contains(&placement.divisionid) {
+            check.error(
+                &format!("placements[{i}].divisionid"),
+                "not a division in this league",
+            );
+        }
+    }
+    check.finish()?;
+
+    let transaction = client.transaction().await?;
+    let mut placed = Vec::with_capacity(placements.len());
+    for placement in placements {
+        placed.push(
+            db::teams::place_roster(
+                &transaction,
+                placement.teamdivid,
+                placement.divisionid,
+                placement.note.as_deref(),
+                user.id,
+            )
+            .await?,
+        );
+    }
+    transaction.commit().await?;
+
+    log::info!(
+        "{0} rosters in league {1} placed by {2}",
+        placed.len(),
+        league.id,
+        user.id
+    );
+    Ok(HttpResponse::Ok().json(placed))
+}
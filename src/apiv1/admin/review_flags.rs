@@ -0,0 +1,39 @@
+use actix_web::{get, post, web, HttpResponse};
+
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::HttpResult;
+use crate::db;
+use crate::errors::MyError;
+use crate::permission::UserPermission;
+use crate::AppState;
+
+/// Every account flagged by the eligibility checks that hasn't been reviewed yet.
+#[get("/api/v1/admin/reviewflags")]
+pub async fn get_review_flags(state: web::Data<AppState>, session: SessionToken) -> HttpResult {
+    log::info!("GET /api/v1/admin/reviewflags");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::ReviewUsers)?;
+
+    let flags = db::users::get_open_review_flags(&client).await?;
+    Ok(HttpResponse::Ok().json(flags))
+}
+
+/// Mark a flagged account as looked at.
+/// Any action to take against the account itself is up to the reviewer.
+#[post("/api/v1/admin/reviewflags/{flag_id}/reviewed")]
+pub async fn post_review_flag_reviewed(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    flag_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/reviewflags/{flag_id}/reviewed");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::ReviewUsers)?;
+
+    let flag = db::users::mark_review_flag_reviewed(&client, *flag_id, user.id).await?;
+    Ok(HttpResponse::Ok().json(flag))
+}
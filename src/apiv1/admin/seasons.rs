@@ -0,0 +1,191 @@
+use actix_web::{patch, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::apiv1::seasons::SeasonReturn;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::apiv1::HttpResult;
+use crate::db;
+use crate::db::leagues::Visibility;
+use crate::errors::MyError;
+use crate::models::{MiniSeason, SeasonStatus};
+use crate::permission::UserPermission;
+use crate::AppState;
+
+impl Validate for MiniSeason {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .text("name", &mut self.name)
+            .length(1, 50)
+            .printable()
+            .allowed_name();
+        check_dates(check, self);
+    }
+}
+
+/// Each range has to end after it starts.
+fn check_dates(check: &mut Checker, season: &MiniSeason) {
+    if let (Some(starts), Some(ends)) = (season.starts_at, season.ends_at) {
+        if ends < starts {
+            check.error("ends_at", "must be after starts_at");
+        }
+    }
+    if let (Some(opens), Some(closes)) =
+        (season.registration_opens_at, season.registration_closes_at)
+    {
+        if closes < opens {
+            check.error(
+                "registration_closes_at",
+                "must be after registration_opens_at",
+            );
+        }
+    }
+}
+
+#[post("/api/v1/admin/seasons")]
+pub async fn post_season(
+    season: Valid<MiniSeason>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/seasons");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let season = db::seasons::add_season(&client, &season).await?;
+    Ok(HttpResponse::Created().json(season))
+}
+
+/// Fields left out aren't changed.
+#[derive(Deserialize, Debug)]
+pub struct SeasonPatch {
+    pub name: Option<String>,
+    pub status: Option<SeasonStatus>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub registration_opens_at: Option<DateTime<Utc>>,
+    pub registration_closes_at: Option<DateTime<Utc>>,
+}
+
+impl Validate for SeasonPatch {
+    fn validate(&mut self, check: &mut Checker) {
+        if let Some(name) = &mut self.name {
+            check
+                .text("name", name)
+                .length(1, 50)
+                .printable()
+                .allowed_name();
+        }
+    }
+}
+
+/// Rename a season, move its dates, or move it on to its next status.
+///
+/// Moving into registration opens sign ups to every league in the season,
+/// and moving past it closes them again. Completing a season archives it.
+#[patch("/api/v1/admin/seasons/{season_id}")]
+pub async fn patch_season(
+    patch: Valid<SeasonPatch>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    season_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("PATCH /api/v1/admin/seasons/{season_id}");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let season = db::seasons::get_season_from_id(&client, *season_id).await?;
+    let current = season.status();
+    if current == SeasonStatus::Completed {
+        return Err(MyError::Conflict(
+            "This season has been archived".to_owned(),
+        ));
+    }
+
+    let patch = patch.into_inner();
+    let status = patch.status.unwrap_or(current);
+    if status < current {
+        return Err(MyError::Conflict(format!(
+            "A season can't go back from {0} to {1}",
+            current.as_str(),
+            status.as_str()
+        )));
+    }
+
+    let mut updated = season.mini();
+    if let Some(name) = patch.name {
+        updated.name = name;
+    }
+    updated.starts_at = patch.starts_at.or(updated.starts_at);
+    updated.ends_at = patch.ends_at.or(updated.ends_at);
+    updated.registration_opens_at = patch
+        .registration_opens_at
+        .or(updated.registration_opens_at);
+    updated.registration_closes_at = patch
+        .registration_closes_at
+        .or(updated.registration_closes_at);
+
+    let mut check = Checker::new(state.name_filter.as_ref());
+    check_dates(&mut check, &updated);
+    check.finish()?;
+
+    let transaction = client.transaction().await?;
+    let mut season = db::seasons::update_season(&transaction, season.id, &updated, status).await?;
+    let registering = status == SeasonStatus::Registration;
+    if registering != (current == SeasonStatus::Registration) {
+        db::leagues::set_accepting_teams_for_season_id(&transaction, season.id, registering)
+            .await?;
+    }
+    // A completed season is archived, along with every league in it.
+    if status == SeasonStatus::Completed {
+        let now = chrono::offset::Utc::now();
+        season = db::seasons::set_completed_at(&transaction, season.id, now).await?;
+        db::leagues::archive_leagues_for_season_id(&transaction, season.id, now).await?;
+    }
+    transaction.commit().await?;
+
+    if status != current {
+        log::info!(
+            "Season {0} moved from {1} to {2} by {3}",
+            season.id,
+            current.as_str(),
+            status.as_str(),
+            user.id
+        );
+    }
+    Ok(HttpResponse::Ok().json(season))
+}
+
+/// Start a new draft season, with a copy of every league and division of this one.
+#[post("/api/v1/admin/seasons/{season_id}/clone")]
+pub async fn clone_season(
+    new_season: Valid<MiniSeason>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    season_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/seasons/{season_id}/clone");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    user.require_admin_or_perm(UserPermission::CreateLeague)?;
+
+    let from = db::seasons::get_season_from_id(&client, *season_id).await?;
+
+    let transaction = client.transaction().await?;
+    let season = db::seasons::add_season(&transaction, &new_season).await?;
+    let leagues = db::seasons::clone_season_structure(&transaction, &from, season.id).await?;
+    let leagues =
+        db::leagues::get_league_trees(&transaction, leagues, false, &Visibility::All).await?;
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Created().json(SeasonReturn {
+        info: season,
+        leagues,
+    }))
+}
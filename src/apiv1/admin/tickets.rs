@@ -0,0 +1,180 @@
+use actix_web::{post, web, HttpResponse};
+use serde::Deserialize;
+
+use super::placements::require_league_admin;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::apiv1::HttpResult;
+use crate::db;
+use crate::models::{TicketOutcome, TicketStatus};
+use crate::AppState;
+
+/// What the other team is awarded when a team forfeits, to its 0.
+const FORFEIT_WIN_SCORE: i32 = 1;
+
+#[derive(Deserialize, Debug)]
+pub struct StatusChange {
+    pub status: TicketStatus,
+}
+
+impl Validate for StatusChange {
+    fn validate(&mut self, check: &mut Checker) {
+        if self.status == TicketStatus::Resolved {
+            check.error("status", "resolve tickets with their resolve route instead");
+        }
+    }
+}
+
+/// Move a ticket to review, close it, or reopen it. Resolved tickets can't be moved.
+#[post("/api/v1/admin/tickets/{ticket_id}/status")]
+pub async fn post_ticket_status(
+    body: Valid<StatusChange>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    ticket_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/tickets/{ticket_id}/status");
+    let client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let ticket = db::tickets::get_ticket_from_id(&client, *ticket_id).await?;
+    require_league_admin(&client, &user, ticket.leagueid).await?;
+
+    let ticket = db::tickets::set_ticket_status(&client, ticket.id, body.status).await?;
+    log::info!(
+        "Ticket {0} moved to {1} by {2}",
+        ticket.id,
+        ticket.status,
+        user.id
+    );
+    Ok(HttpResponse::Ok().json(ticket))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Resolution {
+    pub outcome: TicketOutcome,
+    pub note: Option<String>,
+    /// The game's new score, for a `result_override`.
+    pub home_score: Option<i32>,
+    pub away_score: Option<i32>,
+    /// The team that forfeited the game, for a `forfeit`.
+    pub forfeiting_teamid: Option<i64>,
+}
+
+impl Validate for Resolution {
+    fn validate(&mut self, check: &mut Checker) {
+        check.optional_text("note", &mut self.note).length(1, 2000);
+        match self.outcome {
+            TicketOutcome::ResultOverride => {
+                for (field, score) in [
+                    ("home_score", self.home_score),
+                    ("away_score", self.away_score),
+                ] {
+                    match score {
+                        None => check.error(field, "must be set to override the result"),
+                        Some(score) if score < 0 => check.error(field, "must not be negative"),
+                        Some(_) => (),
+                    }
+                }
+            }
+            TicketOutcome::Forfeit if self.forfeiting_teamid.is_none() => {
+                check.error("forfeiting_teamid", "must be set for a forfeit");
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Resolve a ticket with an outcome, and carry it out.
+///
+/// A `result_override` or `forfeit` changes the score of the ticket's game and confirms it,
+/// rating it again if it was already confirmed. A `ban` bans the reported player.
+#[post("/api/v1/admin/tickets/{ticket_id}/resolve")]
+pub async fn post_ticket_resolution(
+    body: Valid<Resolution>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    ticket_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/admin/tickets/{ticket_id}/resolve");
+    let mut client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let ticket = db::tickets::get_ticket_from_id(&client, *ticket_id).await?;
+    require_league_admin(&client, &user, ticket.leagueid).await?;
+
+    let body = body.into_inner();
+    let transaction = client.transaction().await?;
+    // Locked first, so the result being overridden is the one the game still has.
+    let game = match ticket.gameid {
+        Some(gameid) => {
+            db::games::lock_game(&transaction, gameid).await?;
+            Some(db::games::get_game_from_id(&transaction, gameid).await?)
+        }
+        None => None,
+    };
+    let mut check = Checker::new(state.name_filter.as_ref());
+    let score = match (body.outcome, &game) {
+        (TicketOutcome::ResultOverride, Some(_)) => body.home_score.zip(body.away_score),
+        (TicketOutcome::Forfeit, Some(game)) => match body.forfeiting_teamid {
+            Some(teamid) if teamid == game.teamhomeid => Some((0, FORFEIT_WIN_SCORE)),
+            Some(teamid) if teamid == game.teamawayid => Some((FORFEIT_WIN_SCORE, 0)),
+            _ => {
+                check.error("forfeiting_teamid", "not a team in the ticket's game");
+                None
+            }
+        },
+        (TicketOutcome::ResultOverride | TicketOutcome::Forfeit, None) => {
+            check.error("outcome", "needs a ticket about a game");
+            None
+        }
+        _ => None,
+    };
+    let banned = match body.outcome {
+        TicketOutcome::Ban => match ticket.reported_userid {
+            Some(userid) => Some(userid),
+            None => {
+                check.error("outcome", "needs a ticket that reports a player");
+                None
+            }
+        },
+        _ => None,
+    };
+    check.finish()?;
+
+    let ticket = db::tickets::resolve_ticket(
+        &transaction,
+        ticket.id,
+        body.outcome,
+        body.note.as_deref(),
+        user.id,
+    )
+    .await?;
+    if let (Some(game), Some((home_score, away_score))) = (&game, score) {
+        if game.confirmed_at.is_some() {
+            db::ratings::reverse_game_result(&transaction, game).await?;
+        }
+        let overridden =
+            db::games::override_result(&transaction, game.id, home_score, away_score).await?;
+        db::ratings::apply_game_result(&transaction, &overridden).await?;
+    }
+    if let Some(userid) = banned {
+        db::users::add_ban(
+            &transaction,
+            userid,
+            ticket.leagueid,
+            Some(ticket.id),
+            user.id,
+        )
+        .await?;
+    }
+    transaction.commit().await?;
+
+    log::info!(
+        "Ticket {0} resolved with {1} by {2}",
+        ticket.id,
+        body.outcome.as_str(),
+        user.id
+    );
+    Ok(HttpResponse::Ok().json(ticket))
+}
@@ -0,0 +1,261 @@
+//! STV demos teams upload for their games, so disputes can be settled.
+//!
+//! Demos are kept out of the public `/uploads`: only the players of either team and the
+//! league's admins can download them.
+use actix_files::NamedFile;
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use deadpool_postgres::GenericClient;
+use futures::StreamExt;
+use randomizer::Randomizer;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use super::admin::placements::require_league_admin;
+use super::games::{affiliation, managing_team};
+use super::HttpResult;
+use crate::apiv1::grab_pool;
+use crate::apiv1::session::SessionToken;
+use crate::db;
+use crate::errors::MyError;
+use crate::models::{Game, GameDemo, MiniGameDemo, User};
+use crate::AppState;
+
+/// How many demos each team can upload for one game.
+const MAX_DEMOS_PER_TEAM: i64 = 4;
+/// Every Source demo starts with this.
+const DEMO_MAGIC: &[u8] = b"HL2DEMO\0";
+
+/// Only players on either team and the league's admins can see a game's demos.
+async fn require_demo_access(
+    client: &impl GenericClient,
+    user: &User,
+    game: &Game,
+) -> Result<(), MyError> {
+    for teamid in [game.teamhomeid, game.teamawayid] {
+        if affiliation(client, user, game, teamid).await?.is_some() {
+            return Ok(());
+        }
+    }
+    match require_league_admin(client, user, game.leagueid).await {
+        Err(MyError::Forbidden(_)) => Err(MyError::Forbidden(
+            "Only players of either team and league admins can see this game's demos",
+        )),
+        other => other,
+    }
+}
+
+/// The name to keep for an uploaded file, without any directories.
+fn clean_file_name(filename: Option<&str>) -> String {
+    let name = filename
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .map(|name| {
+            name.trim()
+                .chars()
+                .filter(|c| !c.is_control())
+                .collect::<String>()
+        })
+        .unwrap_or_default();
+    match name.is_empty() {
+        true => "demo.dem".to_owned(),
+        false => name.chars().take(100).collect(),
+    }
+}
+
+#[get("/api/v1/games/{game_id}/demos")]
+pub async fn get_demos(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("GET /api/v1/games/{game_id}/demos");
+    let client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+    require_demo_access(&client, &user, &game).await?;
+
+    let demos = db::demos::get_demos_for_game_id(&client, game.id).await?;
+    Ok(HttpResponse::Ok().json(demos))
+}
+
+/// Upload a demo for your team, as the `demo` field of a `multipart/form-data` body.
+#[post("/api/v1/games/{game_id}/demos")]
+pub async fn post_demo(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> HttpResult {
+    log::info!("POST /api/v1/games/{game_id}/demos");
+    // Demos are big, so the connection goes back to the pool while the file comes in.
+    let (user, game, teamid) = {
+        let client = grab_pool(&state).await?;
+        let user = session.user(&client).await?;
+        let game = db::games::get_game_from_id(&client, *game_id).await?;
+        let teamid = managing_team(&client, &user, &game).await?;
+        // Checked again once the demo is in, but there's no point taking it if we're full.
+        require_room(&client, game.id, teamid).await?;
+        (user, game, teamid)
+    };
+
+    let mut multipart = Multipart::new(req.headers(), payload);
+    let mut field = match multipart.next().await {
+        Some(Ok(field)) => field,
+        Some(Err(
+            MultipartError::ContentTypeMissing
+            | MultipartError::ContentTypeParse
+            | MultipartError::ContentTypeIncompatible
+            | MultipartError::BoundaryMissing,
+        )) => {
+            return Err(MyError::invalid_field(
+                "demo",
+                "must be sent as multipart/form-data",
+            ))
+        }
+        Some(Err(err)) => return Err(malformed(err)),
+        None => return Err(MyError::invalid_field("demo", "is missing")),
+    };
+    if field.name() != Some("demo") {
+        return Err(MyError::invalid_field("demo", "must be the first field"));
+    }
+    let file_name = clean_file_name(
+        field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename()),
+    );
+
+    let stored_name = format!(
+        "{0}-{1}.dem",
+        game.id,
+        Randomizer::ALPHANUMERIC(12).string().map_err(|err| {
+            MyError::IoError(std::io::Error::other(format!(
+                "Couldn't name the demo: {err:?}"
+            )))
+        })?
+    );
+    let path = state.demo_dir.join(&stored_name);
+    let mut file = tokio::fs::File::create(&path).await?;
+    let mut hasher = Sha256::new();
+    let mut size: u64 = 0;
+    let mut head = Vec::with_capacity(DEMO_MAGIC.len());
+    let written: Result<(), MyError> = async {
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(malformed)?;
+            size += chunk.len() as u64;
+            if size > state.max_demo_bytes {
+                return Err(MyError::invalid_field(
+                    "demo",
+                    format!(
+                        "must be at most {0} MiB",
+                        state.max_demo_bytes / 1024 / 1024
+                    ),
+                ));
+            }
+            let missing = DEMO_MAGIC.len() - head.len();
+            head.extend_from_slice(&chunk[..missing.min(chunk.len())]);
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        if head != DEMO_MAGIC {
+            return Err(MyError::invalid_field("demo", "not an STV demo"));
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+    drop(file);
+
+    let demo = MiniGameDemo {
+        gameid: game.id,
+        teamid,
+        uploaded_by: user.id,
+        file_name,
+        stored_name,
+        size_bytes: size as i64,
+        sha256: format!("{:x}", hasher.finalize()),
+    };
+    let added = match written {
+        Ok(()) => match grab_pool(&state).await {
+            Ok(mut client) => add_demo(&mut client, &demo).await,
+            Err(err) => Err(err),
+        },
+        Err(err) => Err(err),
+    };
+    let demo = match added {
+        Ok(demo) => demo,
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(err);
+        }
+    };
+
+    log::info!(
+        "Team {teamid} uploaded demo {0} ({1} bytes) for game {2}",
+        demo.id,
+        demo.size_bytes,
+        game.id
+    );
+    Ok(HttpResponse::Created().json(demo))
+}
+
+fn malformed(err: MultipartError) -> MyError {
+    MyError::Validation(format!("Malformed multipart body: {err}"))
+}
+
+async fn require_room(
+    client: &impl GenericClient,
+    gameid: i64,
+    teamid: i64,
+) -> Result<(), MyError> {
+    match db::demos::count_demos_for_team(client, gameid, teamid).await? {
+        count if count >= MAX_DEMOS_PER_TEAM => Err(MyError::Conflict(format!(
+            "Each team can upload at most {MAX_DEMOS_PER_TEAM} demos per game"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Record `demo`, unless its team filled up its demos while it was being uploaded.
+async fn add_demo(
+    client: &mut deadpool_postgres::Client,
+    demo: &MiniGameDemo,
+) -> Result<GameDemo, MyError> {
+    let transaction = client.transaction().await?;
+    db::games::lock_game(&transaction, demo.gameid).await?;
+    require_room(&transaction, demo.gameid, demo.teamid).await?;
+    let demo = db::demos::add_demo(&transaction, demo).await?;
+    transaction.commit().await?;
+    Ok(demo)
+}
+
+#[get("/api/v1/demos/{demo_id}")]
+pub async fn download_demo(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    demo_id: web::Path<i64>,
+    req: HttpRequest,
+) -> HttpResult {
+    log::info!("GET /api/v1/demos/{demo_id}");
+    let client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let demo = db::demos::get_demo_from_id(&client, *demo_id).await?;
+    let game = db::games::get_game_from_id(&client, demo.gameid).await?;
+    require_demo_access(&client, &user, &game).await?;
+
+    let file = match NamedFile::open_async(state.demo_dir.join(&demo.stored_name)).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(MyError::NotFound),
+        Err(err) => return Err(err.into()),
+    };
+    let file = file
+        .set_content_type(header::ContentType::octet_stream().0)
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(demo.file_name)],
+        });
+    Ok(file.into_response(&req))
+}
@@ -0,0 +1,224 @@
+use actix_web::{get, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+
+use super::HttpResult;
+use crate::apiv1::grab_pool;
+use crate::apiv1::leagues::HiddenParams;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::db;
+use crate::db::teams::UserTeamAffiliation;
+use crate::errors::MyError;
+use crate::models::{Game, GameLog, GameTimeProposal, PlayerGameStats, ProposalStatus, User};
+use crate::permission::UserPermission;
+use crate::AppState;
+
+/// Games in hidden leagues look just like missing ones.
+#[get("/api/v1/games/{game_id}")]
+pub async fn get_game(
+    state: web::Data<AppState>,
+    game_id: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
+    log::info!("GET /api/v1/games/{game_id}");
+    let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
+
+    let game = db::games::get_visible_game_from_id(&client, *game_id, &visibility).await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameStats {
+    /// The log the stats were imported from, if there's been one.
+    pub log: Option<GameLog>,
+    pub players: Vec<PlayerGameStats>,
+}
+
+/// Everyone's PASS Time stats in a game, from its imported log.
+#[get("/api/v1/games/{game_id}/stats")]
+pub async fn get_game_stats(
+    state: web::Data<AppState>,
+    game_id: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
+    log::info!("GET /api/v1/games/{game_id}/stats");
+    let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
+
+    let game = db::games::get_visible_game_from_id(&client, *game_id, &visibility).await?;
+    let log = db::stats::get_log_for_game_id(&client, game.id).await?;
+    let players = db::stats::get_stats_for_game_id(&client, game.id).await?;
+    Ok(HttpResponse::Ok().json(GameStats { log, players }))
+}
+
+/// `user`'s affiliation on team `teamid`'s roster for `game`, if they're on it.
+pub(super) async fn affiliation(
+    client: &impl GenericClient,
+    user: &User,
+    game: &Game,
+    teamid: i64,
+) -> Result<Option<i32>, MyError> {
+    db::teams::get_affiliation_in_league(client, user.id, teamid, game.leagueid).await
+}
+
+/// The team in `game` that `user` can act for: one whose roster they lead or are an officer on.
+pub(super) async fn managing_team(
+    client: &impl GenericClient,
+    user: &User,
+    game: &Game,
+) -> Result<i64, MyError> {
+    for teamid in [game.teamhomeid, game.teamawayid] {
+        let level = affiliation(client, user, game, teamid).await?;
+        if level.is_some_and(|level| level >= UserTeamAffiliation::Officer as i32) {
+            return Ok(teamid);
+        }
+    }
+    Err(MyError::Forbidden(
+        "Only leaders and officers of either team can manage this game",
+    ))
+}
+
+fn require_negotiable(game: &Game) -> Result<(), MyError> {
+    match game.is_negotiable(chrono::offset::Utc::now()) {
+        true => Ok(()),
+        false => Err(MyError::Conflict(
+            "This game's time can't be negotiated any more".to_owned(),
+        )),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameSchedule {
+    pub game: Game,
+    /// Newest first. At most one of them is pending.
+    pub proposals: Vec<GameTimeProposal>,
+}
+
+/// The times proposed for a game so far. Visible to both rosters and to game admins.
+#[get("/api/v1/games/{game_id}/proposals")]
+pub async fn get_proposals(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("GET /api/v1/games/{game_id}/proposals");
+    let client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+    if !user.admin_or_perm(UserPermission::CreateGame)
+        && affiliation(&client, &user, &game, game.teamhomeid)
+            .await?
+            .is_none()
+        && affiliation(&client, &user, &game, game.teamawayid)
+            .await?
+            .is_none()
+    {
+        return Err(MyError::Forbidden("You aren't playing in this game"));
+    }
+
+    let proposals = db::games::get_proposals_for_game_id(&client, game.id).await?;
+    Ok(HttpResponse::Ok().json(GameSchedule { game, proposals }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeProposal {
+    pub proposed_time: DateTime<Utc>,
+}
+
+impl Validate for TimeProposal {
+    fn validate(&mut self, check: &mut Checker) {
+        if self.proposed_time < chrono::offset::Utc::now() {
+            check.error("proposed_time", "must not be in the past");
+        }
+    }
+}
+
+/// Propose a time for a game, replacing whatever was still pending.
+/// Proposing while the other team's proposal is pending is how to counter it.
+#[post("/api/v1/games/{game_id}/proposals")]
+pub async fn post_proposal(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+    proposal: Valid<TimeProposal>,
+) -> HttpResult {
+    log::info!("POST /api/v1/games/{game_id}/proposals");
+    let mut client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+    let teamid = managing_team(&client, &user, &game).await?;
+    require_negotiable(&game)?;
+    if !game.in_window(proposal.proposed_time) {
+        return Err(MyError::invalid_field(
+            "proposed_time",
+            "must be within the game's window",
+        ));
+    }
+
+    let transaction = client.transaction().await?;
+    let proposal = db::games::add_proposal(
+        &transaction,
+        game.id,
+        teamid,
+        user.id,
+        proposal.proposed_time,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    log::info!(
+        "Team {teamid} proposed {0} for game {1}",
+        proposal.proposed_time,
+        game.id
+    );
+    Ok(HttpResponse::Created().json(proposal))
+}
+
+/// Accept the other team's proposal, which moves the game to that time for good.
+#[post("/api/v1/games/{game_id}/proposals/{proposal_id}/accept")]
+pub async fn accept_proposal(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<(i64, i64)>,
+) -> HttpResult {
+    let (game_id, proposal_id) = path.into_inner();
+    log::info!("POST /api/v1/games/{game_id}/proposals/{proposal_id}/accept");
+    let mut client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let game = db::games::get_game_from_id(&client, game_id).await?;
+    let proposal = db::games::get_proposal_from_id(&client, proposal_id).await?;
+    if proposal.gameid != game.id {
+        return Err(MyError::NotFound);
+    }
+    let opponent = match proposal.teamid == game.teamhomeid {
+        true => game.teamawayid,
+        false => game.teamhomeid,
+    };
+    let level = affiliation(&client, &user, &game, opponent).await?;
+    if level.is_none_or(|level| level < UserTeamAffiliation::Officer as i32) {
+        return Err(MyError::Forbidden(
+            "Only leaders and officers of the other team can accept this",
+        ));
+    }
+    require_negotiable(&game)?;
+    if proposal.status != ProposalStatus::Pending.as_str() {
+        return Err(MyError::Conflict(
+            "This proposal isn't pending any more".to_owned(),
+        ));
+    }
+
+    let transaction = client.transaction().await?;
+    let game = db::games::accept_proposal(&transaction, proposal.id, user.id).await?;
+    transaction.commit().await?;
+
+    log::info!("Game {0} scheduled for {1}", game.id, game.played_at);
+    Ok(HttpResponse::Ok().json(game))
+}
@@ -1,96 +1,80 @@
 use crate::db;
-use crate::db::divisions::DeepTeamDivAssociation;
+use crate::db::leagues::Visibility;
 use crate::errors::MyError;
-use actix_web::body::MessageBody;
-use actix_web::{get, web, Error, HttpResponse};
-use deadpool_postgres::Client;
-use serde::{Deserialize, Serialize};
+use actix_web::{get, web, HttpResponse};
+use deadpool_postgres::{Client, GenericClient};
+use serde::Deserialize;
 
+use super::session::SessionToken;
 use super::HttpResult;
 use crate::apiv1::grab_pool;
-use crate::models::{Division, League, Team, TeamDivAssociation, WrappedDivisionAdmin};
+use crate::models::League;
+use crate::permission::UserPermission;
 use crate::AppState;
 
-#[get("/api/v1/leagues")]
-async fn get_all_leagues(state: web::Data<AppState>) -> HttpResult {
-    log::info!("GET /api/v1/leagues");
-    let client = grab_pool(&state).await?;
-
-    let leagues: Vec<League> = db::leagues::get_leagues(&client).await?;
-
-    let mut league_responses: Vec<LeagueReturn> = Vec::with_capacity(leagues.len());
-
-    for league in leagues {
-        let league_divs: Vec<Division> =
-            db::leagues::get_divs_for_league_id(&client, league.id).await?;
-
-        let mut divisions: Vec<DivisionOptionalTeams> = Vec::with_capacity(league_divs.len());
+#[derive(Deserialize)]
+pub(crate) struct HiddenParams {
+    /// Also show hidden leagues and private rosters, to those allowed to see them.
+    #[serde(default)]
+    pub include_hidden: bool,
+}
 
-        for div in league_divs {
-            let admins = db::divisions::get_admins_for_div_id_wrapped(&client, div.id).await?;
-            divisions.push(DivisionOptionalTeams {
-                info: div,
-                admins,
-                teams: None,
-            })
+impl HiddenParams {
+    /// Work out what the request gets to see. Asking for hidden leagues needs a session.
+    pub async fn visibility(
+        &self,
+        client: &impl GenericClient,
+        session: Option<SessionToken>,
+    ) -> Result<Visibility, MyError> {
+        if !self.include_hidden {
+            return Ok(Visibility::Public);
+        }
+        let Some(session) = session else {
+            return Err(MyError::Unauthorized("include_hidden needs a session"));
+        };
+        let user = session.user(client).await?;
+        if user.admin_or_perm(UserPermission::CreateLeague) {
+            return Ok(Visibility::All);
         }
-        league_responses.push(LeagueReturn {
-            divisions,
-            info: league,
-        })
+        let leagueids = db::leagues::get_admin_league_ids_for_user_id(client, user.id).await?;
+        Ok(Visibility::Leagues(leagueids))
     }
-
-    Ok(HttpResponse::Ok().json(league_responses))
 }
 
-#[derive(Serialize, Deserialize)]
-struct AdminInfo {
-    inner_user_id: i64,
-    relation: String,
-}
+#[get("/api/v1/leagues")]
+async fn get_all_leagues(
+    state: web::Data<AppState>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
+    log::info!("GET /api/v1/leagues");
+    let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
 
-#[derive(Serialize, Deserialize)]
-struct DivisionOptionalTeams {
-    info: Division,
-    admins: Vec<WrappedDivisionAdmin>,
-    teams: Option<Vec<DeepTeamDivAssociation>>,
-}
+    let leagues: Vec<League> = db::leagues::get_leagues(&client, &visibility).await?;
+    let league_responses =
+        db::leagues::get_league_trees(&client, leagues, false, &visibility).await?;
 
-#[derive(Serialize, Deserialize)]
-struct LeagueReturn {
-    info: League,
-    divisions: Vec<DivisionOptionalTeams>,
+    Ok(HttpResponse::Ok().json(league_responses))
 }
 
 #[get("/api/v1/leagues/{league_id}")]
-pub async fn get_league(state: web::Data<AppState>, league_id: web::Path<i64>) -> HttpResult {
+pub async fn get_league(
+    state: web::Data<AppState>,
+    league_id: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
     log::info!("GET /api/v1/leagues/league_id");
 
     let client: Client = grab_pool(&state).await?;
-
-    let league_info = db::leagues::get_league_from_id(&client, *league_id).await?;
-
-    let league_divs: Vec<Division> =
-        db::leagues::get_divs_for_league_id(&client, *league_id).await?;
-
-    let mut divisions: Vec<DivisionOptionalTeams> = Vec::with_capacity(league_divs.len());
-    for div in league_divs {
-        let admins = db::divisions::get_admins_for_div_id_wrapped(&client, div.id)
-            .await
-            .expect("should be able to get admins");
-        let teams = db::divisions::get_teams_for_div_id(&client, div.id)
-            .await
-            .expect("should be able to get teams");
-        divisions.push(DivisionOptionalTeams {
-            info: div,
-            admins,
-            teams: Some(teams),
-        })
-    }
-
-    let resp = LeagueReturn {
-        info: league_info,
-        divisions,
-    };
+    let visibility = params.visibility(&client, session).await?;
+
+    // A hidden league looks just like a missing one.
+    let league_info =
+        db::leagues::get_visible_league_from_id(&client, *league_id, &visibility).await?;
+    let mut trees =
+        db::leagues::get_league_trees(&client, vec![league_info], true, &visibility).await?;
+    let resp = trees.pop().ok_or(MyError::NotFound)?;
     Ok(HttpResponse::Ok().json(resp))
 }
@@ -0,0 +1,292 @@
+//! Who played in each game, and teams asking to play mercenaries from outside their roster.
+use std::collections::HashMap;
+
+use actix_web::{get, post, web, HttpResponse};
+use deadpool_postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+
+use super::games::managing_team;
+use super::HttpResult;
+use crate::apiv1::grab_pool;
+use crate::apiv1::leagues::HiddenParams;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::db;
+use crate::errors::MyError;
+use crate::models::{Game, MercenaryRequest, MercenaryStatus, User};
+use crate::AppState;
+
+/// The most players a team can put in one game's lineup.
+const MAX_LINEUP: usize = 18;
+
+/// Something a league admin should look at about a player in a lineup.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineupIssue {
+    /// Wasn't on the team's roster when the game was played, and isn't an approved mercenary.
+    NotRostered,
+    /// Has an eligibility flag nobody has reviewed yet.
+    Flagged,
+    /// Was banned from the game's league.
+    Banned,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LineupPlayer {
+    pub teamid: i64,
+    pub user: User,
+    /// Played as an approved mercenary.
+    pub mercenary: bool,
+    pub issues: Vec<LineupIssue>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameLineups {
+    pub players: Vec<LineupPlayer>,
+    pub mercenary_requests: Vec<MercenaryRequest>,
+}
+
+/// Both lineups of `game`, checked against the rosters as they were when it was played.
+pub async fn get_game_lineups(
+    client: &impl GenericClient,
+    game: &Game,
+) -> Result<GameLineups, MyError> {
+    let lineup = db::lineups::get_lineup_for_game_id(client, game.id).await?;
+    let mercenary_requests =
+        db::lineups::get_mercenary_requests_for_game_id(client, game.id).await?;
+
+    let mut rostered = HashMap::new();
+    for teamid in [game.teamhomeid, game.teamawayid] {
+        let ids =
+            db::teams::get_player_ids_in_league_at(client, teamid, game.leagueid, game.played_at)
+                .await?;
+        rostered.insert(teamid, ids);
+    }
+    let userids: Vec<i64> = lineup.iter().map(|entry| entry.userid).collect();
+    let flagged = db::users::get_flagged_user_ids(client, &userids).await?;
+    let banned = db::users::get_banned_user_ids(client, game.leagueid, &userids).await?;
+    let users: HashMap<i64, User> = db::users::mass_get_user_from_internal_id(client, &userids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+
+    let mut players = Vec::with_capacity(lineup.len());
+    for entry in lineup {
+        let Some(user) = users.get(&entry.userid) else {
+            continue;
+        };
+        let on_roster = rostered
+            .get(&entry.teamid)
+            .is_some_and(|ids| ids.contains(&entry.userid));
+        let approved = mercenary_requests.iter().any(|request| {
+            request.teamid == entry.teamid
+                && request.userid == entry.userid
+                && request.status == MercenaryStatus::Approved.as_str()
+        });
+        let mut issues = Vec::new();
+        if !on_roster && !approved {
+            issues.push(LineupIssue::NotRostered);
+        }
+        if flagged.contains(&entry.userid) {
+            issues.push(LineupIssue::Flagged);
+        }
+        if banned.contains(&entry.userid) {
+            issues.push(LineupIssue::Banned);
+        }
+        players.push(LineupPlayer {
+            teamid: entry.teamid,
+            user: user.clone(),
+            mercenary: !on_roster && approved,
+            issues,
+        });
+    }
+    Ok(GameLineups {
+        players,
+        mercenary_requests,
+    })
+}
+
+fn require_open(game: &Game) -> Result<(), MyError> {
+    match game.confirmed_at {
+        None => Ok(()),
+        Some(_) => Err(MyError::Conflict(
+            "This game's result was already confirmed".to_owned(),
+        )),
+    }
+}
+
+#[get("/api/v1/games/{game_id}/lineups")]
+pub async fn get_lineups(
+    state: web::Data<AppState>,
+    game_id: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
+    log::info!("GET /api/v1/games/{game_id}/lineups");
+    let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
+
+    let game = db::games::get_visible_game_from_id(&client, *game_id, &visibility).await?;
+    let lineups = get_game_lineups(&client, &game).await?;
+    Ok(HttpResponse::Ok().json(lineups))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Lineup {
+    pub userids: Vec<i64>,
+}
+
+impl Validate for Lineup {
+    fn validate(&mut self, check: &mut Checker) {
+        if self.userids.is_empty() {
+            check.error("userids", "must not be empty");
+        }
+        if self.userids.len() > MAX_LINEUP {
+            check.error("userids", format!("must have at most {MAX_LINEUP} players"));
+        }
+        for (i, userid) in self.userids.iter().enumerate() {
+            if self.userids[..i].contains(userid) {
+                check.error(&format!("userids[{i}]"), "listed twice");
+            }
+        }
+    }
+}
+
+/// Declare who played for your team, replacing any earlier lineup.
+///
+/// Everyone has to be on your roster in this league, or be someone you asked to play as a mercenary.
+#[post("/api/v1/games/{game_id}/lineup")]
+pub async fn post_lineup(
+    body: Valid<Lineup>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/games/{game_id}/lineup");
+    let mut client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+    let teamid = managing_team(&client, &user, &game).await?;
+    require_open(&game)?;
+
+    let roster = db::teams::get_active_player_ids_in_league(&client, teamid, game.leagueid).await?;
+    let requests = db::lineups::get_mercenary_requests_for_game_id(&client, game.id).await?;
+    let others: Vec<i64> = db::lineups::get_lineup_for_game_id(&client, game.id)
+        .await?
+        .iter()
+        .filter(|entry| entry.teamid != teamid)
+        .map(|entry| entry.userid)
+        .collect();
+
+    let Lineup { userids } = body.into_inner();
+    let mut check = Checker::new(state.name_filter.as_ref());
+    for (i, userid) in userids.iter().enumerate() {
+        let requested = requests.iter().any(|request| {
+            request.teamid == teamid
+                && request.userid == *userid
+                && request.status != MercenaryStatus::Denied.as_str()
+        });
+        if others.contains(userid) {
+            check.error(
+                &format!("userids[{i}]"),
+                "already in the other team's lineup",
+            );
+        } else if !roster.contains(userid) && !requested {
+            check.error(
+                &format!("userids[{i}]"),
+                "not on your roster, and no mercenary request was made for them",
+            );
+        }
+    }
+    check.finish()?;
+
+    let transaction = client.transaction().await?;
+    db::lineups::set_lineup(&transaction, game.id, teamid, &userids).await?;
+    transaction.commit().await?;
+
+    log::info!(
+        "Team {teamid} declared {0} players for game {1}",
+        userids.len(),
+        game.id
+    );
+    let lineups = get_game_lineups(&client, &game).await?;
+    Ok(HttpResponse::Ok().json(lineups))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MercenaryBody {
+    pub userid: i64,
+    pub note: Option<String>,
+}
+
+impl Validate for MercenaryBody {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .optional_text("note", &mut self.note)
+            .length(1, 500)
+            .printable();
+    }
+}
+
+/// Ask the league's admins to let someone from outside your roster play in this game.
+#[post("/api/v1/games/{game_id}/mercenaries")]
+pub async fn post_mercenary_request(
+    body: Valid<MercenaryBody>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    game_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/games/{game_id}/mercenaries");
+    let client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let game = db::games::get_game_from_id(&client, *game_id).await?;
+    let teamid = managing_team(&client, &user, &game).await?;
+    require_open(&game)?;
+
+    let body = body.into_inner();
+    match db::users::get_user_from_internal_id(&client, body.userid).await {
+        Ok(_) => (),
+        Err(MyError::NotFound) => return Err(MyError::invalid_field("userid", "no such user")),
+        Err(err) => return Err(err),
+    }
+    for side in [game.teamhomeid, game.teamawayid] {
+        let roster =
+            db::teams::get_active_player_ids_in_league(&client, side, game.leagueid).await?;
+        if roster.contains(&body.userid) {
+            let message = match side == teamid {
+                true => "already on your roster",
+                false => "on the other team's roster",
+            };
+            return Err(MyError::invalid_field("userid", message));
+        }
+    }
+    let requests = db::lineups::get_mercenary_requests_for_game_id(&client, game.id).await?;
+    if requests.iter().any(|request| {
+        request.teamid == teamid
+            && request.userid == body.userid
+            && request.status != MercenaryStatus::Denied.as_str()
+    }) {
+        return Err(MyError::Conflict(
+            "You already asked to play this mercenary".to_owned(),
+        ));
+    }
+
+    let request = db::lineups::add_mercenary_request(
+        &client,
+        game.id,
+        teamid,
+        body.userid,
+        user.id,
+        body.note.as_deref(),
+    )
+    .await?;
+    log::info!(
+        "Team {teamid} asked to play {0} in game {1}",
+        request.userid,
+        game.id
+    );
+    Ok(HttpResponse::Created().json(request))
+}
@@ -47,7 +47,7 @@
 //     log::info!("Openid landing received from steamid: {steamid}");
 //     let client: Client = state.pool.get().await.map_err(MyError::PoolError)?;
 
-//     let auth = match db::get_user_from_steamid(&client, &steamid).await {
+//     let auth = match db::users::get_user_from_steamid(&client, &steamid).await {
 //         // there is a user corresponding
 //         Ok(user) => {
 //             log::trace!("User found for steamid {steamid}");
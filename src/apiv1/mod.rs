@@ -3,17 +3,22 @@
 
 use crate::authorization;
 use crate::authorization::get_authorization_for_user;
+use crate::config::{AuthMode, EligibilityConfig};
 use crate::db;
-use crate::db::UserAndAssoc;
+use crate::db::teams::UserAndAssoc;
 use crate::errors::MyError;
+use crate::logstf::LogsApi;
 use crate::models::League;
 use crate::models::MiniTeam;
 use crate::models::Team;
 use crate::models::TeamDivAssociation;
+use crate::models::TeamNameHistory;
 use crate::models::User;
 use crate::steamapi;
+use crate::steamapi::SteamApi;
+use crate::steamid::SteamId;
 use crate::CurrentHost;
-use actix_web::{get, post, web, Error, HttpResponse};
+use actix_web::{get, post, web, HttpResponse};
 use chrono::DateTime;
 use chrono::Utc;
 use deadpool_postgres::{Client, Pool};
@@ -21,19 +26,31 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 pub mod add_teams;
 pub mod admin;
+pub mod demos;
+pub mod games;
 pub mod leagues;
+pub mod lineups;
 pub mod login;
+pub mod seasons;
+pub mod session;
+pub mod stats;
 pub mod teams;
+pub mod tickets;
 pub mod users;
+pub mod validation;
 
 mod apimodels;
+#[cfg(test)]
+mod tests;
 
 use apimodels::*;
 
-type HttpResult = Result<HttpResponse, Error>;
+type HttpResult = Result<HttpResponse, MyError>;
 
 /*
 https://rgl.gg/Login/Default.aspx?push=1&r=40
@@ -52,28 +69,65 @@ pub struct AppState {
     pub current_host: CurrentHost,
     pub pool: Pool,
     pub steam_auth_url: String,
-    pub steam_api_key: String,
+    pub steam: Arc<dyn SteamApi>,
+    pub logs: Arc<dyn LogsApi>,
     pub root_user_steamid: Option<String>,
+    pub auth_mode: AuthMode,
+    pub session_cookie_domain: Option<String>,
+    pub eligibility: EligibilityConfig,
+    /// Checks the names of teams, rosters, leagues and divisions.
+    pub name_filter: Arc<dyn validation::NameFilter>,
+    /// Where uploaded files are written, see [crate::config::ExampleConfig::upload_dir].
+    pub upload_dir: PathBuf,
+    /// Where demos are written, see [crate::config::ExampleConfig::demo_dir].
+    pub demo_dir: PathBuf,
+    /// The largest demo that can be uploaded, in bytes.
+    pub max_demo_bytes: u64,
 }
-#[derive(Serialize, Deserialize)]
+
+/// The body `/logout` used to take, before it used the current session.
+#[derive(Deserialize)]
 struct LogoutFields {
     pub auth_token: String,
 }
 
+/// Log out of the current session.
+///
+/// Still takes the token in the body when bearer tokens are allowed, for clients that haven't
+/// moved to sending it as one yet. That goes away in the next release.
 #[post("/api/v1/logout")]
-pub async fn logout(body: web::Json<LogoutFields>, state: web::Data<AppState>) -> HttpResult {
+pub async fn logout(
+    session: Result<session::SessionToken, MyError>,
+    body: Option<web::Json<LogoutFields>>,
+    state: web::Data<AppState>,
+) -> HttpResult {
     log::info!("POST /api/v1/logout");
-    let client = grab_pool(&state).await?;
-    let user = match db::get_user_from_auth_token(&client, &body.auth_token).await {
-        Ok(user) => user,
-        Err(err) => return Ok(HttpResponse::BadRequest().body(format!("{err:?}"))),
+    let session = match (session, body) {
+        (Ok(session), _) => session,
+        (Err(MyError::Unauthorized(_)), Some(body)) if state.auth_mode.allows_bearer() => {
+            log::warn!(
+                "Logging out with the token in the body is deprecated, send it as a bearer token"
+            );
+            session::SessionToken(body.into_inner().auth_token)
+        }
+        // Including a failed CSRF check, which a body mustn't get around.
+        (Err(err), _) => return Err(err),
     };
+    let client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
+
+    db::users::revoke_user_authorization(&client, &user).await?;
 
-    db::revoke_user_authorization(&client, &user).await?;
-    Ok(HttpResponse::Ok().finish())
+    let mut resp = HttpResponse::Ok();
+    if state.auth_mode.allows_cookie() {
+        for cookie in session::removal_cookies(&state) {
+            resp.cookie(cookie);
+        }
+    }
+    Ok(resp.finish())
 }
 
-/// All parameters that a valid openid request should have.
+// All parameters that a valid openid request should have.
 
 //https://rgl.gg/Login/Default.aspx?push=1&r=40
 //&dnoa.userSuppliedIdentifier=https%3A%2F%2Fsteamcommunity.com%2Fopenid%2F
@@ -118,7 +172,10 @@ struct OpenIdFields {
 #[derive(Serialize, Deserialize)]
 struct IsOpenIdValid {
     pub valid: bool,
+    /// Only handed out if the deployment accepts bearer tokens.
     pub token_info: Option<Token>,
+    /// Must be sent back as the `X-CSRF-Token` header when using cookie sessions.
+    pub csrf_token: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -127,6 +184,85 @@ struct Token {
     pub expires: DateTime<Utc>,
 }
 
+/// Register every route in the API.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // Bodies, paths and queries that don't parse get the same error shape as everything else.
+    cfg.app_data(
+        web::JsonConfig::default()
+            .error_handler(|err, _| MyError::Validation(err.to_string()).into()),
+    )
+    .app_data(
+        web::PathConfig::default()
+            .error_handler(|err, _| MyError::Validation(err.to_string()).into()),
+    )
+    .app_data(
+        web::QueryConfig::default()
+            .error_handler(|err, _| MyError::Validation(err.to_string()).into()),
+    );
+
+    cfg.service(teams::get_team)
+        .service(teams::get_team_div_assoc)
+        .service(teams::post_team)
+        .service(teams::patch_team)
+        .service(teams::transfer_team)
+        .service(teams::put_team_logo)
+        .service(teams::delete_team_logo)
+        .service(teams::disband_team)
+        .service(add_teams::post_team_to_league)
+        .service(users::get_user_from_steamid)
+        .service(users::get_user_from_auth_token)
+        .service(users::get_current_user)
+        .service(users::get_users_paged)
+        .service(users::search_users)
+        // .service(admin::add_user) // this is unauthenticated...
+        .service(leagues::get_league)
+        .service(leagues::get_all_leagues)
+        .service(admin::post_league)
+        .service(admin::post_league_divisions)
+        .service(admin::patch_league)
+        .service(admin::delete_league)
+        .service(admin::patch_division)
+        .service(admin::delete_division)
+        .service(admin::placements::get_pending_placements)
+        .service(admin::placements::post_placements)
+        .service(games::get_game)
+        .service(games::get_proposals)
+        .service(games::post_proposal)
+        .service(games::accept_proposal)
+        .service(lineups::get_lineups)
+        .service(lineups::post_lineup)
+        .service(lineups::post_mercenary_request)
+        .service(admin::games::approve_mercenary)
+        .service(admin::games::deny_mercenary)
+        .service(admin::games::post_game)
+        .service(admin::games::post_game_result)
+        .service(admin::games::get_swiss_pairings)
+        .service(games::get_game_stats)
+        .service(demos::get_demos)
+        .service(demos::post_demo)
+        .service(demos::download_demo)
+        .service(stats::get_user_stats)
+        .service(stats::get_team_stats)
+        .service(stats::get_division_leaderboard)
+        .service(admin::logs::post_game_log)
+        .service(admin::logs::post_game_log_from_logstf)
+        .service(tickets::get_tickets)
+        .service(tickets::get_ticket)
+        .service(tickets::post_ticket)
+        .service(tickets::post_ticket_message)
+        .service(admin::tickets::post_ticket_status)
+        .service(admin::tickets::post_ticket_resolution)
+        .service(seasons::get_all_seasons)
+        .service(seasons::get_season)
+        .service(admin::seasons::post_season)
+        .service(admin::seasons::patch_season)
+        .service(admin::seasons::clone_season)
+        .service(admin::review_flags::get_review_flags)
+        .service(admin::review_flags::post_review_flag_reviewed)
+        .service(verify_openid_login)
+        .service(logout);
+}
+
 pub async fn grab_pool(state: &AppState) -> Result<Client, MyError> {
     state.pool.get().await.map_err(MyError::PoolError)
 }
@@ -135,75 +271,76 @@ pub async fn grab_pool(state: &AppState) -> Result<Client, MyError> {
 pub async fn verify_openid_login(
     body: web::Json<OpenIdFields>,
     state: web::Data<AppState>,
-) -> Result<HttpResponse, Error> {
+) -> HttpResult {
     log::info!("POST /api/v1/loginverify");
     let encode = serde_json::to_string(&body.0).unwrap();
-    let is_valid = match steamapi::verify_auth_underscores(&encode).await {
-        Ok(is_valid) => is_valid,
-        Err(_) => {
-            log::debug!("There was an error reaching out to Steam");
-            return Ok(HttpResponse::InternalServerError().finish());
-        }
-    };
-
+    let key_values: HashMap<String, String> =
+        serde_json::from_str(&encode.replace("__", ".")).unwrap();
+    let is_valid = state.steam.verify_openid(&key_values).await?;
     if !is_valid {
-        return Ok(HttpResponse::BadRequest().json(IsOpenIdValid {
-            valid: false,
-            token_info: None,
-        }));
+        return Err(MyError::Unauthorized("Steam couldn't verify this login"));
     }
 
     let map: HashMap<String, String> = serde_json::from_str(&encode).unwrap();
     // is valid, return a token
     let openid_identity: &String = match map.get("openid__identity") {
         Some(str) => str,
-        None => {
-            log::debug!("There was an error reaching out to Steam");
-            return Ok(HttpResponse::BadRequest().body("Malformed request"));
-        }
+        None => return Err(MyError::Validation("Missing openid.identity".to_owned())),
     };
 
     // let openid_sig = inner.get("openid.sig").expect("No openid.sig on request");
-    let steamid = openid_identity.replace("https://steamcommunity.com/openid/id/", "");
+    let steamid: SteamId = match openid_identity
+        .strip_prefix("https://steamcommunity.com/openid/id/")
+        .and_then(|steamid| steamid.parse().ok())
+    {
+        Some(steamid) => steamid,
+        None => {
+            log::warn!("Openid identity had no valid steamid: {openid_identity}");
+            return Err(MyError::Validation(
+                "openid.identity isn't a Steam profile".to_owned(),
+            ));
+        }
+    };
     log::info!("Openid landing received from steamid: {steamid}");
-    let client: Client = grab_pool(&state).await?;
+    let mut client: Client = grab_pool(&state).await?;
 
-    let auth = match db::get_user_from_steamid(&client, &steamid).await {
+    let auth = match db::users::get_user_from_steamid(&client, &steamid.to_string()).await {
         // there is a user corresponding
         Ok(user) => {
             log::trace!("User found for steamid {steamid}");
-            match crate::authorization::get_authorization_for_user(&client, &user).await {
-                Ok(auth) => {
-                    log::debug!("Assigning {auth:?} to {user:?}");
-                    auth
-                }
-                Err(_) => {
-                    log::error!("Internally failed to get authorization for {user:?}");
-                    return Ok(
-                        HttpResponse::InternalServerError().body("500 Internal Server Error")
-                    );
-                }
-            }
+            let auth = crate::authorization::get_authorization_for_user(&client, &user).await?;
+            log::debug!("Assigning {auth:?} to {user:?}");
+            auth
         }
         // user wasn't found
-        Err(_) => {
+        Err(MyError::NotFound) => {
             log::info!("Creating a new user with steamid {steamid}");
-            let user: User = match users::add_user_with_steamid(&state, &client, &steamid).await {
-                Ok(user) => user,
-                Err(error_whatever) => {
-                    return Ok(HttpResponse::InternalServerError()
-                        .body(format!("Error: {error_whatever:?}")))
-                }
-            };
-            crate::db::get_authorization_for_user(&client, &user).await?
+            let user: User = users::add_user_with_steamid(&state, &mut client, steamid).await?;
+            crate::db::users::get_authorization_for_user(&client, &user).await?
         }
+        Err(err) => return Err(err),
     };
-    Ok(HttpResponse::Ok().json(IsOpenIdValid {
-        valid: true,
-        token_info: Some(Token {
+
+    let mut resp = HttpResponse::Ok();
+    let mut csrf_token = None;
+    if state.auth_mode.allows_cookie() {
+        let token = session::new_csrf_token();
+        for cookie in session::session_cookies(&state, &auth, &token) {
+            resp.cookie(cookie);
+        }
+        csrf_token = Some(token);
+    }
+    let token_info = match state.auth_mode.allows_bearer() {
+        true => Some(Token {
             token: auth.token,
             expires: auth.expires,
         }),
+        false => None,
+    };
+    Ok(resp.json(IsOpenIdValid {
+        valid: true,
+        token_info,
+        csrf_token,
     }))
 }
 
@@ -211,6 +348,8 @@ pub async fn verify_openid_login(
 struct DeepTeamDivResponse {
     pub team_info: Team,
     pub association_info: TeamDivAssociation,
+    /// The name and tag the team went by when this roster signed up.
+    pub team_name_at_signup: Option<TeamNameHistory>,
     pub current_players: Vec<UserAndAssoc>,
     pub past_players: Vec<UserAndAssoc>,
 }
@@ -0,0 +1,49 @@
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use super::leagues::HiddenParams;
+use super::session::SessionToken;
+use super::HttpResult;
+use crate::apiv1::grab_pool;
+use crate::db;
+use crate::db::leagues::LeagueReturn;
+use crate::models::Season;
+use crate::AppState;
+
+#[derive(Serialize, Deserialize)]
+pub struct SeasonReturn {
+    pub info: Season,
+    pub leagues: Vec<LeagueReturn>,
+}
+
+/// Every season, newest first.
+#[get("/api/v1/seasons")]
+async fn get_all_seasons(state: web::Data<AppState>) -> HttpResult {
+    log::info!("GET /api/v1/seasons");
+    let client = grab_pool(&state).await?;
+
+    let seasons = db::seasons::get_seasons(&client).await?;
+    Ok(HttpResponse::Ok().json(seasons))
+}
+
+/// A season with its leagues and their divisions.
+#[get("/api/v1/seasons/{season_id}")]
+pub async fn get_season(
+    state: web::Data<AppState>,
+    season_id: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
+    log::info!("GET /api/v1/seasons/{season_id}");
+    let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
+
+    let season = db::seasons::get_season_from_id(&client, *season_id).await?;
+    let leagues = db::leagues::get_leagues_for_season_id(&client, season.id, &visibility).await?;
+    let leagues = db::leagues::get_league_trees(&client, leagues, false, &visibility).await?;
+
+    Ok(HttpResponse::Ok().json(SeasonReturn {
+        info: season,
+        leagues,
+    }))
+}
@@ -0,0 +1,156 @@
+//! Getting a session token off of a request.
+//!
+//! Depending on [AuthMode], the token is either sent by the client in an
+//! `Authorization: Bearer (token)` header, or lives in an HttpOnly cookie set on login.
+//! Cookies are sent by the browser automatically, so cookie sessions are protected from
+//! CSRF with a double-submit token: the `csrf_token` cookie must be echoed back in the
+//! `X-CSRF-Token` header on every state-changing request.
+use std::future::{ready, Ready};
+
+use actix_http::header::Header;
+use actix_web::cookie::{time::OffsetDateTime, Cookie, SameSite};
+use actix_web::dev::Payload;
+use actix_web::{http::Method, web, FromRequest, HttpRequest};
+use deadpool_postgres::GenericClient;
+use randomizer::Randomizer;
+
+use super::admin::AuthHeader;
+use crate::config::AuthMode;
+use crate::db;
+use crate::errors::MyError;
+use crate::models::{Authorization, User};
+use crate::AppState;
+
+pub const SESSION_COOKIE: &str = "session";
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// A session token taken from the request, in whichever way the deployment allows.
+///
+/// Use this instead of `web::Header<AuthHeader>` in any endpoint that needs a logged in user.
+#[derive(Debug)]
+pub struct SessionToken(pub String);
+
+impl SessionToken {
+    /// The user this session belongs to.
+    /// Errors with [MyError::Unauthorized] if it isn't a session we know of.
+    pub async fn user(&self, client: &impl GenericClient) -> Result<User, MyError> {
+        match db::users::get_user_from_auth_token(client, &self.0).await {
+            Err(MyError::NotFound) => Err(MyError::Unauthorized("Invalid session token")),
+            user => user,
+        }
+    }
+}
+
+impl FromRequest for SessionToken {
+    type Error = MyError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(extract_session_token(req))
+    }
+}
+
+fn extract_session_token(req: &HttpRequest) -> Result<SessionToken, MyError> {
+    let mode = req
+        .app_data::<web::Data<AppState>>()
+        .map(|state| state.auth_mode)
+        .unwrap_or_default();
+
+    // A bearer token is never attached by the browser on its own, so it needs no CSRF check.
+    if mode.allows_bearer() {
+        if let Ok(header) = AuthHeader::parse(req) {
+            return Ok(SessionToken(header.0));
+        }
+    }
+    if mode.allows_cookie() {
+        if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+            verify_csrf(req)?;
+            return Ok(SessionToken(cookie.value().to_owned()));
+        }
+    }
+    Err(MyError::Unauthorized("Missing session token"))
+}
+
+/// Check the double-submitted CSRF token for anything that isn't a safe method.
+fn verify_csrf(req: &HttpRequest) -> Result<(), MyError> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(());
+    }
+    let cookie = req.cookie(CSRF_COOKIE);
+    let header = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    match (cookie, header) {
+        (Some(cookie), Some(header)) if constant_time_eq(cookie.value(), header) => Ok(()),
+        _ => {
+            log::debug!(
+                "Rejecting {} {}: CSRF token mismatch",
+                req.method(),
+                req.path()
+            );
+            Err(MyError::Forbidden("Missing or invalid CSRF token"))
+        }
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.bytes()
+        .zip(b.bytes())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+pub fn new_csrf_token() -> String {
+    Randomizer::ALPHANUMERIC(32).string().unwrap()
+}
+
+/// The session and CSRF cookies to set for a freshly logged in user.
+pub fn session_cookies(
+    state: &AppState,
+    auth: &Authorization,
+    csrf_token: &str,
+) -> [Cookie<'static>; 2] {
+    let expires = OffsetDateTime::from_unix_timestamp(auth.expires.timestamp())
+        .unwrap_or_else(|_| OffsetDateTime::now_utc());
+
+    let session = base_cookie(state, SESSION_COOKIE, auth.token.clone())
+        .http_only(true)
+        .expires(expires)
+        .finish();
+    // Readable by the frontend, so it can be copied into the X-CSRF-Token header.
+    let csrf = base_cookie(state, CSRF_COOKIE, csrf_token.to_owned())
+        .http_only(false)
+        .expires(expires)
+        .finish();
+    [session, csrf]
+}
+
+/// Cookies that overwrite and expire the session and CSRF cookies.
+pub fn removal_cookies(state: &AppState) -> [Cookie<'static>; 2] {
+    [SESSION_COOKIE, CSRF_COOKIE].map(|name| {
+        let mut cookie = base_cookie(state, name, String::new()).finish();
+        cookie.make_removal();
+        cookie
+    })
+}
+
+fn base_cookie(
+    state: &AppState,
+    name: &'static str,
+    value: String,
+) -> actix_web::cookie::CookieBuilder<'static> {
+    let builder = Cookie::build(name, value)
+        .path("/")
+        .secure(true)
+        .same_site(SameSite::Strict);
+    match &state.session_cookie_domain {
+        Some(domain) => builder.domain(domain.clone()),
+        None => builder,
+    }
+}
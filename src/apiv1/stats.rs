@@ -0,0 +1,149 @@
+//! PASS Time stats summed up per player, team and division, from the logs imported for games.
+//!
+//! Stats from hidden leagues are left out everywhere.
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+use actix_web::{get, web, HttpResponse};
+use deadpool_postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+
+use super::HttpResult;
+use crate::apiv1::grab_pool;
+use crate::db;
+use crate::db::leagues::Visibility;
+use crate::errors::MyError;
+use crate::models::{LeaderboardStat, PlayerStatTotals, StatTotals, User};
+use crate::AppState;
+
+/// The most players one leaderboard page can have.
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Serialize, Deserialize)]
+pub struct SeasonStats {
+    /// `None` for leagues that aren't part of a season.
+    pub seasonid: Option<i64>,
+    pub totals: StatTotals,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserStats {
+    pub career: StatTotals,
+    pub seasons: Vec<SeasonStats>,
+}
+
+/// Everything `userid` did, per season and summed up, in one query.
+pub(in crate::apiv1) async fn user_stats(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<UserStats, MyError> {
+    let mut career = StatTotals::default();
+    let mut seasons = Vec::new();
+    for totals in db::stats::get_season_totals(client, userid).await? {
+        let seasonid = totals.seasonid;
+        let totals = StatTotals::from(totals);
+        career += &totals;
+        seasons.push(SeasonStats { seasonid, totals });
+    }
+    Ok(UserStats { career, seasons })
+}
+
+#[get("/api/v1/user/{user_id}/stats")]
+pub async fn get_user_stats(state: web::Data<AppState>, user_id: web::Path<i64>) -> HttpResult {
+    log::info!("GET /api/v1/user/{user_id}/stats");
+    let client = grab_pool(&state).await?;
+
+    let user = db::users::get_user_from_internal_id(&client, *user_id).await?;
+    let stats = user_stats(&client, user.id).await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TeamStats {
+    pub totals: StatTotals,
+    /// What each player did for the team, top scorers first.
+    pub players: Vec<PlayerStatTotals>,
+}
+
+#[get("/api/v1/teams/{team_id}/stats")]
+pub async fn get_team_stats(state: web::Data<AppState>, team_id: web::Path<i64>) -> HttpResult {
+    log::info!("GET /api/v1/teams/{team_id}/stats");
+    let client = grab_pool(&state).await?;
+
+    let team = db::teams::get_team_from_id(&client, *team_id).await?;
+    let totals = db::stats::get_team_totals(&client, team.id);
+    let players = db::stats::get_team_player_totals(&client, team.id);
+    let (totals, players) = futures::future::try_join(totals, players).await?;
+    Ok(HttpResponse::Ok().json(TeamStats { totals, players }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LeaderboardRequest {
+    #[serde(default)]
+    pub stat: LeaderboardStat,
+    pub page: Option<u32>,
+    pub amount_per_page: Option<std::num::NonZero<u32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub user: User,
+    pub totals: PlayerStatTotals,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PagedLeaderboard {
+    pub total_count: i64,
+    pub page: u32,
+    pub amount_per_page: std::num::NonZero<u32>,
+    pub stat: LeaderboardStat,
+    pub players: Vec<LeaderboardEntry>,
+}
+
+/// The players of a division's rosters, ranked by `stat`.
+#[get("/api/v1/divisions/{division_id}/leaderboard")]
+pub async fn get_division_leaderboard(
+    state: web::Data<AppState>,
+    division_id: web::Path<i64>,
+    query: web::Query<LeaderboardRequest>,
+) -> HttpResult {
+    log::info!("GET /api/v1/divisions/{division_id}/leaderboard");
+    let client = grab_pool(&state).await?;
+    let amount = query
+        .amount_per_page
+        .unwrap_or(NonZeroU32::new(10).unwrap())
+        .min(NonZeroU32::new(MAX_PAGE_SIZE).unwrap());
+    let page = query.page.unwrap_or(0);
+
+    let division = db::leagues::get_division_from_id(&client, *division_id).await?;
+    // A division of a hidden league looks just like a missing one.
+    db::leagues::get_visible_league_from_id(&client, division.leagueid, &Visibility::Public)
+        .await?;
+
+    let total_count = db::stats::get_leaderboard_count(&client, division.id);
+    let totals = db::stats::get_leaderboard_page(&client, division.id, query.stat, page, amount);
+    let (total_count, totals) = futures::future::try_join(total_count, totals).await?;
+
+    let userids: Vec<i64> = totals.iter().map(|totals| totals.userid).collect();
+    let mut users: HashMap<i64, User> =
+        db::users::mass_get_user_from_internal_id(&client, &userids)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user))
+            .collect();
+    let players = totals
+        .into_iter()
+        .filter_map(|totals| {
+            let user = users.remove(&totals.userid)?;
+            Some(LeaderboardEntry { user, totals })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(PagedLeaderboard {
+        total_count,
+        page,
+        amount_per_page: amount,
+        stat: query.stat,
+        players,
+    }))
+}
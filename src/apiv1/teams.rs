@@ -1,48 +1,95 @@
 use crate::db;
-use crate::db::get_user_from_auth_token;
 use crate::errors::MyError;
 use crate::grab_pool;
-use crate::models::{League, MiniTeam, Team, TeamDivAssociation, User};
+use crate::images::{self, ImageFormat};
+use crate::models::{
+    League, MiniTeam, RatingHistory, Team, TeamDivAssociation, TeamNameHistory, User,
+};
 use crate::permission::UserPermission;
 use crate::steamapi;
 use crate::CurrentHost;
-use actix_web::{get, post, web, Error, HttpResponse};
+use actix_web::{
+    delete, get, patch, post, put, web, Error, HttpMessage, HttpRequest, HttpResponse,
+};
 use chrono::DateTime;
 use chrono::Utc;
 use deadpool_postgres::{Client, Pool};
+use futures::StreamExt;
+use randomizer::Randomizer;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::apiv1::leagues::HiddenParams;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
 use crate::apiv1::DeepTeamDivResponse;
 use crate::AppState;
 
 use super::HttpResult;
 
+/// A roster, with the name and tag its team went by when it signed up.
+#[derive(Serialize, Deserialize)]
+struct NamedTeamDivAssociation {
+    #[serde(flatten)]
+    pub association: TeamDivAssociation,
+    pub team_name: String,
+    pub team_tag: String,
+}
+
 #[derive(Serialize, Deserialize)]
 struct TeamReturn {
     pub info: Team,
     pub owner: User,
-    pub team_div_assocs: Vec<TeamDivAssociation>,
+    pub team_div_assocs: Vec<NamedTeamDivAssociation>,
+    /// Most recent first.
+    pub name_history: Vec<TeamNameHistory>,
+    /// Every change to the team's rating, newest first.
+    pub rating_history: Vec<RatingHistory>,
 }
 #[get("/api/v1/teams/{team_id}")]
-async fn get_team(state: web::Data<AppState>, path: web::Path<i64>) -> HttpResult {
+async fn get_team(
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
     log::info!("GET /api/v1/teams/{path}");
     let team_id = path.into_inner();
     if team_id < 0 {
-        return Err(MyError::NotFound.into());
+        return Err(MyError::NotFound);
     }
 
     let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
 
-    let team = db::get_team_from_id(&client, team_id).await?;
-    let (owner, team_div_assocs) = futures::try_join!(
-        db::get_user_from_internal_id(&client, team.owner_id),
-        db::team_div_assocs::get_team_tdas_teamid(&client, team_id)
+    let team = db::teams::get_team_from_id(&client, team_id).await?;
+    let (owner, team_div_assocs, name_history, rating_history) = futures::try_join!(
+        db::users::get_user_from_internal_id(&client, team.owner_id),
+        db::teams::get_team_tdas_teamid(&client, team_id, &visibility),
+        db::teams::get_team_name_history(&client, team_id),
+        db::ratings::get_rating_history_for_team_id(&client, team_id)
     )?;
+    let team_div_assocs = team_div_assocs
+        .into_iter()
+        .map(|association| {
+            let (team_name, team_tag) =
+                match db::teams::name_at(&name_history, association.created_at) {
+                    Some(name) => (name.team_name.clone(), name.team_tag.clone()),
+                    None => (team.team_name.clone(), team.team_tag.clone()),
+                };
+            NamedTeamDivAssociation {
+                association,
+                team_name,
+                team_tag,
+            }
+        })
+        .collect();
     let resp = TeamReturn {
         info: team,
         owner,
         team_div_assocs,
+        name_history,
+        rating_history,
     };
 
     Ok(HttpResponse::Ok().json(resp))
@@ -50,26 +97,31 @@ async fn get_team(state: web::Data<AppState>, path: web::Path<i64>) -> HttpResul
 // this will be retroactively changed to be for a teamDivAssociation and not a root team
 // maybe /rootteam/{team_id}?
 #[get("/api/v1/teamdivassocs/{team_id}")]
-async fn get_team_div_assoc(state: web::Data<AppState>, path: web::Path<i64>) -> HttpResult {
+async fn get_team_div_assoc(
+    state: web::Data<AppState>,
+    path: web::Path<i64>,
+    params: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
+) -> HttpResult {
     log::info!("GET /api/v1/teamdivassocs/{path}");
     let team_div_assoc_id = path.into_inner();
     if team_div_assoc_id < 0 {
-        return Err(MyError::NotFound.into());
+        return Err(MyError::NotFound);
     }
     log::debug!("Grabbing pool");
     let client = grab_pool(&state).await?;
+    let visibility = params.visibility(&client, session).await?;
     log::debug!("Getting teamdivassociation");
+    // Rosters in hidden leagues, and private ones, look just like missing ones.
     let team_div_assoc: TeamDivAssociation =
-        db::get_teamdivassociation_from_id(&client, team_div_assoc_id).await?;
+        db::teams::get_team_div_assoc_from_id(&client, team_div_assoc_id, &visibility).await?;
     log::debug!("Getting team");
 
-    let team = db::get_team_from_id(&client, team_div_assoc.teamid)
-        .await
-        .expect("should be able to get team from id");
+    let team = db::teams::get_team_from_id(&client, team_div_assoc.teamid).await?;
 
-    let players = db::get_team_players(&client, &team_div_assoc)
-        .await
-        .expect("should be able to get team players");
+    let players = db::teams::get_team_players(&client, &team_div_assoc).await?;
+    let name_history = db::teams::get_team_name_history(&client, team.id).await?;
+    let team_name_at_signup = db::teams::name_at(&name_history, team_div_assoc.created_at).cloned();
 
     let mut current_players = Vec::with_capacity(players.len());
     let mut past_players = Vec::with_capacity(players.len());
@@ -85,6 +137,7 @@ async fn get_team_div_assoc(state: web::Data<AppState>, path: web::Path<i64>) ->
     let resp = DeepTeamDivResponse {
         association_info: team_div_assoc,
         team_info: team,
+        team_name_at_signup,
         current_players,
         past_players,
     };
@@ -95,30 +148,35 @@ struct TeamInfo {
     pub team_name: String,
     pub team_tag: String,
 }
+
+impl Validate for TeamInfo {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .text("team_name", &mut self.team_name)
+            .length(1, 200)
+            .printable()
+            .allowed_name();
+        check
+            .text("team_tag", &mut self.team_tag)
+            .length(1, 6)
+            .single_word()
+            .allowed_name();
+    }
+}
 #[post("/api/v1/teams")]
 async fn post_team(
     state: web::Data<AppState>,
-    auth_header: web::Header<super::admin::AuthHeader>,
-    new_team: web::Json<TeamInfo>,
+    session: SessionToken,
+    new_team: Valid<TeamInfo>,
 ) -> HttpResult {
     log::info!("POST /api/v1/teams");
-    let auth_token = auth_header.into_inner().0;
-    let client = state.pool.get().await.map_err(MyError::PoolError)?;
-    let user = match get_user_from_auth_token(&client, &auth_token).await {
-        Ok(u) => u,
-        Err(MyError::NotFound) => {
-            return Ok(HttpResponse::InternalServerError()
-                .body(format!("User not found with token {auth_token}")))
-        }
-        Err(a) => {
-            return Ok(HttpResponse::InternalServerError()
-                .body(format!("An unhandled error occurred: {a:?}")))
-        }
-    };
+    let mut client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
     let team = new_team.into_inner();
 
-    let team = db::add_team(
-        &client,
+    let transaction = client.transaction().await?;
+    let team = db::teams::add_team(
+        &transaction,
         &MiniTeam {
             owner_id: user.id,
             team_name: team.team_name,
@@ -126,6 +184,8 @@ async fn post_team(
         },
     )
     .await?;
+    db::teams::record_team_name(&transaction, &team, team.created_at).await?;
+    transaction.commit().await?;
 
     // let resp = db::teams::add_user_team_id(
     //     &client,
@@ -136,3 +196,337 @@ async fn post_team(
     // .await?;
     Ok(HttpResponse::Created().json(team))
 }
+
+/// The owner, or anyone allowed to manage every team, of a team that hasn't been disbanded.
+fn require_team_manager(user: &User, team: &Team) -> Result<(), MyError> {
+    if user.id != team.owner_id && !user.admin_or_perm(UserPermission::CreateTeam) {
+        return Err(MyError::Forbidden("You don't manage this team"));
+    }
+    if team.disbanded_at.is_some() {
+        return Err(MyError::Conflict("This team has been disbanded".to_owned()));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct TeamPatch {
+    pub team_name: Option<String>,
+    pub team_tag: Option<String>,
+}
+
+impl Validate for TeamPatch {
+    fn validate(&mut self, check: &mut Checker) {
+        if let Some(team_name) = &mut self.team_name {
+            check
+                .text("team_name", team_name)
+                .length(1, 200)
+                .printable()
+                .allowed_name();
+        }
+        if let Some(team_tag) = &mut self.team_tag {
+            check
+                .text("team_tag", team_tag)
+                .length(1, 6)
+                .single_word()
+                .allowed_name();
+        }
+    }
+}
+
+/// Rename a team, or change its tag. Only the fields sent are changed.
+#[patch("/api/v1/teams/{team_id}")]
+async fn patch_team(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<i64>,
+    patch: Valid<TeamPatch>,
+) -> HttpResult {
+    log::info!("PATCH /api/v1/teams/{path}");
+    let mut client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
+    let team = db::teams::get_team_from_id(&client, *path).await?;
+    require_team_manager(&user, &team)?;
+
+    let patch = patch.into_inner();
+    let transaction = client.transaction().await?;
+    if let Some(team_tag) = &patch.team_tag {
+        // Under the locks sign ups take too, so no team can take the tag meanwhile.
+        db::teams::lock_team(&transaction, team.id).await?;
+        db::teams::lock_team_leagues(&transaction, team.id).await?;
+        if db::teams::is_tag_taken_in_team_leagues(&transaction, team.id, team_tag).await? {
+            return Err(MyError::invalid_field(
+                "team_tag",
+                "another team in one of this team's leagues already uses this tag",
+            ));
+        }
+    }
+
+    let updated = db::teams::update_team(
+        &transaction,
+        team.id,
+        patch.team_name.as_deref(),
+        patch.team_tag.as_deref(),
+    )
+    .await?;
+    if updated.team_name != team.team_name || updated.team_tag != team.team_tag {
+        db::teams::record_team_name(&transaction, &updated, chrono::offset::Utc::now()).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[derive(Serialize, Deserialize)]
+struct OwnershipTransfer {
+    pub new_owner_id: i64,
+}
+
+impl Validate for OwnershipTransfer {
+    fn validate(&mut self, check: &mut Checker) {
+        if self.new_owner_id <= 0 {
+            check.error("new_owner_id", "no such user");
+        }
+    }
+}
+
+/// Hand a team over to another user.
+///
+/// The new owner has to be on every current roster the old owner is on, so none is left
+/// without a leader. They become its leader, and the old owner stays on as an officer.
+#[post("/api/v1/teams/{team_id}/transfer")]
+async fn transfer_team(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<i64>,
+    transfer: Valid<OwnershipTransfer>,
+) -> HttpResult {
+    log::info!("POST /api/v1/teams/{path}/transfer");
+    let mut client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
+    let team = db::teams::get_team_from_id(&client, *path).await?;
+    require_team_manager(&user, &team)?;
+
+    let new_owner = match db::users::get_user_from_internal_id(&client, transfer.new_owner_id).await
+    {
+        Ok(new_owner) => new_owner,
+        Err(MyError::NotFound) => {
+            return Err(MyError::invalid_field("new_owner_id", "no such user"))
+        }
+        Err(err) => return Err(err),
+    };
+    if new_owner.id == team.owner_id {
+        return Err(MyError::Conflict(
+            "That user already owns this team".to_owned(),
+        ));
+    }
+
+    let transaction = client.transaction().await?;
+    let left_behind = db::teams::count_rosters_without_other_user(
+        &transaction,
+        team.id,
+        team.owner_id,
+        new_owner.id,
+    )
+    .await?;
+    if left_behind > 0 {
+        return Err(MyError::Conflict(
+            "The new owner has to be on every roster the old owner is on".to_owned(),
+        ));
+    }
+    let updated = db::teams::set_team_owner(&transaction, team.id, new_owner.id).await?;
+    db::teams::set_active_affiliation(
+        &transaction,
+        team.id,
+        team.owner_id,
+        db::teams::UserTeamAffiliation::Officer,
+    )
+    .await?;
+    db::teams::set_active_affiliation(
+        &transaction,
+        team.id,
+        new_owner.id,
+        db::teams::UserTeamAffiliation::Leader,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    log::info!(
+        "Team {0} transferred from {1} to {2} by {3}",
+        team.id,
+        team.owner_id,
+        new_owner.id,
+        user.id
+    );
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Where team logos are stored, inside [AppState::upload_dir].
+pub const LOGO_DIR: &str = "team_logos";
+const MAX_LOGO_BYTES: usize = 4 * 1024 * 1024;
+const MIN_LOGO_SIDE: u32 = 32;
+/// Bigger logos are scaled down to fit.
+const MAX_LOGO_SIDE: u32 = 1024;
+/// How much memory decoding an uploaded logo can take.
+const MAX_LOGO_DECODE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Check an uploaded logo is an image we accept, and scale it down if it's too big.
+/// Returns its format and the bytes to store.
+fn prepare_logo(
+    content_type: Option<&str>,
+    bytes: web::Bytes,
+) -> Result<(ImageFormat, web::Bytes), MyError> {
+    let Some(format) = ImageFormat::sniff(&bytes) else {
+        return Err(MyError::invalid_field(
+            "logo",
+            "must be a PNG, JPEG or WebP image",
+        ));
+    };
+    if content_type.and_then(ImageFormat::from_mime) != Some(format) {
+        return Err(MyError::invalid_field(
+            "logo",
+            format!(
+                "is a {format} image, but was sent as {0:?}",
+                content_type.unwrap_or_default()
+            ),
+        ));
+    }
+    let image = format
+        .decode(&bytes, MAX_LOGO_DECODE_BYTES)
+        .map_err(|err| match err {
+            image::ImageError::Limits(_) => {
+                MyError::invalid_field("logo", "is too big to be scaled down")
+            }
+            _ => MyError::invalid_field("logo", "is not a valid image"),
+        })?;
+    let (image, bytes) = match images::shrink_to_fit(&image, MAX_LOGO_SIDE) {
+        Some(shrunk) => {
+            let encoded = format
+                .encode(&shrunk)
+                .map_err(|err| MyError::IoError(std::io::Error::other(err)))?;
+            (shrunk, web::Bytes::from(encoded))
+        }
+        None => (image, bytes),
+    };
+    if image.width() < MIN_LOGO_SIDE || image.height() < MIN_LOGO_SIDE {
+        return Err(MyError::invalid_field(
+            "logo",
+            format!("must be at least {MIN_LOGO_SIDE}x{MIN_LOGO_SIDE} pixels"),
+        ));
+    }
+    Ok((format, bytes))
+}
+
+/// Remove a logo that isn't used anymore. Failing to is only worth a warning.
+async fn remove_logo_file(state: &AppState, logo_url: Option<&str>) {
+    let Some(file_name) =
+        logo_url.and_then(|url| url.strip_prefix(&format!("/uploads/{LOGO_DIR}/")))
+    else {
+        return;
+    };
+    let path = state.upload_dir.join(LOGO_DIR).join(file_name);
+    if let Err(err) = tokio::fs::remove_file(&path).await {
+        log::warn!("Couldn't remove old logo {path:?}: {err}");
+    }
+}
+
+/// Upload a team logo as the raw request body, with its `Content-Type` set.
+#[put("/api/v1/teams/{team_id}/logo")]
+async fn put_team_logo(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<i64>,
+    req: HttpRequest,
+    mut payload: web::Payload,
+) -> HttpResult {
+    log::info!("PUT /api/v1/teams/{path}/logo");
+    let client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
+    let team = db::teams::get_team_from_id(&client, *path).await?;
+    require_team_manager(&user, &team)?;
+
+    let mut bytes = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| MyError::Validation(err.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_LOGO_BYTES {
+            return Err(MyError::invalid_field(
+                "logo",
+                format!("must be at most {0} KiB", MAX_LOGO_BYTES / 1024),
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    // Only the type itself, without parameters like `charset`.
+    let content_type = req
+        .mime_type()
+        .ok()
+        .flatten()
+        .map(|mime| mime.essence_str().to_owned());
+    let bytes = bytes.freeze();
+    // Decoding and resizing is slow, so keep it off the async workers.
+    let (format, bytes) = web::block(move || prepare_logo(content_type.as_deref(), bytes))
+        .await
+        .map_err(|err| MyError::IoError(std::io::Error::other(err)))??;
+
+    // A new name every time, so caches never serve the old logo.
+    let file_name = format!(
+        "{0}-{1}.{2}",
+        team.id,
+        Randomizer::ALPHANUMERIC(12).string().unwrap(),
+        format.extension()
+    );
+    let file_path = state.upload_dir.join(LOGO_DIR).join(&file_name);
+    tokio::fs::write(&file_path, &bytes).await?;
+
+    let logo_url = format!("/uploads/{LOGO_DIR}/{file_name}");
+    let updated = match db::teams::set_team_logo(&client, team.id, Some(&logo_url)).await {
+        Ok(updated) => updated,
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&file_path).await;
+            return Err(err);
+        }
+    };
+    remove_logo_file(&state, team.logo_url.as_deref()).await;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[delete("/api/v1/teams/{team_id}/logo")]
+async fn delete_team_logo(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<i64>,
+) -> HttpResult {
+    log::info!("DELETE /api/v1/teams/{path}/logo");
+    let client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
+    let team = db::teams::get_team_from_id(&client, *path).await?;
+    require_team_manager(&user, &team)?;
+
+    let updated = db::teams::set_team_logo(&client, team.id, None).await?;
+    remove_logo_file(&state, team.logo_url.as_deref()).await;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Disband a team. It's hidden from listings and every current player is taken off
+/// its rosters, but it (and its games) can still be looked up.
+#[delete("/api/v1/teams/{team_id}")]
+async fn disband_team(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    path: web::Path<i64>,
+) -> HttpResult {
+    log::info!("DELETE /api/v1/teams/{path}");
+    let mut client = grab_pool(&state).await?;
+    let user = session.user(&client).await?;
+    let team = db::teams::get_team_from_id(&client, *path).await?;
+    require_team_manager(&user, &team)?;
+
+    let transaction = client.transaction().await?;
+    let disbanded = db::teams::disband_team(&transaction, team.id).await?;
+    db::teams::end_active_memberships(&transaction, team.id).await?;
+    transaction.commit().await?;
+
+    log::info!("Team {0} disbanded by {1}", team.id, user.id);
+    Ok(HttpResponse::Ok().json(disbanded))
+}
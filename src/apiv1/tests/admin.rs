@@ -0,0 +1,551 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, openid_body, TestApp, LUCY, SPEAKER};
+use crate::config::EligibilityAction;
+use crate::steamapi::fake::FakePlayer;
+
+#[actix_web::test]
+async fn post_league_with_divisions() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(LUCY).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier", "Open"],
+        }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let league: Value = test::read_body_json(resp).await;
+    assert_eq!(league["name"], "Summer Cup");
+    let divisions = league["divisions"].as_array().unwrap();
+    assert_eq!(divisions.len(), 2);
+    assert_eq!(divisions[1]["name"], "Open");
+    assert_eq!(divisions[1]["leagueid"], league["id"]);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", league["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["divisions"].as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn post_league_needs_permission() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let league = json!({"name": "Nope", "accepting_teams": true, "is_hidden": false});
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&token))
+        .set_json(&league)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .set_json(&league)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn post_division() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(LUCY).await;
+    let speaker_token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "Cup", "accepting_teams": true, "is_hidden": false}))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(league["divisions"], json!([]));
+
+    let division = json!({"leagueid": league["id"], "name": "Invite"});
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&speaker_token))
+        .set_json(&division)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&token))
+        .set_json(&division)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["name"], "Invite");
+}
+
+#[actix_web::test]
+async fn review_flags() {
+    let Some(app) = TestApp::with_state(|state| {
+        state.eligibility.action = EligibilityAction::Flag;
+        state.eligibility.require_tf2 = true;
+    })
+    .await
+    else {
+        return;
+    };
+    let newcomer = "76561198000000001";
+    app.steam
+        .add_player(FakePlayer::new(newcomer.parse().unwrap(), "newcomer").private());
+    let token = app.login(LUCY).await;
+    let speaker_token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    // Hidden game details break `require_tf2`, so they're flagged on signup.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/verifylogin")
+        .set_json(openid_body(newcomer))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/admin/reviewflags")
+        .insert_header(bearer(&speaker_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/admin/reviewflags")
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let flags: Value = test::read_body_json(resp).await;
+    let flags = flags.as_array().unwrap();
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0]["reasons"], json!(["game details are not public"]));
+
+    let uri = format!("/api/v1/admin/reviewflags/{0}/reviewed", flags[0]["id"]);
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let flag: Value = test::read_body_json(resp).await;
+    assert!(flag["reviewed_at"].is_string());
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/admin/reviewflags")
+        .insert_header(bearer(&token))
+        .to_request();
+    let flags: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(flags, json!([]));
+}
+
+#[actix_web::test]
+async fn post_league_checks_fields() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(LUCY).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&token))
+        .set_json(json!({
+            "name": "x".repeat(51),
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier", ""],
+        }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["details"],
+        json!([
+            {"field": "name", "message": "must be at most 50 characters"},
+            {"field": "divisions[1]", "message": "must not be empty"},
+        ])
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&token))
+        .set_json(json!({"leagueid": 999999, "name": "Invite"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "leagueid");
+}
+
+/// A league with one division, and a roster signed up to it by SPEAKER.
+async fn league_with_roster(app: &TestApp) -> (Value, Value) {
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "Speakers", "team_tag": "SPK"}))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(&service, req).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{0}/teams", league["id"]))
+        .insert_header(bearer(&token))
+        .set_json(json!({"teamid": team["id"], "leagueid": league["id"], "is_private": false}))
+        .to_request();
+    let assoc: Value = test::call_and_read_body_json(&service, req).await;
+    (league, assoc)
+}
+
+#[actix_web::test]
+async fn patch_league_and_division() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let (league, _) = league_with_roster(&app).await;
+    let league_uri = format!("/api/v1/admin/leagues/{0}", league["id"]);
+
+    let req = test::TestRequest::patch()
+        .uri(&league_uri)
+        .insert_header(bearer(&token))
+        .set_json(json!({"is_hidden": true}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::patch()
+        .uri(&league_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"name": "Winter Cup", "is_hidden": true, "archived": true}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["name"], "Winter Cup");
+    assert_eq!(body["is_hidden"], true);
+    assert_eq!(body["accepting_teams"], false);
+    assert!(body["archived_at"].is_string());
+
+    // Archived leagues can't be opened for sign ups until they're brought back.
+    let req = test::TestRequest::patch()
+        .uri(&league_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"accepting_teams": true}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::patch()
+        .uri(&league_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"accepting_teams": true, "archived": false}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["accepting_teams"], true);
+    assert!(body["archived_at"].is_null());
+
+    let division_id = &league["divisions"][0]["id"];
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/admin/divisions/{division_id}"))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"name": "Invite", "prio": 5}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["name"], "Invite");
+    assert_eq!(body["prio"], 5);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/admin/divisions/{division_id}"))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"name": ""}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn delete_league_and_division() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let service = app.service().await;
+    let (league, assoc) = league_with_roster(&app).await;
+
+    // An empty division can go straight away.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"leagueid": league["id"], "name": "Open"}))
+        .to_request();
+    let open: Value = test::call_and_read_body_json(&service, req).await;
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/admin/divisions/{0}", open["id"]))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/admin/divisions/{0}", assoc["divisionid"]))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let league_uri = format!("/api/v1/admin/leagues/{0}", league["id"]);
+    let req = test::TestRequest::delete()
+        .uri(&league_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{league_uri}?force=true"))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", league["id"]))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn place_rosters() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let (league, assoc) = league_with_roster(&app).await;
+    let premier_id = &league["divisions"][0]["id"];
+    assert_eq!(assoc["placement_status"], "pending");
+    assert_eq!(&assoc["requested_divisionid"], premier_id);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"leagueid": league["id"], "name": "Open"}))
+        .to_request();
+    let open: Value = test::call_and_read_body_json(&service, req).await;
+
+    // Asking for a division from somewhere else doesn't work.
+    let other_token = app.add_user("76561198000000003", "newcomer").await;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"team_name": "Newcomers", "team_tag": "NEW"}))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(&service, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{0}/teams", league["id"]))
+        .insert_header(bearer(&other_token))
+        .set_json(json!({
+            "teamid": team["id"],
+            "leagueid": league["id"],
+            "is_private": false,
+            "requested_divisionid": 999999,
+        }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Until it's placed, only the league's admins can look the roster up.
+    let roster_uri = format!("/api/v1/teamdivassocs/{0}", assoc["id"]);
+    let req = test::TestRequest::get()
+        .uri(&format!("{roster_uri}?include_hidden=true"))
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!("{roster_uri}?include_hidden=true"))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let placements_uri = format!("/api/v1/admin/leagues/{0}/placements", league["id"]);
+    let req = test::TestRequest::get()
+        .uri(&placements_uri)
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&placements_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let pending: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(pending.as_array().unwrap().len(), 1);
+    assert_eq!(pending[0]["association_info"]["id"], assoc["id"]);
+
+    let req = test::TestRequest::post()
+        .uri(&placements_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"placements": [
+            {"teamdivid": assoc["id"], "divisionid": 999999},
+        ]}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "placements[0].divisionid");
+
+    let req = test::TestRequest::post()
+        .uri(&placements_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"placements": [
+            {"teamdivid": assoc["id"], "divisionid": open["id"], "note": "New to the game"},
+        ]}))
+        .to_request();
+    let placed: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(placed[0]["divisionid"], open["id"]);
+    assert_eq!(placed[0]["placement_status"], "placed");
+    assert_eq!(placed[0]["placement_note"], "New to the game");
+    assert_eq!(&placed[0]["requested_divisionid"], premier_id);
+
+    let req = test::TestRequest::get()
+        .uri(&placements_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let pending: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(pending, json!([]));
+    let req = test::TestRequest::get().uri(&roster_uri).to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn delete_division_a_placed_roster_asked_for() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let service = app.service().await;
+    let (league, assoc) = league_with_roster(&app).await;
+    let premier_id = &league["divisions"][0]["id"];
+    let count_teams = |body: &Value| {
+        body["divisions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|div| div["teams"].as_array().unwrap().len())
+            .sum::<usize>()
+    };
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"leagueid": league["id"], "name": "Open"}))
+        .to_request();
+    let open: Value = test::call_and_read_body_json(&service, req).await;
+
+    // Sign ups aren't listed until they're placed.
+    let league_uri = format!("/api/v1/leagues/{0}", league["id"]);
+    let req = test::TestRequest::get().uri(&league_uri).to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_teams(&body), 0);
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/api/v1/admin/leagues/{0}/placements",
+            league["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"placements": [
+            {"teamdivid": assoc["id"], "divisionid": open["id"]},
+        ]}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&league_uri).to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_teams(&body), 1);
+
+    // The roster still points at the division it asked for.
+    let premier_uri = format!("/api/v1/admin/divisions/{premier_id}");
+    let req = test::TestRequest::delete()
+        .uri(&premier_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{premier_uri}?force=true"))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["association_info"]["divisionid"], open["id"]);
+    assert_eq!(
+        body["association_info"]["requested_divisionid"],
+        Value::Null
+    );
+}
@@ -0,0 +1,258 @@
+use actix_http::Request;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::{bearer, hide_league, GameFixture, TestApp, LUCY, SPEAKER};
+
+/// A `multipart/form-data` body with `file` as its `field` field.
+fn form(field: &str, filename: &str, file: &[u8]) -> (String, Vec<u8>) {
+    let mut body = format!(
+        "--BOUNDARY\r\n\
+        Content-Disposition: form-data; name=\"{field}\"; filename=\"{filename}\"\r\n\
+        Content-Type: application/octet-stream\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(file);
+    body.extend_from_slice(b"\r\n--BOUNDARY--\r\n");
+    ("multipart/form-data; boundary=BOUNDARY".to_owned(), body)
+}
+
+fn demo(len: usize) -> Vec<u8> {
+    let mut demo = b"HL2DEMO\0".to_vec();
+    demo.extend((0..len).map(|i| (i % 251) as u8));
+    demo
+}
+
+fn upload(game: &Value, token: &str, (content_type, body): (String, Vec<u8>)) -> Request {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/games/{0}/demos", game["id"]))
+        .insert_header(bearer(token))
+        .insert_header(("Content-Type", content_type))
+        .set_payload(body)
+        .to_request()
+}
+
+#[actix_web::test]
+async fn only_players_and_admins_see_demos() {
+    let Some(GameFixture {
+        app, token, game, ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let outsider_token = app.add_user("76561198000000005", "outsider").await;
+    let service = app.service().await;
+
+    let req = upload(
+        &game,
+        &outsider_token,
+        form("demo", "match.dem", &demo(1000)),
+    );
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = upload(&game, &token, form("demo", "match.dem", &demo(1000)));
+    let uploaded: Value = test::call_and_read_body_json(&service, req).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}/demos", game["id"]))
+        .insert_header(bearer(&outsider_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/demos/{0}", uploaded["id"]))
+        .insert_header(bearer(&outsider_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn demo_uploads_are_validated() {
+    let Some(GameFixture {
+        app, token, game, ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+
+    for body in [
+        ("application/octet-stream".to_owned(), demo(1000)),
+        form("file", "match.dem", &demo(1000)),
+        form("demo", "notes.txt", b"gg wp"),
+        form("demo", "huge.dem", &demo(1024 * 1024)),
+    ] {
+        let resp = test::call_service(&service, upload(&game, &token, body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], "demo");
+    }
+    // Nothing is left behind by the failed uploads.
+    assert_eq!(std::fs::read_dir(app.demo_dir()).unwrap().count(), 0);
+}
+
+#[actix_web::test]
+async fn demos_of_hidden_leagues_stay_with_their_players() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        league,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    hide_league(&service, &admin_token, &league["id"]).await;
+
+    let req = upload(&game, &token, form("demo", "match.dem", &demo(1000)));
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}/demos", game["id"]))
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn demo_uploads() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        league,
+        home,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+
+    let file = demo(100_000);
+    let req = upload(&game, &token, form("demo", "../../match.dem", &file));
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let uploaded: Value = test::read_body_json(resp).await;
+    assert_eq!(uploaded["teamid"], home["id"]);
+    assert_eq!(uploaded["file_name"], "match.dem");
+    assert_eq!(uploaded["size_bytes"], file.len());
+    assert_eq!(uploaded["sha256"], format!("{:x}", Sha256::digest(&file)));
+    assert!(uploaded.get("stored_name").is_none());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}/demos", game["id"]))
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    let download_uri = format!("/api/v1/demos/{0}", uploaded["id"]);
+    let req = test::TestRequest::get()
+        .uri(&download_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let disposition = resp.headers().get("Content-Disposition").unwrap();
+    assert!(disposition.to_str().unwrap().starts_with("attachment"));
+    assert_eq!(test::read_body(resp).await, file);
+
+    // Demos past their retention are deleted, files and all.
+    let deleted = crate::demos::clean_up(&app.db.pool, app.demo_dir(), chrono::TimeDelta::days(1))
+        .await
+        .unwrap();
+    assert_eq!(deleted, 0);
+
+    // Unless they might settle a ticket that's still open.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/tickets")
+        .insert_header(bearer(&other_token))
+        .set_json(json!({
+            "leagueid": league["id"],
+            "teamid": game["teamawayid"],
+            "gameid": game["id"],
+            "category": "cheating",
+            "subject": "Wallhacks",
+            "message": "It's all in their demo.",
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let deleted = crate::demos::clean_up(&app.db.pool, app.demo_dir(), chrono::TimeDelta::zero())
+        .await
+        .unwrap();
+    assert_eq!(deleted, 0);
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/api/v1/admin/tickets/{0}/status",
+            body["ticket"]["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"status": "closed"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let deleted = crate::demos::clean_up(&app.db.pool, app.demo_dir(), chrono::TimeDelta::zero())
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(std::fs::read_dir(app.demo_dir()).unwrap().count(), 0);
+    let req = test::TestRequest::get()
+        .uri(&download_uri)
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn demo_limit_holds_and_league_deletion_removes_files() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        league,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let one_more = || upload(&game, &token, form("demo", "match.dem", &demo(1000)));
+
+    for _ in 0..3 {
+        let resp = test::call_service(&service, one_more()).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+    // Two uploads racing for the last spot don't both get it.
+    let (first, second) = futures::join!(
+        test::call_service(&service, one_more()),
+        test::call_service(&service, one_more()),
+    );
+    let mut statuses = [first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::CREATED, StatusCode::CONFLICT]);
+    assert_eq!(std::fs::read_dir(app.demo_dir()).unwrap().count(), 4);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/api/v1/admin/leagues/{0}?force=true",
+            league["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(std::fs::read_dir(app.demo_dir()).unwrap().count(), 0);
+}
@@ -0,0 +1,651 @@
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, hide_league, signed_up_team, GameFixture, TestApp, LUCY, SPEAKER};
+use crate::db;
+use crate::db::teams::UserTeamAffiliation;
+
+#[actix_web::test]
+async fn confirmed_results_update_ratings() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let other_token = app.add_user("76561198000000004", "rival").await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+    let home = signed_up_team(&service, &token, &league["id"], "Speakers", "SPK").await;
+    let away = signed_up_team(&service, &other_token, &league["id"], "Rivals", "RIV").await;
+    assert_eq!(home["rating"], 1500.0);
+
+    let game = json!({
+        "title": "Week 1",
+        "leagueid": league["id"],
+        "teamhomeid": home["id"],
+        "teamawayid": away["id"],
+        "played_at": "2099-06-01T20:00:00Z",
+    });
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&token))
+        .set_json(&game)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let mut same_team = game.clone();
+    same_team["teamawayid"] = home["id"].clone();
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(&same_team)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(&game)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let game: Value = test::read_body_json(resp).await;
+    assert!(game["confirmed_at"].is_null());
+
+    let result_uri = format!("/api/v1/admin/games/{0}/result", game["id"]);
+    let req = test::TestRequest::post()
+        .uri(&result_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"home_score": 3, "away_score": 1}))
+        .to_request();
+    let game: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(game["home_score"], 3);
+    assert!(game["confirmed_at"].is_string());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", home["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["rating"], 1516.0);
+    assert_eq!(body["rating_history"][0]["rating_before"], 1500.0);
+    assert_eq!(body["rating_history"][0]["gameid"], game["id"]);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", away["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["rating"], 1484.0);
+
+    // The players on each roster move with their team.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me?deep=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["rating"], 1516.0);
+    assert_eq!(body["rating_history"].as_array().unwrap().len(), 1);
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me")
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["rating"], 1484.0);
+
+    // A result only counts once.
+    let req = test::TestRequest::post()
+        .uri(&result_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"home_score": 0, "away_score": 5}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}", game["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["away_score"], 1);
+}
+
+#[actix_web::test]
+async fn results_confirmed_at_once_build_on_each_other() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        league,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "title": "Week 2",
+            "leagueid": league["id"],
+            "teamhomeid": home["id"],
+            "teamawayid": away["id"],
+            "played_at": "2099-06-10T20:00:00Z",
+        }))
+        .to_request();
+    let rematch: Value = test::call_and_read_body_json(&service, req).await;
+
+    let result = |game: &Value| {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/admin/games/{0}/result", game["id"]))
+            .insert_header(bearer(&admin_token))
+            .set_json(json!({"home_score": 3, "away_score": 1}))
+            .to_request()
+    };
+    let (first, second) = futures::join!(
+        test::call_service(&service, result(&game)),
+        test::call_service(&service, result(&rematch)),
+    );
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::OK);
+
+    // Whichever went second started from where the first left off.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", home["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let history = body["rating_history"].as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["rating_before"], history[1]["rating_after"]);
+    assert_eq!(body["info"]["rating"], history[0]["rating_after"]);
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me?deep=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let history = body["rating_history"].as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["rating_before"], history[1]["rating_after"]);
+    assert_eq!(body["info"]["rating"], history[0]["rating_after"]);
+}
+
+#[actix_web::test]
+async fn leaders_negotiate_game_times() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let other_token = app.add_user("76561198000000004", "rival").await;
+    let outsider_token = app.add_user("76561198000000005", "outsider").await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+    let home = signed_up_team(&service, &token, &league["id"], "Speakers", "SPK").await;
+    let away = signed_up_team(&service, &other_token, &league["id"], "Rivals", "RIV").await;
+
+    let mut game = json!({
+        "title": "Week 1",
+        "leagueid": league["id"],
+        "teamhomeid": home["id"],
+        "teamawayid": away["id"],
+        "played_at": "2099-06-03T20:00:00Z",
+        "window_starts_at": "2099-06-01T00:00:00Z",
+        "window_ends_at": "2099-06-07T23:59:59Z",
+        "schedule_deadline": "2099-05-30T00:00:00Z",
+    });
+    let mut outside = game.clone();
+    outside["played_at"] = json!("2099-06-10T20:00:00Z");
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(&outside)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(&game)
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&service, req).await;
+    let proposals_uri = format!("/api/v1/games/{0}/proposals", created["id"]);
+
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2099-06-10T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&outsider_token))
+        .set_json(json!({"proposed_time": "2099-06-02T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2099-06-02T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let first: Value = test::read_body_json(resp).await;
+    assert_eq!(first["teamid"], home["id"]);
+
+    // Teams can't accept their own proposals.
+    let req = test::TestRequest::post()
+        .uri(&format!("{proposals_uri}/{0}/accept", first["id"]))
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // The other team counters, which replaces the first proposal.
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"proposed_time": "2099-06-04T21:00:00Z"}))
+        .to_request();
+    let counter: Value = test::call_and_read_body_json(&service, req).await;
+
+    let req = test::TestRequest::get()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["proposals"][0]["id"], counter["id"]);
+    assert_eq!(body["proposals"][0]["status"], "pending");
+    assert_eq!(body["proposals"][1]["status"], "superseded");
+    let req = test::TestRequest::get()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&outsider_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{proposals_uri}/{0}/accept", first["id"]))
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{proposals_uri}/{0}/accept", counter["id"]))
+        .insert_header(bearer(&token))
+        .to_request();
+    let scheduled: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(scheduled["played_at"], "2099-06-04T21:00:00Z");
+    assert!(scheduled["time_agreed_at"].is_string());
+
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2099-06-05T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // Past the deadline, the game keeps its default time.
+    game["schedule_deadline"] = json!("2020-01-01T00:00:00Z");
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(&game)
+        .to_request();
+    let overdue: Value = test::call_and_read_body_json(&service, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/games/{0}/proposals", overdue["id"]))
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2099-06-02T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    assert_eq!(crate::scheduling::settle(&app.db.pool).await.unwrap(), 1);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}", overdue["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["played_at"], "2099-06-03T20:00:00Z");
+    assert!(body["time_agreed_at"].is_string());
+}
+
+#[actix_web::test]
+async fn negotiation_ends_with_the_window() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        league,
+        home,
+        away,
+        rival,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let post_game = |game: Value| {
+        let service = &service;
+        let admin_token = &admin_token;
+        async move {
+            let req = test::TestRequest::post()
+                .uri("/api/v1/admin/games")
+                .insert_header(bearer(admin_token))
+                .set_json(&game)
+                .to_request();
+            test::call_service(service, req).await
+        }
+    };
+    let mut game = json!({
+        "title": "Week 2",
+        "leagueid": league["id"],
+        "teamhomeid": home["id"],
+        "teamawayid": away["id"],
+        "played_at": "2099-06-03T20:00:00Z",
+        "window_starts_at": "2099-06-01T00:00:00Z",
+        "window_ends_at": "2099-06-07T23:59:59Z",
+        "schedule_deadline": "2099-06-10T00:00:00Z",
+    });
+
+    let resp = post_game(game.clone()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "schedule_deadline");
+
+    // Without a deadline, times can be proposed until the window ends.
+    game["schedule_deadline"] = Value::Null;
+    game["window_starts_at"] = json!("2020-01-01T00:00:00Z");
+    let resp = post_game(game.clone()).await;
+    let open: Value = test::read_body_json(resp).await;
+    let proposals_uri = format!("/api/v1/games/{0}/proposals", open["id"]);
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2021-01-01T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "proposed_time");
+    let req = test::TestRequest::post()
+        .uri(&proposals_uri)
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2099-06-02T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    game["window_starts_at"] = json!("2020-01-01T00:00:00Z");
+    game["window_ends_at"] = json!("2020-01-07T23:59:59Z");
+    game["played_at"] = json!("2020-01-03T20:00:00Z");
+    let resp = post_game(game).await;
+    let closed: Value = test::read_body_json(resp).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/games/{0}/proposals", closed["id"]))
+        .insert_header(bearer(&token))
+        .set_json(json!({"proposed_time": "2099-06-02T20:00:00Z"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    assert_eq!(crate::scheduling::settle(&app.db.pool).await.unwrap(), 1);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}", closed["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert!(body["time_agreed_at"].is_string());
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}", open["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert!(body["time_agreed_at"].is_null());
+
+    // Settling it between an accept's checks and its write leaves the game alone.
+    let pending: Value = test::call_and_read_body_json(
+        &service,
+        test::TestRequest::get()
+            .uri(&proposals_uri)
+            .insert_header(bearer(&other_token))
+            .to_request(),
+    )
+    .await;
+    let client = app.db.pool.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE games SET time_agreed_at=now() WHERE id=$1",
+            &[&open["id"].as_i64().unwrap()],
+        )
+        .await
+        .unwrap();
+    let accepted = db::games::accept_proposal(
+        &client,
+        pending["proposals"][0]["id"].as_i64().unwrap(),
+        rival.id,
+    )
+    .await;
+    assert!(matches!(accepted, Err(crate::errors::MyError::Conflict(_))));
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}", open["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["played_at"], open["played_at"]);
+}
+
+#[actix_web::test]
+async fn games_in_hidden_leagues_look_missing() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        league,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+
+    for uri in [
+        format!("/api/v1/games/{0}", game["id"]),
+        format!("/api/v1/games/{0}/stats", game["id"]),
+    ] {
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+    }
+
+    hide_league(&service, &admin_token, &league["id"]).await;
+    for uri in [
+        format!("/api/v1/games/{0}", game["id"]),
+        format!("/api/v1/games/{0}/stats", game["id"]),
+    ] {
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}?include_hidden=true"))
+            .insert_header(bearer(&token))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}?include_hidden=true"))
+            .insert_header(bearer(&admin_token))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+    }
+}
+
+#[actix_web::test]
+async fn ratings_follow_the_lineups() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        home,
+        game,
+        speaker,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let bench_token = app.add_user("76561198000000005", "bench").await;
+    let bench = app.user("76561198000000005").await;
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+    let teamdivid: i64 = client
+        .query_one(
+            "SELECT id FROM teamDivAssociations WHERE teamid=$1",
+            &[&home["id"].as_i64().unwrap()],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    db::teams::add_user_team_id(&client, bench.id, teamdivid, UserTeamAffiliation::Member)
+        .await
+        .unwrap();
+
+    // Only the home team says who played.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/games/{0}/lineup", game["id"]))
+        .insert_header(bearer(&token))
+        .set_json(json!({"userids": [speaker.id]}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/result", game["id"]))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"home_score": 3, "away_score": 1}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let rating = |token: String| {
+        let service = &service;
+        async move {
+            let req = test::TestRequest::get()
+                .uri("/api/v1/user/me")
+                .insert_header(bearer(&token))
+                .to_request();
+            let body: Value = test::call_and_read_body_json(service, req).await;
+            body["info"]["rating"].as_f64().unwrap()
+        }
+    };
+    assert_eq!(rating(token).await, 1516.0);
+    // Sat the game out, so it didn't count for them.
+    assert_eq!(rating(bench_token).await, 1500.0);
+    // The away team's roster stands in for its missing lineup.
+    assert_eq!(rating(other_token).await, 1484.0);
+}
+
+#[actix_web::test]
+async fn swiss_pairings_are_seeded() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        league,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let third_token = app.add_user("76561198000000005", "third").await;
+    let service = app.service().await;
+    let third = signed_up_team(&service, &third_token, &league["id"], "Thirds", "THR").await;
+    let division_id = &league["divisions"][0]["id"];
+
+    let placements_uri = format!("/api/v1/admin/leagues/{0}/placements", league["id"]);
+    let req = test::TestRequest::get()
+        .uri(&placements_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let pending: Value = test::call_and_read_body_json(&service, req).await;
+    let placements: Vec<Value> = pending
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|roster| {
+            json!({"teamdivid": roster["association_info"]["id"], "divisionid": division_id})
+        })
+        .collect();
+    let req = test::TestRequest::post()
+        .uri(&placements_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({ "placements": placements }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/result", game["id"]))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"home_score": 3, "away_score": 1}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let pairings_uri = format!("/api/v1/admin/divisions/{division_id}/pairings");
+    let req = test::TestRequest::get()
+        .uri(&pairings_uri)
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // The winner leads, and the loser's rating has dropped below the newcomer's.
+    let req = test::TestRequest::get()
+        .uri(&pairings_uri)
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let round: Value = test::call_and_read_body_json(&service, req).await;
+    let pairings = round["pairings"].as_array().unwrap();
+    assert_eq!(pairings.len(), 1);
+    assert_eq!(pairings[0]["home"]["id"], home["id"]);
+    assert_eq!(pairings[0]["away"]["id"], third["id"]);
+    assert_eq!(round["bye"]["id"], away["id"]);
+}
@@ -0,0 +1,223 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::Value;
+
+use super::{bearer, seed_league, TestApp, LUCY, SPEAKER};
+use crate::admin::MiniDivision;
+use crate::db;
+use crate::models::{MiniLeague, MiniTeam};
+
+/// Make a league with one division and one team in it, returning the league's id.
+async fn seed_league_with_team(app: &TestApp) -> i64 {
+    let league_id = seed_league(app, true, true).await;
+    let client = app.db.pool.get().await.unwrap();
+    let division = &db::leagues::get_divs_for_league_id(&client, league_id)
+        .await
+        .unwrap()[0];
+
+    let owner = db::users::get_user_from_steamid(&client, LUCY)
+        .await
+        .unwrap();
+    let team = db::teams::add_team(
+        &client,
+        &MiniTeam {
+            owner_id: owner.id,
+            team_name: "Lucy's Team".to_owned(),
+            team_tag: "LT".to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    db::teams::add_team_div_assoc(
+        &client,
+        db::teams::MiniTeamDivAssociation {
+            roster_name: None,
+            teamid: team.id,
+            divisionid: division.id,
+            is_private: false,
+            requested_divisionid: None,
+        },
+    )
+    .await
+    .unwrap();
+    league_id
+}
+
+#[actix_web::test]
+async fn get_all_leagues() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::get().uri("/api/v1/leagues").to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body, serde_json::json!([]));
+
+    let league_id = seed_league_with_team(&app).await;
+    let req = test::TestRequest::get().uri("/api/v1/leagues").to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let leagues = body.as_array().unwrap();
+    assert_eq!(leagues.len(), 1);
+    assert_eq!(leagues[0]["info"]["id"], league_id);
+    assert_eq!(leagues[0]["divisions"][0]["info"]["name"], "Main");
+    // Teams are only listed for a single league.
+    assert!(leagues[0]["divisions"][0]["teams"].is_null());
+}
+
+#[actix_web::test]
+async fn get_league() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league_with_team(&app).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{league_id}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["info"]["name"], "Test League");
+    let teams = body["divisions"][0]["teams"].as_array().unwrap();
+    assert_eq!(teams.len(), 1);
+    assert_eq!(teams[0]["team_info"]["team_name"], "Lucy's Team");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", league_id + 1))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn hidden_leagues_and_private_rosters() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let visible_id = seed_league_with_team(&app).await;
+    let client = app.db.pool.get().await.unwrap();
+    let visible_div = db::leagues::get_divs_for_league_id(&client, visible_id)
+        .await
+        .unwrap()
+        .remove(0);
+    let speaker = db::users::get_user_from_steamid(&client, SPEAKER)
+        .await
+        .unwrap();
+    let team = db::teams::add_team(
+        &client,
+        &MiniTeam {
+            owner_id: speaker.id,
+            team_name: "Secret Team".to_owned(),
+            team_tag: "SEC".to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    db::teams::add_team_div_assoc(
+        &client,
+        db::teams::MiniTeamDivAssociation {
+            roster_name: None,
+            teamid: team.id,
+            divisionid: visible_div.id,
+            is_private: true,
+            requested_divisionid: None,
+        },
+    )
+    .await
+    .unwrap();
+    let hidden = db::leagues::add_league(
+        &client,
+        MiniLeague {
+            name: "Hidden League".to_owned(),
+            accepting_teams: false,
+            is_hidden: true,
+            seasonid: None,
+        },
+    )
+    .await
+    .unwrap();
+    let hidden_div = db::leagues::add_division(
+        &client,
+        MiniDivision {
+            leagueid: hidden.id,
+            name: "Main".to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+
+    let token = app.login(SPEAKER).await;
+    let admin_token = app.login(LUCY).await;
+    let service = app.service().await;
+    let count_leagues = |body: &Value| body.as_array().unwrap().len();
+    let count_teams = |body: &Value| body["divisions"][0]["teams"].as_array().unwrap().len();
+
+    let req = test::TestRequest::get().uri("/api/v1/leagues").to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_leagues(&body), 1);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", hidden.id))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{visible_id}"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_teams(&body), 1);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/leagues?include_hidden=true")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // Players without any say over a league see what everyone else does.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/leagues?include_hidden=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_leagues(&body), 1);
+
+    // Division admins see the hidden leagues they help run.
+    client
+        .execute(
+            "INSERT INTO division_admins(divisionid, userid) VALUES ($1, $2)",
+            &[&hidden_div.id, &speaker.id],
+        )
+        .await
+        .unwrap();
+    let req = test::TestRequest::get()
+        .uri("/api/v1/leagues?include_hidden=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_leagues(&body), 2);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{visible_id}?include_hidden=true"))
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_teams(&body), 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{visible_id}?include_hidden=true"))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_teams(&body), 2);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/leagues/{0}?include_hidden=true",
+            hidden.id
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
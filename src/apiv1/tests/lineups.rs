@@ -0,0 +1,312 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, hide_league, GameFixture, TestApp, LUCY, SPEAKER};
+use crate::db;
+
+/// Ask to play a mercenary in `game` for `token`'s team.
+fn mercenary_request(game: &Value, token: &str, body: Value) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/games/{0}/mercenaries", game["id"]))
+        .insert_header(bearer(token))
+        .set_json(body)
+        .to_request()
+}
+
+fn lineup(game: &Value, token: &str, userids: &[i64]) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/games/{0}/lineup", game["id"]))
+        .insert_header(bearer(token))
+        .set_json(json!({ "userids": userids }))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn only_leaders_and_admins_manage_lineups() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        game,
+        speaker,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    app.add_user("76561198000000005", "ringer").await;
+    let ringer = app.user("76561198000000005").await;
+    let service = app.service().await;
+
+    // League admins review mercenaries, but don't declare lineups for the teams.
+    let resp = test::call_service(&service, lineup(&game, &admin_token, &[speaker.id])).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = test::call_service(
+        &service,
+        mercenary_request(&game, &admin_token, json!({"userid": ringer.id})),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let request: Value = test::call_and_read_body_json(
+        &service,
+        mercenary_request(&game, &token, json!({"userid": ringer.id})),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/api/v1/admin/mercenaries/{0}/approve",
+            request["id"]
+        ))
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn lineups_and_mercenary_requests_are_validated() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        game,
+        speaker,
+        rival,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    app.add_user("76561198000000005", "ringer").await;
+    let ringer = app.user("76561198000000005").await;
+    let service = app.service().await;
+
+    for (userids, field) in [
+        (vec![], "userids"),
+        (vec![speaker.id, speaker.id], "userids[1]"),
+        (vec![speaker.id, ringer.id], "userids[1]"),
+    ] {
+        let resp = test::call_service(&service, lineup(&game, &token, &userids)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], field);
+    }
+
+    // Rostered players don't need a request, and nobody needs two.
+    let resp = test::call_service(
+        &service,
+        mercenary_request(&game, &token, json!({"userid": rival.id})),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(
+        &service,
+        mercenary_request(&game, &token, json!({"userid": ringer.id})),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let request: Value = test::read_body_json(resp).await;
+    let resp = test::call_service(
+        &service,
+        mercenary_request(&game, &token, json!({"userid": ringer.id})),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // Requests are only reviewed once.
+    let review = |action: &str| {
+        test::TestRequest::post()
+            .uri(&format!(
+                "/api/v1/admin/mercenaries/{0}/{action}",
+                request["id"]
+            ))
+            .insert_header(bearer(&admin_token))
+            .to_request()
+    };
+    let resp = test::call_service(&service, review("approve")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&service, review("deny")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // Nobody plays for both teams.
+    let resp = test::call_service(&service, lineup(&game, &token, &[speaker.id, ringer.id])).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(
+        &service,
+        lineup(&game, &other_token, &[rival.id, ringer.id]),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "userids[1]");
+}
+
+#[actix_web::test]
+async fn mercenaries_play_once_approved() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        home,
+        game,
+        speaker,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    app.add_user("76561198000000005", "ringer").await;
+    let ringer = app.user("76561198000000005").await;
+    let service = app.service().await;
+
+    let resp = test::call_service(
+        &service,
+        mercenary_request(
+            &game,
+            &token,
+            json!({"userid": ringer.id, "note": "Our medic is away"}),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let request: Value = test::read_body_json(resp).await;
+    assert_eq!(request["status"], "pending");
+    assert_eq!(request["teamid"], home["id"]);
+
+    // Until the request is approved, the mercenary is flagged as unrostered.
+    let body: Value =
+        test::call_and_read_body_json(&service, lineup(&game, &token, &[speaker.id, ringer.id]))
+            .await;
+    assert_eq!(body["players"][0]["issues"], json!([]));
+    assert_eq!(body["players"][1]["user"]["id"], ringer.id);
+    assert_eq!(body["players"][1]["issues"], json!(["not_rostered"]));
+    assert_eq!(body["players"][1]["mercenary"], false);
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/api/v1/admin/mercenaries/{0}/approve",
+            request["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["status"], "approved");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}/lineups", game["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["players"][1]["mercenary"], true);
+    assert_eq!(body["players"][1]["issues"], json!([]));
+    assert_eq!(body["mercenary_requests"][0]["id"], request["id"]);
+}
+
+#[actix_web::test]
+async fn lineups_in_hidden_leagues_look_missing() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        league,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let uri = format!("/api/v1/games/{0}/lineups", game["id"]);
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    hide_league(&service, &admin_token, &league["id"]).await;
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!("{uri}?include_hidden=true"))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn banned_players_are_flagged() {
+    let Some(GameFixture {
+        app,
+        token,
+        other_token,
+        league,
+        home,
+        away,
+        game,
+        admin: lucy,
+        speaker,
+        rival,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+
+    // An unreviewed eligibility flag is not a ban.
+    db::users::add_review_flag(&client, speaker.id, &["no TF2".to_owned()])
+        .await
+        .unwrap();
+    let leagueid = league["id"].as_i64().unwrap();
+    db::users::add_ban(&client, rival.id, leagueid, None, lucy.id)
+        .await
+        .unwrap();
+    for (token, userid) in [(&token, speaker.id), (&other_token, rival.id)] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/v1/games/{0}/lineup", game["id"]))
+            .insert_header(bearer(token))
+            .set_json(json!({"userids": [userid]}))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}/lineups", game["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["players"][0]["teamid"], home["id"]);
+    assert_eq!(body["players"][0]["issues"], json!(["flagged"]));
+    assert_eq!(body["players"][1]["teamid"], away["id"]);
+    assert_eq!(body["players"][1]["issues"], json!(["banned"]));
+}
+
+#[actix_web::test]
+async fn lineups_racing_for_a_player_conflict() {
+    let Some(GameFixture {
+        app,
+        home,
+        away,
+        game,
+        speaker,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let client = app.db.pool.get().await.unwrap();
+    let gameid = game["id"].as_i64().unwrap();
+
+    // As if the other team's lineup was saved after this one's checks passed.
+    db::lineups::set_lineup(&client, gameid, away["id"].as_i64().unwrap(), &[speaker.id])
+        .await
+        .unwrap();
+    let err = db::lineups::set_lineup(&client, gameid, home["id"].as_i64().unwrap(), &[speaker.id])
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), "conflict");
+}
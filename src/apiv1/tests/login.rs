@@ -0,0 +1,232 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, openid_body, TestApp, LUCY};
+use crate::apiv1::session::{CSRF_COOKIE, CSRF_HEADER, SESSION_COOKIE};
+use crate::config::{AuthMode, EligibilityAction};
+use crate::steamapi::fake::FakePlayer;
+
+const NEWCOMER: &str = "76561198000000001";
+
+#[actix_web::test]
+async fn first_login_creates_user() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    app.steam
+        .add_player(FakePlayer::new(NEWCOMER.parse().unwrap(), "newcomer"));
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/verifylogin")
+        .set_json(openid_body(NEWCOMER))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["valid"], true);
+    assert!(body["csrf_token"].is_null());
+    let token = body["token_info"]["token"].as_str().unwrap().to_owned();
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me?deep=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["username"], "newcomer");
+    assert_eq!(body["username_history"][0]["username"], "newcomer");
+
+    // Logging in again hands back the same session.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/verifylogin")
+        .set_json(openid_body(NEWCOMER))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["token_info"]["token"], token.as_str());
+}
+
+#[actix_web::test]
+async fn login_rejected_by_steam() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    app.steam.reject_openid();
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/verifylogin")
+        .set_json(openid_body(LUCY))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "unauthorized");
+}
+
+#[actix_web::test]
+async fn ineligible_login_is_blocked() {
+    let Some(app) = TestApp::with_state(|state| {
+        state.eligibility.action = EligibilityAction::Block;
+        state.eligibility.require_public_profile = true;
+    })
+    .await
+    else {
+        return;
+    };
+    app.steam
+        .add_player(FakePlayer::new(NEWCOMER.parse().unwrap(), "newcomer").private());
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/verifylogin")
+        .set_json(openid_body(NEWCOMER))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "ineligible");
+    assert_eq!(body["details"], json!([{"reason": "private_profile"}]));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/steamid/{NEWCOMER}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn logout_revokes_the_token() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(LUCY).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/logout")
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me")
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // There's nothing left to log out of.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/logout")
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn logout_still_takes_the_token_in_the_body() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(LUCY).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/logout")
+        .set_json(json!({"auth_token": token}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me")
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn cookie_sessions_need_a_csrf_token() {
+    let Some(app) = TestApp::with_state(|state| state.auth_mode = AuthMode::Cookie).await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/verifylogin")
+        .set_json(openid_body(LUCY))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cookies: Vec<_> = resp.response().cookies().map(|c| c.into_owned()).collect();
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["token_info"].is_null());
+    let csrf = body["csrf_token"].as_str().unwrap().to_owned();
+    let session = cookies
+        .iter()
+        .find(|cookie| cookie.name() == SESSION_COOKIE)
+        .unwrap();
+    assert!(session.http_only().unwrap_or(false));
+    let csrf_cookie = cookies
+        .iter()
+        .find(|cookie| cookie.name() == CSRF_COOKIE)
+        .unwrap();
+    assert_eq!(csrf_cookie.value(), csrf);
+
+    // Bearer tokens aren't accepted in cookie mode.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me")
+        .insert_header(bearer(session.value()))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me")
+        .cookie(session.clone())
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let team = json!({"team_name": "Cookies", "team_tag": "CK"});
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .cookie(session.clone())
+        .cookie(csrf_cookie.clone())
+        .set_json(&team)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .cookie(session.clone())
+        .cookie(csrf_cookie.clone())
+        .insert_header((CSRF_HEADER, csrf.as_str()))
+        .set_json(&team)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // Nor can another site log you out.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/logout")
+        .cookie(session.clone())
+        .cookie(csrf_cookie.clone())
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/logout")
+        .cookie(session.clone())
+        .cookie(csrf_cookie.clone())
+        .insert_header((CSRF_HEADER, csrf.as_str()))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,306 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, hide_league, GameFixture, TestApp, LUCY, SPEAKER};
+use crate::steamid::SteamId;
+use crate::{db, logstf};
+
+fn steam3(steamid: &str) -> String {
+    steamid.parse::<SteamId>().unwrap().steam3()
+}
+
+/// A log of the home team, on blue, against the away team.
+fn sample_log() -> Value {
+    json!({
+        "info": {"map": "pass_arena2", "title": "Speakers vs Rivals"},
+        "players": {
+            steam3(SPEAKER): {
+                "team": "Blue",
+                "deaths": 4,
+                "passtime": {"goals": 3, "assists": 1, "passes": 12},
+            },
+            steam3("76561198000000004"): {
+                "team": "Red",
+                "deaths": 6,
+                "passtime": {"intercepts": 2, "steals": 1},
+            },
+        },
+    })
+}
+
+fn upload(game: &Value, token: &str, log: &Value) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/log", game["id"]))
+        .insert_header(bearer(token))
+        .set_json(log)
+        .to_request()
+}
+
+#[actix_web::test]
+async fn only_admins_import_logs() {
+    let Some(GameFixture {
+        app,
+        token,
+        other_token,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+
+    let resp = test::call_service(&service, upload(&game, &token, &sample_log())).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/log/3500000", game["id"]))
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn logs_are_validated() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/log", game["id"]))
+        .insert_header(bearer(&admin_token))
+        .set_payload("not a log")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Everyone in the log has to be one of ours, on the roster of the side they played.
+    let stranger = SteamId::from_account_id(5).unwrap().steam3();
+    let mut log = sample_log();
+    log["players"][&stranger] = json!({"team": "Red"});
+    let resp = test::call_service(&service, upload(&game, &admin_token, &log)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], format!("players.{stranger}"));
+
+    let mut same_side = log.clone();
+    same_side["players"][steam3("76561198000000004")]["team"] = json!("Blue");
+    let resp = test::call_service(&service, upload(&game, &admin_token, &same_side)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/log/3500001", game["id"]))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "log_id");
+}
+
+#[actix_web::test]
+async fn stats_of_hidden_leagues_look_missing() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        league,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let resp = test::call_service(&service, upload(&game, &admin_token, &sample_log())).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let uri = format!("/api/v1/games/{0}/stats", game["id"]);
+
+    hide_league(&service, &admin_token, &league["id"]).await;
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!("{uri}?include_hidden=true"))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn import_logs() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    // An import makes do with the one connection its request took.
+    let mut others = Vec::new();
+    while others.len() + 1 < app.db.pool.status().max_size {
+        others.push(app.db.pool.get().await.unwrap());
+    }
+
+    let resp = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        test::call_service(&service, upload(&game, &admin_token, &sample_log())),
+    )
+    .await
+    .expect("the import should finish with one connection");
+    drop(others);
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["log"]["map"], "pass_arena2");
+    assert_eq!(body["log"]["blue_teamid"], home["id"]);
+    assert_eq!(body["log"]["red_teamid"], away["id"]);
+    assert!(body["log"]["logid"].is_null());
+    let speaker = body["players"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|player| player["teamid"] == home["id"])
+        .unwrap();
+    assert_eq!(speaker["goals"], 3);
+    assert_eq!(speaker["passes"], 12);
+    assert_eq!(speaker["deaths"], 4);
+    assert_eq!(speaker["steals"], 0);
+
+    // Fetching from logs.tf replaces the upload.
+    app.logs.add_log(
+        3_500_000,
+        serde_json::from_value::<logstf::Log>(sample_log()).unwrap(),
+    );
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/log/3500000", game["id"]))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}/stats", game["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["log"]["logid"], 3_500_000);
+    assert_eq!(body["players"].as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn logs_are_checked_against_the_lineups() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        home,
+        game,
+        speaker,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    app.add_user("76561198000000005", "ringer").await;
+    let ringer = app.user("76561198000000005").await;
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+    let log_uri = format!("/api/v1/admin/games/{0}/log", game["id"]);
+    let log = json!({
+        "info": {"map": "pass_arena2", "title": "Speakers vs Rivals"},
+        "players": {
+            steam3(SPEAKER): {"team": "Blue"},
+            steam3("76561198000000005"): {"team": "Blue"},
+            steam3("76561198000000004"): {"team": "Red"},
+        },
+    });
+
+    // Not on the home team's roster, and it didn't declare a lineup.
+    let req = test::TestRequest::post()
+        .uri(&log_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(&log)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["details"][0]["field"],
+        format!("players.{0}", steam3("76561198000000005"))
+    );
+
+    db::lineups::set_lineup(
+        &client,
+        game["id"].as_i64().unwrap(),
+        home["id"].as_i64().unwrap(),
+        &[speaker.id, ringer.id],
+    )
+    .await
+    .unwrap();
+    let req = test::TestRequest::post()
+        .uri(&log_uri)
+        .insert_header(bearer(&admin_token))
+        .set_json(&log)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn logs_imported_at_once_add_up() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        league,
+        home,
+        away,
+        game,
+        speaker,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "title": "Week 2",
+            "leagueid": league["id"],
+            "teamhomeid": home["id"],
+            "teamawayid": away["id"],
+            "played_at": "2099-06-10T20:00:00Z",
+        }))
+        .to_request();
+    let rematch: Value = test::call_and_read_body_json(&service, req).await;
+
+    // Both rebuild the same teams' rollups, so the second has to see the first's stats.
+    let (first, second) = futures::join!(
+        test::call_service(&service, upload(&game, &admin_token, &sample_log())),
+        test::call_service(&service, upload(&rematch, &admin_token, &sample_log())),
+    );
+    assert_eq!(first.status(), StatusCode::CREATED);
+    assert_eq!(second.status(), StatusCode::CREATED);
+    let client = app.db.pool.get().await.unwrap();
+    let row = client
+        .query_one(
+            "SELECT games_played, goals FROM player_league_stats WHERE userid=$1",
+            &[&speaker.id],
+        )
+        .await
+        .unwrap();
+    assert_eq!(row.get::<_, i32>(0), 2);
+    assert_eq!(row.get::<_, i32>(1), 6);
+}
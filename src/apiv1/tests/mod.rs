@@ -0,0 +1,358 @@
+// Request/response tests for every route, run against a throwaway database, a fake Steam
+// and a fake logs.tf.
+//
+// Needs `TEST_DATABASE_URL`, see `db::test_db`; every test is skipped with `SKIP_DB_TESTS` set.
+use std::sync::Arc;
+
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+
+use super::validation::WordBlocklist;
+use super::AppState;
+use crate::admin::MiniDivision;
+use crate::config::{AuthMode, EligibilityConfig};
+use crate::db;
+use crate::db::test_db::TestDb;
+use crate::logstf::fake::FakeLogs;
+use crate::models::{MiniLeague, User};
+use crate::steamapi::fake::FakeSteam;
+use crate::CurrentHost;
+
+mod admin;
+mod demos;
+mod games;
+mod leagues;
+mod lineups;
+mod login;
+mod logs;
+mod seasons;
+mod stats;
+mod teams;
+mod tickets;
+mod users;
+
+/// Users loaded from `sql/test_data.sql`.
+pub const LUCY: &str = "76561198025040446";
+pub const AVG: &str = "76561198307081282";
+/// The only one without any permissions.
+pub const SPEAKER: &str = "76561198138221795";
+/// Made by [GameFixture] to lead the away team.
+pub const RIVAL: &str = "76561198000000004";
+
+pub struct TestApp {
+    pub db: TestDb,
+    pub steam: Arc<FakeSteam>,
+    pub logs: Arc<FakeLogs>,
+    state: web::Data<AppState>,
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.state.upload_dir);
+        let _ = std::fs::remove_dir_all(&self.state.demo_dir);
+    }
+}
+
+impl TestApp {
+    pub async fn new() -> Option<TestApp> {
+        Self::with_state(|_| {}).await
+    }
+
+    /// Like [TestApp::new], letting the test change the settings the app runs with first.
+    pub async fn with_state(change: impl FnOnce(&mut AppState)) -> Option<TestApp> {
+        let db = TestDb::new().await?;
+        let steam = Arc::new(FakeSteam::default());
+        let logs = Arc::new(FakeLogs::default());
+        let upload_dir = std::env::temp_dir().join(format!("{0}_uploads", db.name()));
+        std::fs::create_dir_all(upload_dir.join(super::teams::LOGO_DIR)).unwrap();
+        let demo_dir = std::env::temp_dir().join(format!("{0}_demos", db.name()));
+        std::fs::create_dir_all(&demo_dir).unwrap();
+
+        let mut state = AppState {
+            current_host: CurrentHost {
+                address: "127.0.0.1".to_owned(),
+                port: 8080,
+            },
+            pool: db.pool.clone(),
+            steam_auth_url: "https://steamcommunity.com/openid/login".to_owned(),
+            steam: steam.clone(),
+            logs: logs.clone(),
+            root_user_steamid: None,
+            auth_mode: AuthMode::Bearer,
+            session_cookie_domain: None,
+            eligibility: EligibilityConfig::default(),
+            name_filter: Arc::new(WordBlocklist::new(["blocked"])),
+            upload_dir,
+            demo_dir,
+            max_demo_bytes: 1024 * 1024,
+        };
+        change(&mut state);
+
+        Some(TestApp {
+            db,
+            steam,
+            logs,
+            state: web::Data::new(state),
+        })
+    }
+
+    /// The app with every route in the API, as `main` serves it.
+    pub async fn service(
+        &self,
+    ) -> impl Service<Request, Response = ServiceResponse, Error = actix_web::Error> {
+        test::init_service(
+            App::new()
+                .app_data(self.state.clone())
+                .configure(super::configure)
+                .service(actix_files::Files::new("/uploads", &self.state.upload_dir)),
+        )
+        .await
+    }
+
+    pub fn upload_dir(&self) -> &std::path::Path {
+        &self.state.upload_dir
+    }
+
+    pub fn demo_dir(&self) -> &std::path::Path {
+        &self.state.demo_dir
+    }
+
+    /// Make a user with no permissions, returning a session token for them.
+    pub async fn add_user(&self, steamid: &str, username: &str) -> String {
+        let client = self.db.pool.get().await.unwrap();
+        db::users::add_user(
+            &client,
+            crate::models::MiniUser {
+                steamid: steamid.to_owned(),
+                permissions: None,
+                avatarurl: String::new(),
+                username: username.to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        self.login(steamid).await
+    }
+
+    /// The user with `steamid`, who must already exist.
+    pub async fn user(&self, steamid: &str) -> User {
+        let client = self.db.pool.get().await.unwrap();
+        db::users::get_user_from_steamid(&client, steamid)
+            .await
+            .unwrap()
+    }
+
+    /// A session token for the user with `steamid`, who must already exist.
+    pub async fn login(&self, steamid: &str) -> String {
+        let client = self.db.pool.get().await.unwrap();
+        let user = db::users::get_user_from_steamid(&client, steamid)
+            .await
+            .unwrap();
+        db::users::get_authorization_for_user(&client, &user)
+            .await
+            .unwrap()
+            .token
+    }
+}
+
+/// Make a public league, with one division if `with_division`, returning its id.
+pub async fn seed_league(app: &TestApp, accepting_teams: bool, with_division: bool) -> i64 {
+    let client = app.db.pool.get().await.unwrap();
+    let league = db::leagues::add_league(
+        &client,
+        MiniLeague {
+            name: "Test League".to_owned(),
+            accepting_teams,
+            is_hidden: false,
+            seasonid: None,
+        },
+    )
+    .await
+    .unwrap();
+    if with_division {
+        db::leagues::add_division(
+            &client,
+            MiniDivision {
+                leagueid: league.id,
+                name: "Main".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+    }
+    league.id
+}
+
+/// Make a team for `token` and sign it up to `league_id`, returning the team.
+pub async fn signed_up_team(
+    service: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+    token: &str,
+    league_id: &Value,
+    name: &str,
+    tag: &str,
+) -> Value {
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(token))
+        .set_json(json!({"team_name": name, "team_tag": tag}))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(service, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(token))
+        .set_json(json!({"teamid": team["id"], "leagueid": league_id, "is_private": false}))
+        .to_request();
+    let resp = test::call_service(service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    team
+}
+
+/// Place every roster still waiting in `league_id` in the division it asked for.
+pub async fn place_rosters(
+    service: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+    admin_token: &str,
+    league_id: &Value,
+) {
+    let uri = format!("/api/v1/admin/leagues/{league_id}/placements");
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer(admin_token))
+        .to_request();
+    let pending: Vec<Value> = test::call_and_read_body_json(service, req).await;
+    let placements: Vec<Value> = pending
+        .iter()
+        .map(|roster| {
+            json!({
+                "teamdivid": roster["association_info"]["id"],
+                "divisionid": roster["association_info"]["requested_divisionid"],
+            })
+        })
+        .collect();
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(bearer(admin_token))
+        .set_json(json!({ "placements": placements }))
+        .to_request();
+    let resp = test::call_service(service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+/// A public league with one division, teams for `home_token` and `away_token` placed in it,
+/// and a game between them. Returns the league, the home team, the away team and the game.
+pub async fn league_with_game(
+    service: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+    admin_token: &str,
+    home_token: &str,
+    away_token: &str,
+) -> (Value, Value, Value, Value) {
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(admin_token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(service, req).await;
+    let home = signed_up_team(service, home_token, &league["id"], "Speakers", "SPK").await;
+    let away = signed_up_team(service, away_token, &league["id"], "Rivals", "RIV").await;
+    place_rosters(service, admin_token, &league["id"]).await;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(admin_token))
+        .set_json(json!({
+            "title": "Week 1",
+            "leagueid": league["id"],
+            "teamhomeid": home["id"],
+            "teamawayid": away["id"],
+            "played_at": "2099-06-03T20:00:00Z",
+        }))
+        .to_request();
+    let game: Value = test::call_and_read_body_json(service, req).await;
+    (league, home, away, game)
+}
+
+/// What most game tests start from: [LUCY] as the admin, and [league_with_game] with
+/// [SPEAKER] leading the home team and [RIVAL] the away team.
+pub struct GameFixture {
+    pub app: TestApp,
+    pub admin_token: String,
+    /// [SPEAKER]'s.
+    pub token: String,
+    /// [RIVAL]'s.
+    pub other_token: String,
+    pub admin: User,
+    pub speaker: User,
+    pub rival: User,
+    pub league: Value,
+    pub home: Value,
+    pub away: Value,
+    pub game: Value,
+}
+
+impl GameFixture {
+    pub async fn new() -> Option<GameFixture> {
+        let app = TestApp::new().await?;
+        let admin_token = app.login(LUCY).await;
+        let token = app.login(SPEAKER).await;
+        let other_token = app.add_user(RIVAL, "rival").await;
+        let (league, home, away, game) = {
+            let service = app.service().await;
+            league_with_game(&service, &admin_token, &token, &other_token).await
+        };
+        Some(GameFixture {
+            admin: app.user(LUCY).await,
+            speaker: app.user(SPEAKER).await,
+            rival: app.user(RIVAL).await,
+            app,
+            admin_token,
+            token,
+            other_token,
+            league,
+            home,
+            away,
+            game,
+        })
+    }
+}
+
+/// Hide league `league_id` from everyone who isn't allowed to see hidden leagues.
+pub async fn hide_league(
+    service: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+    admin_token: &str,
+    league_id: &Value,
+) {
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/admin/leagues/{league_id}"))
+        .insert_header(bearer(admin_token))
+        .set_json(json!({"is_hidden": true}))
+        .to_request();
+    let resp = test::call_service(service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+pub fn bearer(token: &str) -> (&'static str, String) {
+    ("Authorization", format!("Bearer {token}"))
+}
+
+/// What Steam sends back to `/verifylogin` after `steamid` logs in.
+pub fn openid_body(steamid: &str) -> serde_json::Value {
+    let identity = format!("https://steamcommunity.com/openid/id/{steamid}");
+    serde_json::json!({
+        "openid__ns": "http://specs.openid.net/auth/2.0",
+        "openid__mode": "id_res",
+        "openid__op_endpoint": "https://steamcommunity.com/openid/login",
+        "openid__claimed_id": identity,
+        "openid__identity": identity,
+        "openid__return_to": "http://127.0.0.1:8080/api/v1/login/landing",
+        "openid__response_nonce": "2024-07-27T16:07:06Zdg9+zW7ALLLycjtF7T7mWe3qKp0=",
+        "openid__assoc_handle": "1234567890",
+        "openid__signed": "signed,op_endpoint,claimed_id,identity,return_to,response_nonce,assoc_handle",
+        "openid__sig": "f9dFKCcwpaGUWp2VsXwMV7csgsU=",
+    })
+}
@@ -0,0 +1,235 @@
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, TestApp, LUCY, SPEAKER};
+
+async fn set_status(
+    service: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+    token: &str,
+    season_id: &Value,
+    status: &str,
+) -> StatusCode {
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/admin/seasons/{season_id}"))
+        .insert_header(bearer(token))
+        .set_json(json!({ "status": status }))
+        .to_request();
+    test::call_service(service, req).await.status()
+}
+
+#[actix_web::test]
+async fn season_lifecycle() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/seasons")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"name": "Season 1"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let season: Value = test::read_body_json(resp).await;
+    assert_eq!(season["status"], "draft");
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Highlander",
+            "accepting_teams": false,
+            "is_hidden": false,
+            "seasonid": season["id"],
+            "divisions": ["Premier", "Open"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(league["seasonid"], season["id"]);
+    let league_id = &league["id"];
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "Speakers", "team_tag": "SPK"}))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(&service, req).await;
+    let signup = json!({"teamid": team["id"], "leagueid": league_id, "is_private": false});
+
+    // Sign ups open and close with the season's registration.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(&signup)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let status = set_status(&service, &admin_token, &season["id"], "registration").await;
+    assert_eq!(status, StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(&signup)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let status = set_status(&service, &admin_token, &season["id"], "draft").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let status = set_status(&service, &admin_token, &season["id"], "active").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/seasons/{0}", season["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["status"], "active");
+    assert_eq!(body["leagues"][0]["info"]["accepting_teams"], false);
+    assert_eq!(body["leagues"][0]["divisions"].as_array().unwrap().len(), 2);
+
+    // A league retired on its own isn't carried over into the next season.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Retired",
+            "accepting_teams": false,
+            "is_hidden": false,
+            "seasonid": season["id"],
+            "divisions": ["Open"],
+        }))
+        .to_request();
+    let retired: Value = test::call_and_read_body_json(&service, req).await;
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/admin/leagues/{0}", retired["id"]))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"archived": true}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Archived seasons are read only, and so are their leagues.
+    let status = set_status(&service, &admin_token, &season["id"], "completed").await;
+    assert_eq!(status, StatusCode::OK);
+    let status = set_status(&service, &admin_token, &season["id"], "completed").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{league_id}"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert!(body["info"]["archived_at"].is_string());
+    for patch in [json!({"archived": false}), json!({"accepting_teams": true})] {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/v1/admin/leagues/{league_id}"))
+            .insert_header(bearer(&admin_token))
+            .set_json(patch)
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/divisions")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"leagueid": league_id, "name": "Intermediate"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let req = test::TestRequest::patch()
+        .uri(&format!(
+            "/api/v1/admin/divisions/{0}",
+            body["divisions"][0]["info"]["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"name": "Invite"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/seasons/{0}/clone", season["id"]))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"name": "Season 2"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let next: Value = test::read_body_json(resp).await;
+    assert_eq!(next["info"]["status"], "draft");
+    let leagues = next["leagues"].as_array().unwrap();
+    assert_eq!(leagues.len(), 1);
+    assert_ne!(&leagues[0]["info"]["id"], league_id);
+    assert_eq!(leagues[0]["info"]["name"], "Highlander");
+    assert_eq!(leagues[0]["info"]["seasonid"], next["info"]["id"]);
+    let divisions = leagues[0]["divisions"].as_array().unwrap();
+    assert_eq!(divisions.len(), 2);
+    assert_eq!(divisions[0]["info"]["name"], "Premier");
+
+    // The roster stayed behind with the old season.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", leagues[0]["info"]["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["divisions"][0]["teams"].as_array().unwrap().len(), 0);
+
+    let req = test::TestRequest::get().uri("/api/v1/seasons").to_request();
+    let seasons: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(seasons[0]["name"], "Season 2");
+    assert_eq!(seasons[1]["status"], "completed");
+}
+
+#[actix_web::test]
+async fn post_season_checks_fields() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/seasons")
+        .insert_header(bearer(&token))
+        .set_json(json!({"name": "Season 1"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/seasons")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": " ",
+            "starts_at": "2026-06-01T00:00:00Z",
+            "ends_at": "2026-05-01T00:00:00Z",
+        }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    let fields: Vec<&str> = body["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["name", "ends_at"]);
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Orphan",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "seasonid": 999999,
+        }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,316 @@
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, signed_up_team, TestApp, LUCY, RIVAL, SPEAKER};
+use crate::apiv1::stats::user_stats;
+use crate::db::{self, count_queries};
+use crate::models::{MiniLeague, MiniSeason, MiniTeam};
+use crate::steamid::SteamId;
+
+async fn game_with_log(
+    service: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+    admin_token: &str,
+    game: Value,
+    log: Value,
+) {
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/games")
+        .insert_header(bearer(admin_token))
+        .set_json(game)
+        .to_request();
+    let game: Value = test::call_and_read_body_json(service, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/games/{0}/log", game["id"]))
+        .insert_header(bearer(admin_token))
+        .set_json(log)
+        .to_request();
+    let resp = test::call_service(service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+fn log(speaker: Value, rival: Value) -> Value {
+    let steam3 = |steamid: &str| steamid.parse::<SteamId>().unwrap().steam3();
+    json!({
+        "info": {"map": "pass_arena2", "title": "Speakers vs Rivals"},
+        "players": {
+            steam3(SPEAKER): {"team": "Blue", "deaths": 2, "passtime": speaker},
+            steam3(RIVAL): {"team": "Red", "deaths": 3, "passtime": rival},
+        },
+    })
+}
+
+#[actix_web::test]
+async fn stats_add_up() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let other_token = app.add_user(RIVAL, "rival").await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+    let home = signed_up_team(&service, &token, &league["id"], "Speakers", "SPK").await;
+    let away = signed_up_team(&service, &other_token, &league["id"], "Rivals", "RIV").await;
+    let game = json!({
+        "leagueid": league["id"],
+        "teamhomeid": home["id"],
+        "teamawayid": away["id"],
+        "played_at": "2099-06-03T20:00:00Z",
+    });
+    game_with_log(
+        &service,
+        &admin_token,
+        game.clone(),
+        log(
+            json!({"goals": 3, "assists": 1}),
+            json!({"goals": 1, "intercepts": 4}),
+        ),
+    )
+    .await;
+    game_with_log(
+        &service,
+        &admin_token,
+        game,
+        log(
+            json!({"goals": 2, "intercepts": 1}),
+            json!({"intercepts": 2}),
+        ),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", league["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let leaderboard_uri = format!(
+        "/api/v1/divisions/{0}/leaderboard",
+        body["divisions"][0]["info"]["id"]
+    );
+
+    let req = test::TestRequest::get().uri(&leaderboard_uri).to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["total_count"], 2);
+    assert_eq!(body["stat"], "goals");
+    assert_eq!(body["players"][0]["user"]["steamid"], SPEAKER);
+    assert_eq!(body["players"][0]["totals"]["goals"], 5);
+    assert_eq!(body["players"][0]["totals"]["games_played"], 2);
+    let speaker_id = body["players"][0]["user"]["id"].clone();
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "{leaderboard_uri}?stat=intercepts&page=1&amount_per_page=1"
+        ))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["total_count"], 2);
+    let players = body["players"].as_array().unwrap();
+    assert_eq!(players.len(), 1);
+    assert_eq!(players[0]["user"]["steamid"], SPEAKER);
+    assert_eq!(players[0]["totals"]["intercepts"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{leaderboard_uri}?stat=deaths"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/{speaker_id}/stats"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["career"]["goals"], 5);
+    assert_eq!(body["career"]["deaths"], 4);
+    assert_eq!(body["seasons"].as_array().unwrap().len(), 1);
+    assert!(body["seasons"][0]["seasonid"].is_null());
+    assert_eq!(body["seasons"][0]["totals"]["assists"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}/stats", away["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["totals"]["games_played"], 2);
+    assert_eq!(body["totals"]["intercepts"], 6);
+    assert_eq!(body["players"][0]["goals"], 1);
+
+    // Hidden leagues don't count.
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/admin/leagues/{0}", league["id"]))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"is_hidden": true}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/{speaker_id}/stats"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["career"]["goals"], 0);
+    assert_eq!(body["seasons"].as_array().unwrap().len(), 0);
+    let req = test::TestRequest::get().uri(&leaderboard_uri).to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn user_stats_take_one_query() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let client = app.db.pool.get().await.unwrap();
+    let speaker = db::users::get_user_from_steamid(&client, SPEAKER)
+        .await
+        .unwrap();
+    let team = db::teams::add_team(
+        &client,
+        &MiniTeam {
+            owner_id: speaker.id,
+            team_name: "Speakers".to_owned(),
+            team_tag: "SPK".to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    let mut seasonids = vec![None];
+    for name in ["Spring", "Summer"] {
+        let season = db::seasons::add_season(
+            &client,
+            &MiniSeason {
+                name: name.to_owned(),
+                starts_at: None,
+                ends_at: None,
+                registration_opens_at: None,
+                registration_closes_at: None,
+            },
+        )
+        .await
+        .unwrap();
+        seasonids.push(Some(season.id));
+    }
+    for (i, seasonid) in seasonids.into_iter().enumerate() {
+        let league = db::leagues::add_league(
+            &client,
+            MiniLeague {
+                name: format!("League {i}"),
+                accepting_teams: true,
+                is_hidden: false,
+                seasonid,
+            },
+        )
+        .await
+        .unwrap();
+        client
+            .execute(
+                "INSERT INTO player_league_stats(userid, teamid, leagueid, games_played, goals) \
+                VALUES ($1, $2, $3, 1, $4)",
+                &[&speaker.id, &team.id, &league.id, &(i as i32 + 1)],
+            )
+            .await
+            .unwrap();
+    }
+
+    let (stats, queries) = count_queries(user_stats(&client, speaker.id)).await;
+    assert_eq!(queries, 1);
+    let stats = stats.unwrap();
+    assert_eq!(stats.career.games_played, 3);
+    assert_eq!(stats.career.goals, 1 + 2 + 3);
+    let goals: Vec<i64> = stats.seasons.iter().map(|s| s.totals.goals).collect();
+    assert_eq!(goals, [2, 3, 1]);
+    assert!(stats.seasons[2].seasonid.is_none());
+}
+
+#[actix_web::test]
+async fn leaderboards_count_players_in_their_roster_division() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let other_token = app.add_user(RIVAL, "rival").await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/admin/leagues")
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({
+            "name": "Summer Cup",
+            "accepting_teams": true,
+            "is_hidden": false,
+            "divisions": ["Premier", "Open"],
+        }))
+        .to_request();
+    let league: Value = test::call_and_read_body_json(&service, req).await;
+    let home = signed_up_team(&service, &token, &league["id"], "Speakers", "SPK").await;
+    let away = signed_up_team(&service, &other_token, &league["id"], "Rivals", "RIV").await;
+    game_with_log(
+        &service,
+        &admin_token,
+        json!({
+            "leagueid": league["id"],
+            "teamhomeid": home["id"],
+            "teamawayid": away["id"],
+            "played_at": "2099-06-03T20:00:00Z",
+        }),
+        log(json!({"goals": 3}), json!({"goals": 1})),
+    )
+    .await;
+
+    // The home team has a second roster, without players, in the other division.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{0}", league["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let divisions: Vec<Value> = body["divisions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|div| div["info"]["id"].clone())
+        .collect();
+    let client = app.db.pool.get().await.unwrap();
+    db::teams::add_team_div_assoc(
+        &client,
+        db::teams::MiniTeamDivAssociation {
+            roster_name: Some("Speakers B".to_owned()),
+            teamid: home["id"].as_i64().unwrap(),
+            divisionid: divisions[1].as_i64().unwrap(),
+            is_private: false,
+            requested_divisionid: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    let leaderboard = |divisionid: &Value, query: &str| {
+        test::TestRequest::get()
+            .uri(&format!(
+                "/api/v1/divisions/{divisionid}/leaderboard{query}"
+            ))
+            .to_request()
+    };
+    let body: Value = test::call_and_read_body_json(&service, leaderboard(&divisions[0], "")).await;
+    assert_eq!(body["total_count"], 2);
+    assert_eq!(body["players"][0]["totals"]["goals"], 3);
+    let body: Value = test::call_and_read_body_json(&service, leaderboard(&divisions[1], "")).await;
+    assert_eq!(body["total_count"], 0);
+    assert_eq!(body["players"], json!([]));
+
+    let body: Value = test::call_and_read_body_json(
+        &service,
+        leaderboard(&divisions[0], "?amount_per_page=100000"),
+    )
+    .await;
+    assert_eq!(body["amount_per_page"], 100);
+}
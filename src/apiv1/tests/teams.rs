@@ -0,0 +1,875 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use image::DynamicImage;
+use serde_json::{json, Value};
+
+use super::{
+    bearer, hide_league, place_rosters, seed_league, GameFixture, TestApp, AVG, LUCY, SPEAKER,
+};
+use crate::db;
+use crate::images::ImageFormat;
+
+#[actix_web::test]
+async fn post_and_get_team() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "Speakers", "team_tag": "SPK"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let team: Value = test::read_body_json(resp).await;
+    assert_eq!(team["team_name"], "Speakers");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", team["id"]))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["info"]["team_tag"], "SPK");
+    assert_eq!(body["owner"]["steamid"], SPEAKER);
+    assert_eq!(body["team_div_assocs"], json!([]));
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/teams/999999")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn post_team_needs_a_session() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .set_json(json!({"team_name": "Nobody", "team_tag": "NB"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Make a team owned by whoever `token` belongs to, returning its id.
+async fn post_team(
+    service: &impl actix_web::dev::Service<
+        actix_http::Request,
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+    >,
+    token: &str,
+) -> i64 {
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(token))
+        .set_json(json!({"team_name": "Speakers", "team_tag": "SPK"}))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(service, req).await;
+    team["id"].as_i64().unwrap()
+}
+
+#[actix_web::test]
+async fn register_team_to_league() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league(&app, true, true).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+
+    let signup = json!({
+        "roster_name": "Speakers A",
+        "teamid": team_id,
+        "leagueid": league_id,
+        "is_private": false,
+    });
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(&signup)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let assoc: Value = test::read_body_json(resp).await;
+    assert_eq!(assoc["teamid"], team_id);
+
+    // It can't be looked up until it's placed.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let admin_token = app.login(LUCY).await;
+    place_rosters(&service, &admin_token, &json!(league_id)).await;
+
+    // The owner is put on the roster as its leader.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["team_info"]["id"], team_id);
+    let players = body["current_players"].as_array().unwrap();
+    assert_eq!(players.len(), 1);
+    assert_eq!(players[0]["user"]["steamid"], SPEAKER);
+    assert_eq!(players[0]["assoc"]["affiliation"], 20);
+
+    // Signing up twice isn't allowed.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(&signup)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "conflict");
+
+    // Neither is signing up someone else's team.
+    let other_token = app.login(AVG).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&other_token))
+        .set_json(&signup)
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Or a team that doesn't exist.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"teamid": 999999, "leagueid": league_id, "is_private": false}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn register_team_to_closed_league() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let closed = seed_league(&app, false, true).await;
+    let no_divisions = seed_league(&app, true, false).await;
+    let token = app.login(SPEAKER).await;
+    let admin_token = app.login(LUCY).await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+
+    for league_id in [closed, no_divisions] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+            .insert_header(bearer(&token))
+            .set_json(json!({"teamid": team_id, "leagueid": league_id, "is_private": false}))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT, "{league_id}");
+    }
+
+    // Admins can still sign up their own teams to closed leagues.
+    let admin_team_id = post_team(&service, &admin_token).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{closed}/teams"))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"teamid": admin_team_id, "leagueid": closed, "is_private": false}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn get_missing_team_div_assoc() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/teamdivassocs/999999")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn post_team_checks_fields() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "   ", "team_tag": "TOOLONG"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(
+        body["details"],
+        json!([
+            {"field": "team_name", "message": "must not be empty"},
+            {"field": "team_tag", "message": "must be at most 6 characters"},
+        ])
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "The Blocked Ones", "team_tag": "B L"}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(
+        body["details"],
+        json!([
+            {"field": "team_name", "message": "contains a blocked word"},
+            {"field": "team_tag", "message": "must not contain spaces"},
+        ])
+    );
+
+    // Names are stored trimmed.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "  Speakers\n", "team_tag": " SPK "}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let team: Value = test::read_body_json(resp).await;
+    assert_eq!(team["team_name"], "Speakers");
+    assert_eq!(team["team_tag"], "SPK");
+}
+
+#[actix_web::test]
+async fn tags_are_unique_within_a_league() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league(&app, true, true).await;
+    let token = app.login(SPEAKER).await;
+    let other_token = app.login(AVG).await;
+    let service = app.service().await;
+
+    let mut sign_ups = Vec::new();
+    for (token, tag) in [(&token, "SPK"), (&other_token, "spk")] {
+        let req = test::TestRequest::post()
+            .uri("/api/v1/teams")
+            .insert_header(bearer(token))
+            .set_json(json!({"team_name": "Speakers", "team_tag": tag}))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&service, req).await;
+        sign_ups.push(
+            test::TestRequest::post()
+                .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+                .insert_header(bearer(token))
+                .set_json(json!({"teamid": team["id"], "leagueid": league_id, "is_private": false}))
+                .to_request(),
+        );
+    }
+
+    // Even signing up at the same time, only one of them gets the tag.
+    let second = sign_ups.pop().unwrap();
+    let first = sign_ups.pop().unwrap();
+    let (first, second) = futures::join!(
+        test::call_service(&service, first),
+        test::call_service(&service, second),
+    );
+    let (taken, resp) = match first.status() {
+        StatusCode::CREATED => (first, second),
+        _ => (second, first),
+    };
+    assert_eq!(taken.status(), StatusCode::CREATED);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "teamid");
+}
+
+#[actix_web::test]
+async fn patch_team() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(SPEAKER).await;
+    let other_token = app.add_user("76561198000000002", "bystander").await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": " Loudspeakers "}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let team: Value = test::read_body_json(resp).await;
+    assert_eq!(team["team_name"], "Loudspeakers");
+    assert_eq!(team["team_tag"], "SPK");
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_tag": ""}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Only the owner, or someone who manages teams, can edit it.
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"team_tag": "AVG"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let admin_token = app.login(LUCY).await;
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&admin_token))
+        .set_json(json!({"team_tag": "LOUD"}))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(team["team_tag"], "LOUD");
+}
+
+#[actix_web::test]
+async fn patch_team_keeps_tags_unique() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league(&app, true, true).await;
+    let token = app.login(SPEAKER).await;
+    let other_token = app.login(AVG).await;
+    let service = app.service().await;
+
+    let mut team_ids = Vec::new();
+    for (token, tag) in [(&token, "SPK"), (&other_token, "AVG")] {
+        let req = test::TestRequest::post()
+            .uri("/api/v1/teams")
+            .insert_header(bearer(token))
+            .set_json(json!({"team_name": "Team", "team_tag": tag}))
+            .to_request();
+        let team: Value = test::call_and_read_body_json(&service, req).await;
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+            .insert_header(bearer(token))
+            .set_json(json!({"teamid": team["id"], "leagueid": league_id, "is_private": false}))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        team_ids.push(team["id"].as_i64().unwrap());
+    }
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{0}", team_ids[1]))
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"team_tag": "spk"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "team_tag");
+}
+
+#[actix_web::test]
+async fn transfer_team() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league(&app, true, true).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"teamid": team_id, "leagueid": league_id, "is_private": false}))
+        .to_request();
+    let assoc: Value = test::call_and_read_body_json(&service, req).await;
+    let admin_token = app.login(LUCY).await;
+    place_rosters(&service, &admin_token, &json!(league_id)).await;
+
+    let client = app.db.pool.get().await.unwrap();
+    let avg = db::users::get_user_from_steamid(&client, AVG)
+        .await
+        .unwrap();
+    db::teams::add_user_team_id(
+        &client,
+        avg.id,
+        assoc["id"].as_i64().unwrap(),
+        db::teams::UserTeamAffiliation::Member,
+    )
+    .await
+    .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/teams/{team_id}/transfer"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"new_owner_id": 999999}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Nobody can take over a roster they aren't on, or it would have no leader.
+    let lucy = db::users::get_user_from_steamid(&client, LUCY)
+        .await
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/teams/{team_id}/transfer"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"new_owner_id": lucy.id}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/teams/{team_id}/transfer"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"new_owner_id": avg.id}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let team: Value = test::read_body_json(resp).await;
+    assert_eq!(team["owner_id"], avg.id);
+
+    // The roster's leadership follows the team.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    for player in body["current_players"].as_array().unwrap() {
+        let expected = match player["user"]["steamid"].as_str().unwrap() {
+            AVG => 20,
+            _ => 10,
+        };
+        assert_eq!(player["assoc"]["affiliation"], expected);
+    }
+
+    // The old owner can't hand it back.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/teams/{team_id}/transfer"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"new_owner_id": avg.id}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    ImageFormat::Png
+        .encode(&DynamicImage::new_rgba8(width, height))
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn team_logo() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+    let uri = format!("/api/v1/teams/{team_id}/logo");
+
+    let mut header_only = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    header_only.extend([0, 0, 1, 0, 0, 0, 1, 0, 8, 6, 0, 0, 0]);
+    for (content_type, body, message) in [
+        (
+            "image/png",
+            b"GIF89a".to_vec(),
+            "must be a PNG, JPEG or WebP image",
+        ),
+        (
+            "image/jpeg",
+            png(64, 64),
+            "is a PNG image, but was sent as \"image/jpeg\"",
+        ),
+        ("image/png", header_only, "is not a valid image"),
+        ("image/png", png(16, 64), "must be at least 32x32 pixels"),
+        // Too thin to still be 32 pixels tall once it's scaled down.
+        ("image/png", png(4096, 64), "must be at least 32x32 pixels"),
+        (
+            "image/png",
+            vec![0; 5 * 1024 * 1024],
+            "must be at most 4096 KiB",
+        ),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .insert_header(bearer(&token))
+            .insert_header(("Content-Type", content_type))
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["message"], message);
+    }
+
+    // Logos that fit are stored as they were sent.
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(bearer(&token))
+        .insert_header(("Content-Type", "image/png"))
+        .set_payload(png(256, 256))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let team: Value = test::read_body_json(resp).await;
+    let logo_url = team["logo_url"].as_str().unwrap().to_owned();
+    assert!(logo_url.starts_with(&format!("/uploads/team_logos/{team_id}-")));
+
+    let req = test::TestRequest::get().uri(&logo_url).to_request();
+    let served = test::call_and_read_body(&service, req).await;
+    assert_eq!(served, png(256, 256));
+
+    // Bigger ones are scaled down to fit, and replace the old file.
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(bearer(&token))
+        .insert_header(("Content-Type", "image/png; charset=binary"))
+        .set_payload(png(2048, 512))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let team: Value = test::read_body_json(resp).await;
+    let req = test::TestRequest::get()
+        .uri(team["logo_url"].as_str().unwrap())
+        .to_request();
+    let served = test::call_and_read_body(&service, req).await;
+    assert_eq!(ImageFormat::Png.dimensions(&served), Some((1024, 256)));
+    let logos = std::fs::read_dir(app.upload_dir().join("team_logos")).unwrap();
+    assert_eq!(logos.count(), 1);
+
+    // Removing the logo cleans up its file.
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(bearer(&token))
+        .to_request();
+    let team: Value = test::call_and_read_body_json(&service, req).await;
+    assert!(team["logo_url"].is_null());
+    let logos = std::fs::read_dir(app.upload_dir().join("team_logos")).unwrap();
+    assert_eq!(logos.count(), 0);
+}
+
+#[actix_web::test]
+async fn rosters_keep_the_name_they_signed_up_with() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league(&app, true, true).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"teamid": team_id, "leagueid": league_id, "is_private": false}))
+        .to_request();
+    let assoc: Value = test::call_and_read_body_json(&service, req).await;
+    let admin_token = app.login(LUCY).await;
+    place_rosters(&service, &admin_token, &json!(league_id)).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "Loudspeakers", "team_tag": "LOUD"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["team_name"], "Loudspeakers");
+    let history = body["name_history"].as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["team_tag"], "LOUD");
+    assert_eq!(history[1]["team_tag"], "SPK");
+    assert_eq!(body["team_div_assocs"][0]["id"], assoc["id"]);
+    assert_eq!(body["team_div_assocs"][0]["team_name"], "Speakers");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["team_name_at_signup"]["team_tag"], "SPK");
+}
+
+#[actix_web::test]
+async fn disband_team() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let league_id = seed_league(&app, true, true).await;
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+    let team_id = post_team(&service, &token).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"teamid": team_id, "leagueid": league_id, "is_private": false}))
+        .to_request();
+    let assoc: Value = test::call_and_read_body_json(&service, req).await;
+    let admin_token = app.login(LUCY).await;
+    place_rosters(&service, &admin_token, &json!(league_id)).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let team: Value = test::read_body_json(resp).await;
+    assert!(team["disbanded_at"].is_string());
+
+    // Every player has left the roster.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{0}", assoc["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["current_players"].as_array().unwrap().len(), 0);
+    assert_eq!(body["past_players"].as_array().unwrap().len(), 1);
+
+    // It's gone from listings, but can still be looked up.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me?deep=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["ownerships"].as_array().unwrap().len(), 0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{league_id}"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["divisions"][0]["teams"].as_array().unwrap().len(), 0);
+
+    // Its tag is free for another team to sign up with.
+    let other_token = app.login(AVG).await;
+    let req = test::TestRequest::post()
+        .uri("/api/v1/teams")
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"team_name": "Other Speakers", "team_tag": "SPK"}))
+        .to_request();
+    let other: Value = test::call_and_read_body_json(&service, req).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/leagues/{league_id}/teams"))
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"teamid": other["id"], "leagueid": league_id, "is_private": false}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let other_assoc: Value = test::read_body_json(resp).await;
+
+    // Once the league is over, its history shows the team again.
+    let client = app.db.pool.get().await.unwrap();
+    let lucy = db::users::get_user_from_steamid(&client, LUCY)
+        .await
+        .unwrap();
+    for assoc in [&assoc, &other_assoc] {
+        db::teams::place_roster(
+            &client,
+            assoc["id"].as_i64().unwrap(),
+            assoc["divisionid"].as_i64().unwrap(),
+            None,
+            lucy.id,
+        )
+        .await
+        .unwrap();
+    }
+    let mut league = db::leagues::get_league_from_id(&client, league_id)
+        .await
+        .unwrap();
+    league.archived_at = Some(chrono::offset::Utc::now());
+    db::leagues::update_league(&client, &league).await.unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/leagues/{league_id}"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    let teams = body["divisions"][0]["teams"].as_array().unwrap();
+    assert_eq!(teams.len(), 2);
+    assert!(teams
+        .iter()
+        .any(|team| team["association_info"]["id"] == assoc["id"]));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&token))
+        .set_json(json!({"team_name": "Back again"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/v1/teams/{team_id}"))
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn get_team_leaves_out_hidden_and_private_rosters() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        league,
+        home,
+        away,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE teamDivAssociations SET is_private=true WHERE teamid=$1",
+            &[&away["id"].as_i64().unwrap()],
+        )
+        .await
+        .unwrap();
+    let count_rosters = |body: &Value| body["team_div_assocs"].as_array().unwrap().len();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", away["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 0);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/teams/{0}?include_hidden=true",
+            away["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 1);
+
+    hide_league(&service, &admin_token, &league["id"]).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", home["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 0);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/teams/{0}?include_hidden=true",
+            home["id"]
+        ))
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 0);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/teams/{0}?include_hidden=true",
+            home["id"]
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 1);
+}
+
+#[actix_web::test]
+async fn get_team_div_assoc_hides_hidden_and_private_rosters() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        league,
+        home,
+        away,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+    let roster_id = |teamid: &Value| {
+        let client = &client;
+        let teamid = teamid.as_i64().unwrap();
+        async move {
+            let row = client
+                .query_one(
+                    "SELECT id FROM teamDivAssociations WHERE teamid=$1",
+                    &[&teamid],
+                )
+                .await
+                .unwrap();
+            row.get::<_, i64>(0)
+        }
+    };
+    let home_roster = roster_id(&home["id"]).await;
+    let away_roster = roster_id(&away["id"]).await;
+    client
+        .execute(
+            "UPDATE teamDivAssociations SET is_private=true WHERE id=$1",
+            &[&away_roster],
+        )
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{home_roster}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{away_roster}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/teamdivassocs/{away_roster}?include_hidden=true"
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    hide_league(&service, &admin_token, &league["id"]).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teamdivassocs/{home_roster}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/teamdivassocs/{home_roster}?include_hidden=true"
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,539 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, hide_league, GameFixture, TestApp, LUCY, SPEAKER};
+use crate::db;
+
+/// Make someone an admin of `league`'s division, returning a session token for them.
+async fn division_admin(app: &TestApp, league: &Value) -> String {
+    let token = app.add_user("76561198000000007", "division admin").await;
+    let div_admin = app.user("76561198000000007").await;
+    let client = app.db.pool.get().await.unwrap();
+    let divs = db::leagues::get_divs_for_league_id(&client, league["id"].as_i64().unwrap())
+        .await
+        .unwrap();
+    client
+        .execute(
+            "INSERT INTO division_admins(divisionid, userid) VALUES ($1, $2)",
+            &[&divs[0].id, &div_admin.id],
+        )
+        .await
+        .unwrap();
+    token
+}
+
+/// Put "ringer" in the home team's lineup for `game`, returning their id.
+async fn ringer_in_lineup(app: &TestApp, home: &Value, game: &Value) -> i64 {
+    app.add_user("76561198000000005", "ringer").await;
+    let ringer = app.user("76561198000000005").await;
+    let client = app.db.pool.get().await.unwrap();
+    db::lineups::set_lineup(
+        &client,
+        game["id"].as_i64().unwrap(),
+        home["id"].as_i64().unwrap(),
+        &[ringer.id],
+    )
+    .await
+    .unwrap();
+    ringer.id
+}
+
+/// The away team reporting the home team's ringer.
+fn ringer_ticket(league: &Value, away: &Value, game: &Value, ringer_id: i64) -> Value {
+    json!({
+        "leagueid": league["id"],
+        "teamid": away["id"],
+        "gameid": game["id"],
+        "reported_userid": ringer_id,
+        "category": "ringer",
+        "subject": "They played a ringer",
+        "message": "Their scout isn't on their roster.\nSee the log.",
+        "evidence": ["https://logs.tf/3500000"],
+    })
+}
+
+fn open(token: &str, ticket: &Value) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri("/api/v1/tickets")
+        .insert_header(bearer(token))
+        .set_json(ticket)
+        .to_request()
+}
+
+fn reply(token: &str, ticket_id: &Value) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/tickets/{ticket_id}/messages"))
+        .insert_header(bearer(token))
+        .set_json(json!({"message": "He's our sub, we asked for him."}))
+        .to_request()
+}
+
+fn set_status(token: &str, ticket_id: &Value, status: &str) -> actix_http::Request {
+    test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/tickets/{ticket_id}/status"))
+        .insert_header(bearer(token))
+        .set_json(json!({ "status": status }))
+        .to_request()
+}
+
+fn list_tickets(token: &str, query: &str) -> actix_http::Request {
+    test::TestRequest::get()
+        .uri(&format!("/api/v1/tickets{query}"))
+        .insert_header(bearer(token))
+        .to_request()
+}
+
+#[actix_web::test]
+async fn only_the_people_involved_follow_tickets() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        league,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let bystander_token = app.add_user("76561198000000006", "bystander").await;
+    let service = app.service().await;
+    let div_admin_token = division_admin(&app, &league).await;
+    let ringer_id = ringer_in_lineup(&app, &home, &game).await;
+    let ringer_token = app.login("76561198000000005").await;
+    let ticket = ringer_ticket(&league, &away, &game, ringer_id);
+
+    // Only the away team's leaders and officers open tickets for it.
+    let resp = test::call_service(&service, open(&token, &ticket)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::call_and_read_body_json(&service, open(&other_token, &ticket)).await;
+    let ticket_id = body["ticket"]["id"].clone();
+
+    // Both teams, the reported player and the league's admins can follow it. Nobody else can.
+    for token in [&token, &ringer_token, &div_admin_token, &admin_token] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/tickets/{ticket_id}"))
+            .insert_header(bearer(token))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/tickets/{ticket_id}"))
+        .insert_header(bearer(&bystander_token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value =
+        test::call_and_read_body_json(&service, list_tickets(&bystander_token, "")).await;
+    assert_eq!(body.as_array().unwrap().len(), 0);
+    let resp = test::call_service(&service, reply(&bystander_token, &ticket_id)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Only the league's admins move it along.
+    let resp = test::call_service(&service, set_status(&token, &ticket_id, "in_review")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/tickets/{ticket_id}/resolve"))
+        .insert_header(bearer(&other_token))
+        .set_json(json!({"outcome": "no_action"}))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn tickets_are_validated() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        league,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let ringer_id = ringer_in_lineup(&app, &home, &game).await;
+    let ticket = ringer_ticket(&league, &away, &game, ringer_id);
+
+    let mut about_nothing = ticket.clone();
+    about_nothing["gameid"] = Value::Null;
+    about_nothing["reported_userid"] = Value::Null;
+    let resp = test::call_service(&service, open(&other_token, &about_nothing)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let mut bad_link = ticket.clone();
+    bad_link["evidence"] = json!(["ftp://example.com/demo.dem"]);
+    let resp = test::call_service(&service, open(&other_token, &bad_link)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "evidence[0]");
+
+    let body: Value = test::call_and_read_body_json(&service, open(&other_token, &ticket)).await;
+    let ticket_id = body["ticket"]["id"].clone();
+    // Tickets are resolved with an outcome, not by setting their status.
+    let resp = test::call_service(&service, set_status(&admin_token, &ticket_id, "resolved")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resolve = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/admin/tickets/{ticket_id}/resolve"))
+            .insert_header(bearer(&admin_token))
+            .set_json(json!({"outcome": "no_action"}))
+            .to_request()
+    };
+    let resp = test::call_service(&service, resolve()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&service, resolve()).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = test::call_service(&service, reply(&token, &ticket_id)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn tickets_in_hidden_leagues_stay_with_the_teams() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        other_token,
+        league,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let ringer_id = ringer_in_lineup(&app, &home, &game).await;
+    hide_league(&service, &admin_token, &league["id"]).await;
+
+    let ticket = ringer_ticket(&league, &away, &game, ringer_id);
+    let resp = test::call_service(&service, open(&other_token, &ticket)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::call_and_read_body_json(&service, list_tickets(&token, "")).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn dispute_tickets() {
+    let Some(GameFixture {
+        app,
+        token,
+        other_token,
+        league,
+        home,
+        away,
+        game,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let div_admin_token = division_admin(&app, &league).await;
+    let ringer_id = ringer_in_lineup(&app, &home, &game).await;
+    let div_admin = app.user("76561198000000007").await;
+
+    let ticket = ringer_ticket(&league, &away, &game, ringer_id);
+    let resp = test::call_service(&service, open(&other_token, &ticket)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["ticket"]["status"], "open");
+    assert_eq!(body["ticket"]["category"], "ringer");
+    assert_eq!(
+        body["messages"][0]["evidence"][0],
+        "https://logs.tf/3500000"
+    );
+    let ticket_id = body["ticket"]["id"].clone();
+    for token in [&token, &div_admin_token] {
+        let body: Value = test::call_and_read_body_json(&service, list_tickets(token, "")).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+    }
+
+    let resp = test::call_service(&service, reply(&token, &ticket_id)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = test::call_and_read_body_json(
+        &service,
+        set_status(&div_admin_token, &ticket_id, "in_review"),
+    )
+    .await;
+    assert_eq!(body["status"], "in_review");
+
+    let resolve = test::TestRequest::post()
+        .uri(&format!("/api/v1/admin/tickets/{ticket_id}/resolve"))
+        .insert_header(bearer(&div_admin_token))
+        .set_json(json!({
+            "outcome": "forfeit",
+            "note": "Unapproved mercenary.",
+            "forfeiting_teamid": home["id"],
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, resolve).await;
+    assert_eq!(body["status"], "resolved");
+    assert_eq!(body["outcome"], "forfeit");
+    assert_eq!(body["resolved_by"], div_admin.id);
+    let body: Value =
+        test::call_and_read_body_json(&service, list_tickets(&token, "?status=open")).await;
+    assert_eq!(body.as_array().unwrap().len(), 0);
+    let body: Value =
+        test::call_and_read_body_json(&service, list_tickets(&token, "?status=resolved")).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    // The forfeit stands, so the ticket can't be reopened or replied to.
+    let resp = test::call_service(&service, set_status(&div_admin_token, &ticket_id, "open")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = test::call_service(&service, reply(&token, &ticket_id)).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/tickets/{ticket_id}"))
+        .insert_header(bearer(&other_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["ticket"]["status"], "resolved");
+    assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn reported_players_must_be_involved() {
+    let Some(GameFixture {
+        app,
+        token,
+        other_token,
+        league,
+        home,
+        game,
+        speaker,
+        rival,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    app.add_user("76561198000000005", "stranger").await;
+    let stranger = app.user("76561198000000005").await;
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+    let report = |userid: i64| {
+        test::TestRequest::post()
+            .uri("/api/v1/tickets")
+            .insert_header(bearer(&token))
+            .set_json(json!({
+                "leagueid": league["id"],
+                "teamid": home["id"],
+                "reported_userid": userid,
+                "category": "cheating",
+                "subject": "Aimbot",
+                "message": "See the demo.",
+            }))
+            .to_request()
+    };
+
+    for userid in [speaker.id, stranger.id] {
+        let resp = test::call_service(&service, report(userid)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["details"][0]["field"], "reported_userid");
+    }
+    let resp = test::call_service(&service, report(rival.id)).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // Someone who only played in the game as a mercenary can be reported about it.
+    client
+        .execute(
+            "INSERT INTO game_lineups(gameid, teamid, userid, created_at) VALUES ($1, $2, $3, now())",
+            &[
+                &game["id"].as_i64().unwrap(),
+                &home["id"].as_i64().unwrap(),
+                &stranger.id,
+            ],
+        )
+        .await
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/v1/tickets")
+        .insert_header(bearer(&other_token))
+        .set_json(json!({
+            "leagueid": league["id"],
+            "teamid": game["teamawayid"],
+            "gameid": game["id"],
+            "reported_userid": stranger.id,
+            "category": "ringer",
+            "subject": "Ringer",
+            "message": "Not on their roster.",
+        }))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn resolutions_are_carried_out() {
+    let Some(GameFixture {
+        app,
+        admin_token,
+        token,
+        league,
+        home,
+        away,
+        game,
+        rival,
+        ..
+    }) = GameFixture::new().await
+    else {
+        return;
+    };
+    let service = app.service().await;
+    let client = app.db.pool.get().await.unwrap();
+
+    let open = |body: Value| {
+        let service = &service;
+        let token = &token;
+        async move {
+            let req = test::TestRequest::post()
+                .uri("/api/v1/tickets")
+                .insert_header(bearer(token))
+                .set_json(body)
+                .to_request();
+            let body: Value = test::call_and_read_body_json(service, req).await;
+            body["ticket"]["id"].clone()
+        }
+    };
+    let resolve = |ticket_id: &Value, body: Value| {
+        test::TestRequest::post()
+            .uri(&format!("/api/v1/admin/tickets/{ticket_id}/resolve"))
+            .insert_header(bearer(&admin_token))
+            .set_json(body)
+            .to_request()
+    };
+    let about_game = open(json!({
+        "leagueid": league["id"],
+        "teamid": home["id"],
+        "gameid": game["id"],
+        "category": "no_show",
+        "subject": "No show",
+        "message": "They never joined.",
+    }))
+    .await;
+    let about_player = open(json!({
+        "leagueid": league["id"],
+        "teamid": home["id"],
+        "reported_userid": rival.id,
+        "category": "cheating",
+        "subject": "Aimbot",
+        "message": "See the demo.",
+    }))
+    .await;
+
+    let resp = test::call_service(
+        &service,
+        resolve(
+            &about_game,
+            json!({"outcome": "result_override", "home_score": 2}),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["details"][0]["field"], "away_score");
+    let resp = test::call_service(
+        &service,
+        resolve(
+            &about_player,
+            json!({"outcome": "forfeit", "forfeiting_teamid": away["id"]}),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&service, resolve(&about_game, json!({"outcome": "ban"}))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // A forfeit confirms the game as a loss for the team that forfeited.
+    let resp = test::call_service(
+        &service,
+        resolve(
+            &about_game,
+            json!({"outcome": "forfeit", "forfeiting_teamid": away["id"]}),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/games/{0}", game["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["home_score"], 1);
+    assert_eq!(body["away_score"], 0);
+    assert!(body["confirmed_at"].is_string());
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", home["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["rating"], 1516.0);
+
+    // Overriding the confirmed result takes back the forfeit's ratings before rating it again.
+    let rating_changes = || async {
+        let row = client
+            .query_one(
+                "SELECT COUNT(*) FROM rating_history WHERE gameid=$1",
+                &[&game["id"].as_i64().unwrap()],
+            )
+            .await
+            .unwrap();
+        row.get::<_, i64>(0)
+    };
+    let forfeit_changes = rating_changes().await;
+    let about_score = open(json!({
+        "leagueid": league["id"],
+        "teamid": home["id"],
+        "gameid": game["id"],
+        "category": "other",
+        "subject": "Wrong score",
+        "message": "They did turn up in the end, and won 3-0.",
+    }))
+    .await;
+    let resp = test::call_service(
+        &service,
+        resolve(
+            &about_score,
+            json!({"outcome": "result_override", "home_score": 0, "away_score": 3}),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/teams/{0}", home["id"]))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(body["info"]["rating"], 1484.0);
+    assert_eq!(rating_changes().await, forfeit_changes);
+
+    // Resolving a ticket twice at once carries it out once.
+    let (first, second) = futures::join!(
+        test::call_service(&service, resolve(&about_player, json!({"outcome": "ban"}))),
+        test::call_service(&service, resolve(&about_player, json!({"outcome": "ban"}))),
+    );
+    let mut statuses = [first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+    let banned: i64 = client
+        .query_one(
+            "SELECT COUNT(*) FROM user_bans WHERE userid=$1",
+            &[&rival.id],
+        )
+        .await
+        .unwrap()
+        .get(0);
+    assert_eq!(banned, 1);
+}
@@ -0,0 +1,234 @@
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+
+use super::{bearer, hide_league, league_with_game, TestApp, LUCY, SPEAKER};
+
+#[actix_web::test]
+async fn get_user_by_any_steamid_format() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    for steamid in [
+        LUCY,
+        "STEAM_0:0:32387359",
+        "[U:1:64774718]",
+        "https://steamcommunity.com/profiles/76561198025040446",
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v1/user/steamid/{steamid}"))
+            .to_request();
+        let resp = test::call_service(&service, req).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{steamid}");
+
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["info"]["username"], "lucy", "{steamid}");
+        assert!(body["rosters"].is_null());
+    }
+}
+
+#[actix_web::test]
+async fn get_user_by_vanity_url() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    app.steam.add_vanity("lucy", LUCY.parse().unwrap());
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/steamid/https://steamcommunity.com/id/Lucy/")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["info"]["steamid"], LUCY);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/steamid/steamcommunity.com/id/nobody")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn get_user_by_steamid_rejects_bad_and_unknown_ids() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/steamid/not-a-steamid")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/steamid/76561197960265729")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn get_current_user() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(SPEAKER).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me?deep=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["info"]["username"], "speaker");
+    assert_eq!(body["ownerships"], serde_json::json!([]));
+    assert_eq!(body["rosters"], serde_json::json!([]));
+
+    let req = test::TestRequest::get().uri("/api/v1/user/me").to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn get_user_from_auth_token() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let token = app.login(LUCY).await;
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/authtoken/{token}"))
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["info"]["steamid"], LUCY);
+}
+
+#[actix_web::test]
+async fn get_users_paged() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/users?page=1&amount_per_page=2")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["total_count"], 3);
+    assert_eq!(body["page"], 1);
+    assert_eq!(body["users"].as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn search_users() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/users/search?q=speak")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    let users = body.as_array().unwrap();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0]["steamid"], SPEAKER);
+}
+
+#[actix_web::test]
+async fn errors_are_json() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let service = app.service().await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/steamid/76561198000000009")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        json!({"code": "not_found", "message": "Not found", "details": null})
+    );
+
+    // Extractor errors too, not just ones from handlers.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/teams/not-a-number")
+        .to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "validation_failed");
+
+    let req = test::TestRequest::get().uri("/api/v1/user/me").to_request();
+    let resp = test::call_service(&service, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["code"], "unauthorized");
+}
+
+#[actix_web::test]
+async fn get_user_leaves_out_hidden_and_private_rosters() {
+    let Some(app) = TestApp::new().await else {
+        return;
+    };
+    let admin_token = app.login(LUCY).await;
+    let token = app.login(SPEAKER).await;
+    let rival = "76561198000000004";
+    let other_token = app.add_user(rival, "rival").await;
+    let service = app.service().await;
+    let (league, home, _, _) = league_with_game(&service, &admin_token, &token, &other_token).await;
+    let client = app.db.pool.get().await.unwrap();
+    client
+        .execute(
+            "UPDATE teamDivAssociations SET is_private=true WHERE teamid=$1",
+            &[&home["id"].as_i64().unwrap()],
+        )
+        .await
+        .unwrap();
+    let count_rosters = |body: &Value| body["rosters"].as_array().unwrap().len();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/steamid/{SPEAKER}?deep=true"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 0);
+    // Everyone still sees their own.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/user/me?deep=true")
+        .insert_header(bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 1);
+
+    hide_league(&service, &admin_token, &league["id"]).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/v1/user/steamid/{rival}?deep=true"))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 0);
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/v1/user/steamid/{rival}?deep=true&include_hidden=true"
+        ))
+        .insert_header(bearer(&admin_token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&service, req).await;
+    assert_eq!(count_rosters(&body), 1);
+}
@@ -0,0 +1,294 @@
+//! Disputes and support tickets. Team leaders and officers open them about a game or a player,
+//! and the league's admins answer, review and resolve them, see [super::admin::tickets].
+//!
+//! A ticket and its thread are only visible to the teams involved, the reported player and the
+//! league's admins.
+use actix_web::{get, post, web, HttpResponse};
+use deadpool_postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+
+use super::admin::placements::require_league_admin;
+use super::HttpResult;
+use crate::apiv1::grab_pool;
+use crate::apiv1::session::SessionToken;
+use crate::apiv1::validation::{Checker, Valid, Validate};
+use crate::db;
+use crate::db::teams::UserTeamAffiliation;
+use crate::errors::MyError;
+use crate::models::{MiniTicket, Ticket, TicketCategory, TicketMessage, TicketStatus, User};
+use crate::permission::UserPermission;
+use crate::AppState;
+
+/// How many evidence links one message can have.
+const MAX_EVIDENCE: usize = 10;
+
+/// Each link in `evidence` has to be a single http(s) URL.
+fn check_evidence(check: &mut Checker, evidence: &mut [String]) {
+    if evidence.len() > MAX_EVIDENCE {
+        check.error(
+            "evidence",
+            format!("must have at most {MAX_EVIDENCE} links"),
+        );
+    }
+    for (i, link) in evidence.iter_mut().enumerate() {
+        let field = format!("evidence[{i}]");
+        check.text(&field, link).length(1, 500).single_word();
+        if !link.is_empty() && !link.starts_with("https://") && !link.starts_with("http://") {
+            check.error(&field, "must be an http or https link");
+        }
+    }
+}
+
+/// Only the teams involved, the reported player and the league's admins can see `ticket`.
+async fn require_ticket_access(
+    client: &impl GenericClient,
+    user: &User,
+    ticket: &Ticket,
+) -> Result<(), MyError> {
+    if ticket.reported_userid == Some(user.id) {
+        return Ok(());
+    }
+    let mut teamids = vec![ticket.teamid];
+    if let Some(gameid) = ticket.gameid {
+        let game = db::games::get_game_from_id(client, gameid).await?;
+        teamids.extend([game.teamhomeid, game.teamawayid]);
+    }
+    for teamid in teamids {
+        let level =
+            db::teams::get_affiliation_in_league(client, user.id, teamid, ticket.leagueid).await?;
+        if level.is_some() {
+            return Ok(());
+        }
+    }
+    match require_league_admin(client, user, ticket.leagueid).await {
+        Err(MyError::Forbidden(_)) => Err(MyError::Forbidden(
+            "Only the teams and players involved, and league admins, can see this ticket",
+        )),
+        other => other,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TicketThread {
+    pub ticket: Ticket,
+    /// Oldest first, starting with the one the ticket was opened with.
+    pub messages: Vec<TicketMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TicketQuery {
+    pub status: Option<TicketStatus>,
+}
+
+/// Every ticket you can see, most recently active first.
+#[get("/api/v1/tickets")]
+pub async fn get_tickets(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    query: web::Query<TicketQuery>,
+) -> HttpResult {
+    log::info!("GET /api/v1/tickets");
+    let client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let status = query.status.map(TicketStatus::as_str);
+    let tickets = match user.admin_or_perm(UserPermission::CreateLeague) {
+        true => db::tickets::get_tickets(&client, status).await?,
+        false => {
+            let leagueids = db::leagues::get_admin_league_ids_for_user_id(&client, user.id).await?;
+            db::tickets::get_tickets_for_user_id(&client, user.id, &leagueids, status).await?
+        }
+    };
+    Ok(HttpResponse::Ok().json(tickets))
+}
+
+#[get("/api/v1/tickets/{ticket_id}")]
+pub async fn get_ticket(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    ticket_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("GET /api/v1/tickets/{ticket_id}");
+    let client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let ticket = db::tickets::get_ticket_from_id(&client, *ticket_id).await?;
+    require_ticket_access(&client, &user, &ticket).await?;
+
+    let messages = db::tickets::get_messages_for_ticket_id(&client, ticket.id).await?;
+    Ok(HttpResponse::Ok().json(TicketThread { ticket, messages }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewTicket {
+    pub leagueid: i64,
+    /// Your team, which the ticket is opened for.
+    pub teamid: i64,
+    pub gameid: Option<i64>,
+    pub reported_userid: Option<i64>,
+    pub category: TicketCategory,
+    pub subject: String,
+    pub message: String,
+    #[serde(default)]
+    pub evidence: Vec<String>,
+}
+
+impl Validate for NewTicket {
+    fn validate(&mut self, check: &mut Checker) {
+        check
+            .text("subject", &mut self.subject)
+            .length(1, 100)
+            .printable();
+        check.text("message", &mut self.message).length(1, 5000);
+        check_evidence(check, &mut self.evidence);
+        if self.gameid.is_none() && self.reported_userid.is_none() {
+            check.error("gameid", "must be set unless a player is reported");
+        }
+    }
+}
+
+/// Open a ticket for your team, about one of its games, a player, or both.
+#[post("/api/v1/tickets")]
+pub async fn post_ticket(
+    body: Valid<NewTicket>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+) -> HttpResult {
+    log::info!("POST /api/v1/tickets");
+    let mut client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let body = body.into_inner();
+    match db::leagues::get_league_from_id(&client, body.leagueid).await {
+        Ok(_) => (),
+        Err(MyError::NotFound) => {
+            return Err(MyError::invalid_field("leagueid", "no such league"));
+        }
+        Err(err) => return Err(err),
+    }
+    let level =
+        db::teams::get_affiliation_in_league(&client, user.id, body.teamid, body.leagueid).await?;
+    if level.is_none_or(|level| level < UserTeamAffiliation::Officer as i32) {
+        return Err(MyError::Forbidden(
+            "Only leaders and officers of a team in this league can open tickets for it",
+        ));
+    }
+
+    let mut check = Checker::new(state.name_filter.as_ref());
+    if let Some(gameid) = body.gameid {
+        match db::games::get_game_from_id(&client, gameid).await {
+            Ok(game) => {
+                let ours = game.teamhomeid == body.teamid || game.teamawayid == body.teamid;
+                if game.leagueid != body.leagueid || !ours {
+                    check.error("gameid", "not one of your team's games in this league");
+                }
+            }
+            Err(MyError::NotFound) => check.error("gameid", "no such game"),
+            Err(err) => return Err(err),
+        }
+    }
+    if let Some(userid) = body.reported_userid {
+        let in_game = match body.gameid {
+            Some(gameid) => db::lineups::get_lineup_for_game_id(&client, gameid)
+                .await?
+                .iter()
+                .any(|entry| entry.userid == userid),
+            None => false,
+        };
+        if userid == user.id {
+            check.error("reported_userid", "can't be yourself");
+        } else if !in_game
+            && !db::teams::has_played_in_league(&client, userid, body.leagueid).await?
+        {
+            check.error(
+                "reported_userid",
+                "not a player in this league or in the game",
+            );
+        }
+    }
+    check.finish()?;
+
+    let transaction = client.transaction().await?;
+    let ticket = db::tickets::add_ticket(
+        &transaction,
+        &MiniTicket {
+            leagueid: body.leagueid,
+            teamid: body.teamid,
+            opened_by: user.id,
+            gameid: body.gameid,
+            reported_userid: body.reported_userid,
+            category: body.category,
+            subject: body.subject,
+        },
+    )
+    .await?;
+    let message = db::tickets::add_message(
+        &transaction,
+        ticket.id,
+        user.id,
+        &body.message,
+        &body.evidence,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    log::info!(
+        "Team {0} opened ticket {1} in league {2}",
+        ticket.teamid,
+        ticket.id,
+        ticket.leagueid
+    );
+    Ok(HttpResponse::Created().json(TicketThread {
+        ticket,
+        messages: vec![message],
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewMessage {
+    pub message: String,
+    #[serde(default)]
+    pub evidence: Vec<String>,
+}
+
+impl Validate for NewMessage {
+    fn validate(&mut self, check: &mut Checker) {
+        check.text("message", &mut self.message).length(1, 5000);
+        check_evidence(check, &mut self.evidence);
+    }
+}
+
+/// Reply in a ticket's thread, while it's open or in review.
+#[post("/api/v1/tickets/{ticket_id}/messages")]
+pub async fn post_ticket_message(
+    body: Valid<NewMessage>,
+    state: web::Data<AppState>,
+    session: SessionToken,
+    ticket_id: web::Path<i64>,
+) -> HttpResult {
+    log::info!("POST /api/v1/tickets/{ticket_id}/messages");
+    let mut client = grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
+    let ticket = db::tickets::get_ticket_from_id(&client, *ticket_id).await?;
+    require_ticket_access(&client, &user, &ticket).await?;
+    if !ticket.is_open() {
+        return Err(MyError::Conflict(format!(
+            "This ticket is {0}",
+            ticket.status
+        )));
+    }
+
+    let body = body.into_inner();
+    let transaction = client.transaction().await?;
+    let message = db::tickets::add_message(
+        &transaction,
+        ticket.id,
+        user.id,
+        &body.message,
+        &body.evidence,
+    )
+    .await?;
+    transaction.commit().await?;
+    Ok(HttpResponse::Created().json(message))
+}
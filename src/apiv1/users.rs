@@ -1,12 +1,19 @@
+use crate::config::EligibilityAction;
 use crate::db;
-use crate::db::divisions::DeepTeamDivAssociation;
-use crate::db::SuperDeepTeamDivAssociation;
+use crate::db::leagues::Visibility;
+use crate::db::teams::DeepTeamDivAssociation;
+use crate::db::teams::SuperDeepTeamDivAssociation;
+use crate::eligibility;
 use crate::errors::MyError;
 use crate::models::MiniUser;
+use crate::models::RatingHistory;
 use crate::models::Team;
 use crate::models::TeamDivAssociation;
 use crate::models::User;
+use crate::models::UsernameHistory;
 use crate::steamapi;
+use crate::steamapi::ApiError;
+use crate::steamid::{SteamId, SteamIdInput};
 use crate::PlayerSummaryAccess;
 use actix_web::{get, web, Error, HttpResponse};
 use deadpool_postgres::Client;
@@ -14,6 +21,8 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::num::NonZeroU32;
 
+use crate::apiv1::leagues::HiddenParams;
+use crate::apiv1::session::SessionToken;
 use crate::apiv1::HttpResult;
 use crate::AppState;
 
@@ -26,54 +35,43 @@ struct UserResponse {
     info: User,
     ownerships: Option<Vec<Team>>,
     rosters: Option<Vec<SuperDeepTeamDivAssociation>>,
+    /// Every Steam name this user has been seen with.
+    username_history: Option<Vec<UsernameHistory>>,
+    /// Every change to this user's rating, newest first.
+    rating_history: Option<Vec<RatingHistory>>,
 }
-#[get("/api/v1/user/steamid/{steamid}")]
+/// `{steamid}` can be in any format [SteamIdInput] accepts, including profile and vanity links.
+#[get("/api/v1/user/steamid/{steamid:.*}")]
 pub async fn get_user_from_steamid(
     state: web::Data<AppState>,
     steamid: web::Path<String>,
     query_params: web::Query<UserParams>,
+    hidden: web::Query<HiddenParams>,
+    session: Option<SessionToken>,
 ) -> HttpResult {
     log::info!("GET request at /api/v1/user/steamid/{steamid}");
     let client: Client = crate::grab_pool(&state).await?;
+    let visibility = hidden.visibility(&client, session).await?;
 
-    let user = match db::get_user_from_steamid(&client, &steamid).await {
-        Ok(user) => user,
-        Err(err) => {
-            if let MyError::NotFound = err {
-                return Ok(HttpResponse::NotFound().body("404 Not Found"));
-            } else {
-                return Err(err.into());
-            }
-        }
-    };
-
-    let rosters: Option<Vec<SuperDeepTeamDivAssociation>> = match query_params.deep {
-        Some(deep) => {
-            let mut resp = None;
-            if deep {
-                resp = Some(db::get_rosters_for_user_id(&client, user.id).await?)
-            }
-            resp
-        }
-        None => None,
+    let steamid = match steamid.parse::<SteamIdInput>() {
+        Ok(input) => input,
+        Err(err) => return Err(MyError::Validation(format!("Invalid steamid: {err}"))),
     };
-    let ownerships = match query_params.deep {
-        Some(deep) => {
-            let mut resp = None;
-            if deep {
-                resp = Some(db::get_ownerships_for_user_id(&client, user.id).await?)
-            }
-            resp
-        }
-        None => None,
+    let steamid = match steamid.resolve(state.steam.as_ref()).await {
+        Ok(steamid) => steamid,
+        Err(ApiError::NotFound) => return Err(MyError::NotFound),
+        Err(err) => return Err(err.into()),
     };
 
-    let resp = UserResponse {
-        info: user,
-        rosters,
-        ownerships,
-    };
+    let user = db::users::get_user_from_steamid(&client, &steamid.to_string()).await?;
 
+    let resp = user_response(
+        &client,
+        user,
+        query_params.deep.unwrap_or(false),
+        &visibility,
+    )
+    .await?;
     Ok(HttpResponse::Ok().json(resp))
 }
 
@@ -84,37 +82,67 @@ pub async fn get_user_from_auth_token(
     query_params: web::Query<UserParams>,
 ) -> HttpResult {
     log::info!("GET request at /api/v1/user/authtoken/{authtoken}");
-    let client: Client = state.pool.get().await.map_err(MyError::PoolError)?;
+    let client: Client = crate::grab_pool(&state).await?;
 
-    let user = db::get_user_from_auth_token(&client, &authtoken).await?;
+    let user = db::users::get_user_from_auth_token(&client, &authtoken).await?;
 
-    let rosters: Option<Vec<SuperDeepTeamDivAssociation>> = match query_params.deep {
-        Some(deep) => {
-            let mut resp = None;
-            if deep {
-                resp = Some(db::get_rosters_for_user_id(&client, user.id).await?)
-            }
-            resp
-        }
-        None => None,
-    };
-    let ownerships = match query_params.deep {
-        Some(deep) => {
-            let mut resp = None;
-            if deep {
-                resp = Some(db::get_ownerships_for_user_id(&client, user.id).await?)
-            }
-            resp
-        }
-        None => None,
+    // Whoever has the token is this user, and gets to see all of their own rosters.
+    let resp = user_response(
+        &client,
+        user,
+        query_params.deep.unwrap_or(false),
+        &Visibility::All,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// `user`, and if `deep` everything they've been part of. Only the rosters `visibility` shows
+/// are listed.
+async fn user_response(
+    client: &Client,
+    user: User,
+    deep: bool,
+    visibility: &Visibility,
+) -> Result<UserResponse, MyError> {
+    let (rosters, ownerships, username_history, rating_history) = match deep {
+        true => (
+            Some(db::teams::get_rosters_for_user_id(client, user.id, visibility).await?),
+            Some(db::teams::get_ownerships_for_user_id(client, user.id).await?),
+            Some(db::users::get_username_history(client, user.id).await?),
+            Some(db::ratings::get_rating_history_for_user_id(client, user.id).await?),
+        ),
+        false => (None, None, None, None),
     };
 
-    let resp = UserResponse {
+    Ok(UserResponse {
         info: user,
         rosters,
         ownerships,
-    };
+        username_history,
+        rating_history,
+    })
+}
+
+/// The user owning the current session, for frontends that can't read their own token.
+#[get("/api/v1/user/me")]
+pub async fn get_current_user(
+    state: web::Data<AppState>,
+    session: SessionToken,
+    query_params: web::Query<UserParams>,
+) -> HttpResult {
+    log::info!("GET request at /api/v1/user/me");
+    let client: Client = crate::grab_pool(&state).await?;
+
+    let user = session.user(&client).await?;
 
+    let resp = user_response(
+        &client,
+        user,
+        query_params.deep.unwrap_or(false),
+        &Visibility::All,
+    )
+    .await?;
     Ok(HttpResponse::Ok().json(resp))
 }
 
@@ -136,14 +164,14 @@ pub async fn get_users_paged(
     state: web::Data<AppState>,
     query: web::Query<PageRequest>,
 ) -> HttpResult {
-    let client = state.pool.get().await.unwrap();
+    let client = crate::grab_pool(&state).await?;
     let amount = query
         .amount_per_page
         .unwrap_or(NonZeroU32::new(10).unwrap());
     let page = query.page.unwrap_or(0);
 
-    let total_count = db::get_user_count(&client);
-    let users = db::get_user_page(&client, page, amount);
+    let total_count = db::users::get_user_count(&client);
+    let users = db::users::get_user_page(&client, page, amount);
 
     let joined = futures::future::try_join(total_count, users).await?;
 
@@ -167,24 +195,38 @@ pub async fn search_users(
     state: web::Data<AppState>,
     query: web::Query<SearchQuery>,
 ) -> HttpResult {
-    let client = state.pool.get().await.unwrap();
+    let client = crate::grab_pool(&state).await?;
     let amount = query
         .amount_per_page
         .unwrap_or(NonZeroU32::new(10).unwrap());
     let page = query.page.unwrap_or(0);
 
-    let results = db::search_usernames(&client, &query.q, page, amount).await?;
+    let results = db::users::search_usernames(&client, &query.q, page, amount).await?;
 
     Ok(HttpResponse::Ok().json(results))
 }
 
 pub async fn add_user_with_steamid(
     state: &web::Data<AppState>,
-    db_client: &Client,
-    steamid: &str,
+    db_client: &mut Client,
+    steamid: SteamId,
 ) -> Result<User, MyError> {
     log::debug!("Adding users with steamid: {steamid}");
-    let steam_user_access_level = steamapi::get_user_summary(&state.steam_api_key, steamid).await?;
+    let steam_user_access_level = state.steam.get_user_summary(&steamid.to_string()).await?;
+
+    let mut ineligible_reasons = Vec::new();
+    if state.eligibility.action != EligibilityAction::Off {
+        ineligible_reasons = eligibility::check_eligibility(
+            &state.eligibility,
+            state.steam.as_ref(),
+            &steam_user_access_level,
+        )
+        .await?;
+    }
+    if !ineligible_reasons.is_empty() && state.eligibility.action == EligibilityAction::Block {
+        log::info!("Refusing signup of {steamid}: {ineligible_reasons:?}");
+        return Err(MyError::Ineligible(ineligible_reasons));
+    }
 
     // hacky oneliner: extract public information regardless of return type
     let (PlayerSummaryAccess::All { public, .. } | PlayerSummaryAccess::Private { public }) =
@@ -196,15 +238,34 @@ pub async fn add_user_with_steamid(
         permissions: None,
     };
 
+    // Only open the transaction now that we're done waiting on Steam.
     log::trace!("Adding user in db");
-    let add_user_resp = db::add_user(db_client, user).await?;
+    let transaction = db_client.transaction().await?;
+    let mut add_user_resp = db::users::add_user(&transaction, user).await?;
+    db::users::record_username(
+        &transaction,
+        add_user_resp.id,
+        &add_user_resp.username,
+        add_user_resp.created_at,
+    )
+    .await?;
+
+    if !ineligible_reasons.is_empty() {
+        log::info!("Flagging new user {steamid} for review: {ineligible_reasons:?}");
+        let reasons: Vec<String> = ineligible_reasons
+            .iter()
+            .map(|reason| reason.to_string())
+            .collect();
+        db::users::add_review_flag(&transaction, add_user_resp.id, &reasons).await?;
+    }
 
     if let Some(rootid) = &state.root_user_steamid {
-        if rootid == steamid {
-            if let Ok(user) = db::set_super_user(db_client, &add_user_resp).await {
-                log::info!("Set super user {user:?}")
-            };
+        // Accept the root user's id in any format.
+        if rootid.parse::<SteamId>() == Ok(steamid) {
+            add_user_resp = db::users::set_super_user(&transaction, &add_user_resp).await?;
+            log::info!("Set super user {add_user_resp:?}");
         }
     }
+    transaction.commit().await?;
     Ok(add_user_resp)
 }
@@ -0,0 +1,334 @@
+//! Checking request bodies before they reach a handler.
+//!
+//! A body implements [Validate] and is taken as [Valid] instead of `web::Json`.
+//! Text fields are trimmed before they're checked, and every broken rule is collected,
+//! so the client gets all of them at once as the `details` of a `validation_failed` error.
+use std::collections::HashSet;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::MyError;
+use crate::AppState;
+
+/// One broken rule, on the field named `field`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+pub trait Validate {
+    /// Check (and tidy up) every field with `check`.
+    fn validate(&mut self, check: &mut Checker);
+}
+
+/// Decides whether a name shown to other players is acceptable, e.g. against a profanity list.
+pub trait NameFilter: Send + Sync {
+    /// Why `text` isn't allowed, or `None` if it's fine.
+    fn reject(&self, text: &str) -> Option<String>;
+}
+
+/// Rejects names containing any of a list of words, ignoring case.
+#[derive(Default, Debug, Clone)]
+pub struct WordBlocklist {
+    words: HashSet<String>,
+}
+
+impl WordBlocklist {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        let words = words
+            .into_iter()
+            .map(|word| word.as_ref().trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect();
+        WordBlocklist { words }
+    }
+
+    /// One word per line. Empty lines and lines starting with `#` are skipped.
+    pub fn from_file(path: &str) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            contents.lines().filter(|line| !line.starts_with('#')),
+        ))
+    }
+}
+
+impl NameFilter for WordBlocklist {
+    fn reject(&self, text: &str) -> Option<String> {
+        let text = text.to_lowercase();
+        self.words
+            .iter()
+            .any(|word| text.contains(word.as_str()))
+            .then(|| "contains a blocked word".to_owned())
+    }
+}
+
+/// Collects [FieldError]s while a body is validated.
+pub struct Checker<'a> {
+    filter: &'a dyn NameFilter,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(filter: &'a dyn NameFilter) -> Self {
+        Checker {
+            filter,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Trim `value`, and start checking it.
+    pub fn text<'c>(&'c mut self, field: &str, value: &'c mut String) -> TextRules<'c, 'a> {
+        trim_in_place(value);
+        TextRules {
+            checker: self,
+            field: field.to_owned(),
+            value: Some(value),
+            failed: false,
+        }
+    }
+
+    /// Like [Checker::text], but a missing or blank value is fine and becomes `None`.
+    pub fn optional_text<'c>(
+        &'c mut self,
+        field: &str,
+        value: &'c mut Option<String>,
+    ) -> TextRules<'c, 'a> {
+        if let Some(text) = value {
+            trim_in_place(text);
+        }
+        if value.as_ref().is_some_and(|text| text.is_empty()) {
+            *value = None;
+        }
+        TextRules {
+            checker: self,
+            field: field.to_owned(),
+            value: value.as_deref(),
+            failed: false,
+        }
+    }
+
+    /// Record a broken rule that isn't covered by [TextRules].
+    pub fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_owned(),
+            message: message.into(),
+        });
+    }
+
+    pub fn finish(self) -> Result<(), MyError> {
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(MyError::InvalidFields(self.errors)),
+        }
+    }
+}
+
+fn trim_in_place(value: &mut String) {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = trimmed.to_owned();
+    }
+}
+
+/// Rules for one text field. Only the first rule it breaks is reported.
+pub struct TextRules<'c, 'a> {
+    checker: &'c mut Checker<'a>,
+    field: String,
+    /// `None` for a missing optional field, which passes every rule.
+    value: Option<&'c str>,
+    failed: bool,
+}
+
+impl TextRules<'_, '_> {
+    fn rule(mut self, broken: impl FnOnce(&str) -> Option<String>) -> Self {
+        if self.failed {
+            return self;
+        }
+        if let Some(message) = self.value.and_then(broken) {
+            self.checker.error(&self.field, message);
+            self.failed = true;
+        }
+        self
+    }
+
+    /// Between `min` and `max` characters long, inclusive.
+    pub fn length(self, min: usize, max: usize) -> Self {
+        self.rule(|value| {
+            let length = value.chars().count();
+            if length < min {
+                match min {
+                    1 => Some("must not be empty".to_owned()),
+                    _ => Some(format!("must be at least {min} characters")),
+                }
+            } else if length > max {
+                Some(format!("must be at most {max} characters"))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// No control characters, or characters that don't show up when printed.
+    pub fn printable(self) -> Self {
+        self.rule(|value| {
+            value
+                .chars()
+                .any(is_invisible)
+                .then(|| "must not contain control or invisible characters".to_owned())
+        })
+    }
+
+    /// [TextRules::printable], and no whitespace either.
+    pub fn single_word(self) -> Self {
+        self.printable().rule(|value| {
+            value
+                .chars()
+                .any(char::is_whitespace)
+                .then(|| "must not contain spaces".to_owned())
+        })
+    }
+
+    /// Run the deployment's [NameFilter].
+    pub fn allowed_name(self) -> Self {
+        let filter = self.checker.filter;
+        self.rule(|value| filter.reject(value))
+    }
+}
+
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}'
+        )
+}
+
+/// A JSON body that passed [Validate]. Use in place of `web::Json`.
+pub struct Valid<T>(pub T);
+
+impl<T> Valid<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Valid<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for Valid<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        let filter: Arc<dyn NameFilter> = match req.app_data::<web::Data<AppState>>() {
+            Some(state) => state.name_filter.clone(),
+            None => Arc::new(WordBlocklist::default()),
+        };
+
+        Box::pin(async move {
+            let mut body = json.await?.into_inner();
+            let mut check = Checker::new(filter.as_ref());
+            body.validate(&mut check);
+            check.finish()?;
+            Ok(Valid(body))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Body {
+        name: String,
+        tag: String,
+        nickname: Option<String>,
+    }
+
+    impl Validate for Body {
+        fn validate(&mut self, check: &mut Checker) {
+            check
+                .text("name", &mut self.name)
+                .length(1, 10)
+                .printable()
+                .allowed_name();
+            check.text("tag", &mut self.tag).length(1, 4).single_word();
+            check
+                .optional_text("nickname", &mut self.nickname)
+                .length(2, 5);
+        }
+    }
+
+    fn check(body: &mut Body) -> Vec<FieldError> {
+        let blocklist = WordBlocklist::new(["Darn"]);
+        let mut check = Checker::new(&blocklist);
+        body.validate(&mut check);
+        match check.finish() {
+            Ok(()) => Vec::new(),
+            Err(MyError::InvalidFields(errors)) => errors,
+            Err(err) => panic!("{err:?}"),
+        }
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|error| error.field.as_str()).collect()
+    }
+
+    #[test]
+    fn trims_and_accepts_valid_bodies() {
+        let mut body = Body {
+            name: "  Froyotech ".to_owned(),
+            tag: "\tFROY\n".to_owned(),
+            nickname: Some("   ".to_owned()),
+        };
+        assert_eq!(check(&mut body), []);
+        assert_eq!(body.name, "Froyotech");
+        assert_eq!(body.tag, "FROY");
+        assert_eq!(body.nickname, None);
+    }
+
+    #[test]
+    fn reports_every_field_once() {
+        let mut body = Body {
+            name: "darnation\u{200B}".to_owned(),
+            tag: "a b".to_owned(),
+            nickname: Some("x".to_owned()),
+        };
+        let errors = check(&mut body);
+        assert_eq!(fields(&errors), ["name", "tag", "nickname"]);
+        assert_eq!(
+            errors[0].message,
+            "must not contain control or invisible characters"
+        );
+        assert_eq!(errors[1].message, "must not contain spaces");
+        assert_eq!(errors[2].message, "must be at least 2 characters");
+    }
+
+    #[test]
+    fn blocklist_ignores_case() {
+        let mut body = Body {
+            name: "DARNit".to_owned(),
+            tag: "ok".to_owned(),
+            nickname: None,
+        };
+        let errors = check(&mut body);
+        assert_eq!(fields(&errors), ["name"]);
+        assert_eq!(errors[0].message, "contains a blocked word");
+
+        body.name = String::new();
+        assert_eq!(check(&mut body)[0].message, "must not be empty");
+    }
+}
@@ -1,21 +1,21 @@
 // Code relating to the Authorization model - generation, etc.
 use chrono::Months;
-use deadpool_postgres::Client;
+use deadpool_postgres::GenericClient;
 use randomizer::Randomizer;
 
-use crate::db::register_authorization;
+use crate::db::users::register_authorization;
 use crate::errors::MyError;
 use crate::models::{Authorization, User};
 
 pub async fn get_authorization_for_user(
-    dbclient: &Client,
+    dbclient: &impl GenericClient,
     user: &User,
 ) -> Result<Authorization, MyError> {
-    crate::db::get_authorization_for_user(dbclient, user).await
+    crate::db::users::get_authorization_for_user(dbclient, user).await
 }
 
 pub async fn create_authorization_for_user(
-    dbclient: &Client,
+    dbclient: &impl GenericClient,
     user: &User,
 ) -> Result<Authorization, MyError> {
     let token = Randomizer::ALPHANUMERIC(100).string().unwrap();
@@ -6,14 +6,126 @@ use serde::Deserialize;
 pub struct ExampleConfig {
     pub root_user_steamid: Option<String>,
     pub steam_api_key: String,
+    /// How long to wait on a single Steam API request, in seconds.
+    #[confik(default = 10u64)]
+    pub steam_api_timeout_secs: u64,
+    /// How long to wait on logs.tf when fetching a log, in seconds.
+    #[confik(default = 10u64)]
+    pub logs_tf_timeout_secs: u64,
+    /// How many Steam API requests to make per second, on average.
+    #[confik(default = 1.0f64)]
+    pub steam_api_requests_per_second: f64,
+    /// How long to cache player summaries for, in seconds.
+    #[confik(default = 300u64)]
+    pub steam_summary_cache_secs: u64,
     pub openid_realm: String,
     pub openid_port: String,
     pub server_addr: String,
     pub server_port: u16,
+    /// How often to refresh every user's Steam name and avatar, in seconds. 0 disables it.
+    #[confik(default = 3600u64)]
+    pub steam_sync_interval_secs: u64,
+    /// How often to settle games whose scheduling deadline passed, in seconds. 0 disables it.
+    #[confik(default = 300u64)]
+    pub schedule_check_interval_secs: u64,
+    /// How clients present their session token. Defaults to [AuthMode::Bearer].
+    #[confik(default)]
+    pub auth_mode: AuthMode,
+    /// The `Domain` attribute of session cookies, e.g. `passtime.tf` to share
+    /// them between the frontend and API subdomains. Host-only if unset.
+    pub session_cookie_domain: Option<String>,
+    /// Rules new accounts are checked against on their first login.
+    #[confik(default)]
+    pub eligibility: EligibilityConfig,
+    /// Where uploaded files, like team logos, are stored. Served under `/uploads`.
+    #[confik(default = "uploads")]
+    pub upload_dir: String,
+    /// Where uploaded STV demos are stored. Unlike `upload_dir`, it isn't served as is.
+    #[confik(default = "demos")]
+    pub demo_dir: String,
+    /// How big a demo can be, in MiB.
+    #[confik(default = 200u64)]
+    pub max_demo_size_mib: u64,
+    /// How long to keep demos for, in days. 0 keeps them forever.
+    #[confik(default = 90u64)]
+    pub demo_retention_days: u64,
+    /// A file of words, one per line, that team, roster, league and division names can't contain.
+    pub name_blocklist_file: Option<String>,
     #[confik(from = DbConfig)]
     pub pg: deadpool_postgres::Config,
 }
 
+/// How a deployment expects clients to authenticate.
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// `Authorization: Bearer (token)` only. The token is handed to the client on login.
+    #[default]
+    Bearer,
+    /// HttpOnly session cookie only. State-changing requests must carry a CSRF token.
+    Cookie,
+    /// Accept either of the above.
+    Both,
+}
+
+impl AuthMode {
+    pub fn allows_bearer(self) -> bool {
+        self != AuthMode::Cookie
+    }
+    pub fn allows_cookie(self) -> bool {
+        self != AuthMode::Bearer
+    }
+}
+
+impl confik::Configuration for AuthMode {
+    type Builder = Option<Self>;
+}
+
+/// Which Steam accounts are allowed to sign up. Every rule is off by default.
+#[derive(Debug, Default, Configuration, Clone)]
+pub struct EligibilityConfig {
+    /// What happens to an account that breaks any of the rules.
+    #[confik(default)]
+    pub action: EligibilityAction,
+    /// How old the Steam account must be, in days.
+    #[confik(default)]
+    pub min_account_age_days: u32,
+    /// Whether the Steam profile must be public.
+    #[confik(default)]
+    pub require_public_profile: bool,
+    /// Whether the account must own (or have played) TF2.
+    #[confik(default)]
+    pub require_tf2: bool,
+    /// How many hours of TF2 the account must have. Implies `require_tf2`.
+    #[confik(default)]
+    pub min_tf2_hours: u32,
+    /// Whether accounts with a VAC ban are ineligible.
+    #[confik(default)]
+    pub reject_vac_banned: bool,
+    /// Whether accounts with a game ban are ineligible.
+    #[confik(default)]
+    pub reject_game_banned: bool,
+    /// Only count bans from the last this many days. 0 counts every ban ever.
+    #[confik(default)]
+    pub ban_lookback_days: u32,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EligibilityAction {
+    /// Don't check eligibility at all.
+    #[default]
+    Off,
+    /// Let the account sign up, but flag it for an admin to review.
+    Flag,
+    /// Refuse to create the account.
+    Block,
+}
+
+impl confik::Configuration for EligibilityAction {
+    type Builder = Option<Self>;
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(transparent)]
 struct DbConfig(deadpool_postgres::Config);
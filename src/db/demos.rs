@@ -0,0 +1,117 @@
+// STV demos uploaded for games. The files themselves are in `AppState::demo_dir`.
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
+
+use super::{query_all, query_one, query_scalar};
+use crate::{
+    errors::MyError,
+    models::{GameDemo, MiniGameDemo, TicketStatus},
+};
+
+/// Every demo uploaded for game `gameid`, oldest first.
+pub async fn get_demos_for_game_id(
+    client: &impl GenericClient,
+    gameid: i64,
+) -> Result<Vec<GameDemo>, MyError> {
+    query_all(
+        client,
+        sql!(
+            GameDemo,
+            "SELECT $table_fields FROM game_demos WHERE gameid=$1 ORDER BY created_at, id"
+        ),
+        &[&gameid],
+    )
+    .await
+}
+
+pub async fn get_demo_from_id(
+    client: &impl GenericClient,
+    demoid: i64,
+) -> Result<GameDemo, MyError> {
+    query_one(
+        client,
+        sql!(GameDemo, "SELECT $table_fields FROM game_demos WHERE id=$1"),
+        &[&demoid],
+    )
+    .await
+}
+
+pub async fn count_demos_for_team(
+    client: &impl GenericClient,
+    gameid: i64,
+    teamid: i64,
+) -> Result<i64, MyError> {
+    query_scalar(
+        client,
+        "SELECT COUNT(*) FROM game_demos WHERE gameid=$1 AND teamid=$2",
+        &[&gameid, &teamid],
+    )
+    .await
+}
+
+pub async fn add_demo(
+    client: &impl GenericClient,
+    demo: &MiniGameDemo,
+) -> Result<GameDemo, MyError> {
+    query_one(
+        client,
+        sql!(
+            GameDemo,
+            "INSERT INTO game_demos(gameid, teamid, uploaded_by, file_name, stored_name, \
+            size_bytes, sha256, created_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &demo.gameid,
+            &demo.teamid,
+            &demo.uploaded_by,
+            &demo.file_name,
+            &demo.stored_name,
+            &demo.size_bytes,
+            &demo.sha256,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+/// Forget every demo of league `leagueid`'s games, returning them so their files can be removed.
+pub async fn delete_demos_for_league_id(
+    client: &impl GenericClient,
+    leagueid: i64,
+) -> Result<Vec<GameDemo>, MyError> {
+    query_all(
+        client,
+        sql!(
+            GameDemo,
+            "DELETE FROM game_demos \
+            WHERE gameid IN (SELECT id FROM games WHERE leagueid=$1) \
+            RETURNING $table_fields"
+        ),
+        &[&leagueid],
+    )
+    .await
+}
+
+/// Forget every demo uploaded before `before`, returning them so their files can be removed.
+/// Demos of games with a ticket still open or in review are kept, as they may settle it.
+pub async fn delete_demos_before(
+    client: &impl GenericClient,
+    before: DateTime<Utc>,
+) -> Result<Vec<GameDemo>, MyError> {
+    query_all(
+        client,
+        sql!(
+            GameDemo,
+            "DELETE FROM game_demos WHERE created_at < $1 \
+            AND gameid NOT IN (SELECT gameid FROM tickets WHERE gameid IS NOT NULL AND status=ANY($2)) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &before,
+            &[TicketStatus::Open.as_str(), TicketStatus::InReview.as_str()].as_slice(),
+        ],
+    )
+    .await
+}
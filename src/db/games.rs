@@ -0,0 +1,297 @@
+// Games played between teams.
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
+
+use super::{execute, leagues::Visibility, query_all, query_one, query_opt};
+use crate::{
+    errors::MyError,
+    models::{Game, GameTimeProposal, MiniGame, ProposalStatus},
+};
+
+pub async fn get_game_from_id(client: &impl GenericClient, gameid: i64) -> Result<Game, MyError> {
+    query_one(
+        client,
+        sql!(Game, "SELECT $table_fields FROM games WHERE id=$1"),
+        &[&gameid],
+    )
+    .await
+}
+
+/// Game `gameid`, unless `visibility` doesn't show its league, in which case it's
+/// [MyError::NotFound] just like a missing game.
+pub async fn get_visible_game_from_id(
+    client: &impl GenericClient,
+    gameid: i64,
+    visibility: &Visibility,
+) -> Result<Game, MyError> {
+    let (all, leagueids) = visibility.params();
+    query_one(
+        client,
+        sql!(
+            Game,
+            "SELECT $table_fields FROM games JOIN leagues ON leagues.id=games.leagueid \
+            WHERE games.id=$1 AND (NOT leagues.is_hidden OR $2 OR leagues.id=any($3))"
+        ),
+        &[&gameid, &all, &leagueids],
+    )
+    .await
+}
+
+/// Lock game `gameid` until the end of the transaction, so checks on what belongs to it
+/// can't race each other.
+pub async fn lock_game(client: &impl GenericClient, gameid: i64) -> Result<(), MyError> {
+    match execute(
+        client,
+        "SELECT id FROM games WHERE id=$1 FOR UPDATE",
+        &[&gameid],
+    )
+    .await?
+    {
+        0 => Err(MyError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Every game in `leagueid`, earliest first.
+pub async fn get_games_for_league_id(
+    client: &impl GenericClient,
+    leagueid: i64,
+) -> Result<Vec<Game>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Game,
+            "SELECT $table_fields FROM games WHERE leagueid=$1 ORDER BY played_at ASC"
+        ),
+        &[&leagueid],
+    )
+    .await
+}
+
+pub async fn add_game(client: &impl GenericClient, game: &MiniGame) -> Result<Game, MyError> {
+    query_one(
+        client,
+        sql!(
+            Game,
+            "INSERT INTO games(title, leagueid, teamhomeid, teamawayid, created_at, played_at, \
+            window_starts_at, window_ends_at, schedule_deadline) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &game.title,
+            &game.leagueid,
+            &game.teamhomeid,
+            &game.teamawayid,
+            &chrono::offset::Utc::now(),
+            &game.played_at,
+            &game.window_starts_at,
+            &game.window_ends_at,
+            &game.schedule_deadline,
+        ],
+    )
+    .await
+}
+
+/// Record the score of game `gameid` and mark it confirmed.
+/// Errors with [MyError::NotFound] if it doesn't exist or was already confirmed.
+pub async fn confirm_result(
+    client: &impl GenericClient,
+    gameid: i64,
+    home_score: i32,
+    away_score: i32,
+) -> Result<Game, MyError> {
+    query_one(
+        client,
+        sql!(
+            Game,
+            "UPDATE games SET home_score=$2, away_score=$3, confirmed_at=$4 \
+            WHERE id=$1 AND confirmed_at IS NULL \
+            RETURNING $table_fields"
+        ),
+        &[
+            &gameid,
+            &home_score,
+            &away_score,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+/// Replace the score of game `gameid`, confirming it if it wasn't already.
+pub async fn override_result(
+    client: &impl GenericClient,
+    gameid: i64,
+    home_score: i32,
+    away_score: i32,
+) -> Result<Game, MyError> {
+    query_one(
+        client,
+        sql!(
+            Game,
+            "UPDATE games SET home_score=$2, away_score=$3, \
+            confirmed_at=COALESCE(confirmed_at, $4) \
+            WHERE id=$1 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &gameid,
+            &home_score,
+            &away_score,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+/// Every time proposed for game `gameid`, newest first.
+pub async fn get_proposals_for_game_id(
+    client: &impl GenericClient,
+    gameid: i64,
+) -> Result<Vec<GameTimeProposal>, MyError> {
+    query_all(
+        client,
+        sql!(
+            GameTimeProposal,
+            "SELECT $table_fields FROM game_time_proposals WHERE gameid=$1 \
+            ORDER BY created_at DESC, id DESC"
+        ),
+        &[&gameid],
+    )
+    .await
+}
+
+pub async fn get_proposal_from_id(
+    client: &impl GenericClient,
+    proposalid: i64,
+) -> Result<GameTimeProposal, MyError> {
+    query_one(
+        client,
+        sql!(
+            GameTimeProposal,
+            "SELECT $table_fields FROM game_time_proposals WHERE id=$1"
+        ),
+        &[&proposalid],
+    )
+    .await
+}
+
+/// Propose `proposed_time` for game `gameid` on behalf of team `teamid`.
+/// Whatever was still pending for the game is superseded, so this doubles as a counter-proposal.
+pub async fn add_proposal(
+    client: &impl GenericClient,
+    gameid: i64,
+    teamid: i64,
+    proposed_by: i64,
+    proposed_time: DateTime<Utc>,
+) -> Result<GameTimeProposal, MyError> {
+    let now = chrono::offset::Utc::now();
+    execute(
+        client,
+        "UPDATE game_time_proposals SET status=$2, responded_by=$3, responded_at=$4 \
+        WHERE gameid=$1 AND status=$5",
+        &[
+            &gameid,
+            &ProposalStatus::Superseded.as_str(),
+            &proposed_by,
+            &now,
+            &ProposalStatus::Pending.as_str(),
+        ],
+    )
+    .await?;
+    query_one(
+        client,
+        sql!(
+            GameTimeProposal,
+            "INSERT INTO game_time_proposals(gameid, teamid, proposed_by, proposed_time, \
+            status, created_at) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &gameid,
+            &teamid,
+            &proposed_by,
+            &proposed_time,
+            &ProposalStatus::Pending.as_str(),
+            &now,
+        ],
+    )
+    .await
+}
+
+/// Accept pending proposal `proposalid` and move its game to the proposed time.
+/// Errors with [MyError::Conflict] if the proposal isn't pending any more,
+/// or the game's time was already settled.
+pub async fn accept_proposal(
+    client: &impl GenericClient,
+    proposalid: i64,
+    accepted_by: i64,
+) -> Result<Game, MyError> {
+    let now = chrono::offset::Utc::now();
+    let Some(proposal) = query_opt::<GameTimeProposal>(
+        client,
+        sql!(
+            GameTimeProposal,
+            "UPDATE game_time_proposals SET status=$2, responded_by=$3, responded_at=$4 \
+            WHERE id=$1 AND status=$5 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &proposalid,
+            &ProposalStatus::Accepted.as_str(),
+            &accepted_by,
+            &now,
+            &ProposalStatus::Pending.as_str(),
+        ],
+    )
+    .await?
+    else {
+        return Err(MyError::Conflict(
+            "This proposal isn't pending any more".to_owned(),
+        ));
+    };
+    // Settling the game may have beaten us to it, after the caller checked.
+    query_opt(
+        client,
+        sql!(
+            Game,
+            "UPDATE games SET played_at=$2, time_agreed_at=$3 \
+            WHERE id=$1 AND time_agreed_at IS NULL AND confirmed_at IS NULL \
+            RETURNING $table_fields"
+        ),
+        &[&proposal.gameid, &proposal.proposed_time, &now],
+    )
+    .await?
+    .ok_or_else(|| MyError::Conflict("This game's time can't be negotiated any more".to_owned()))
+}
+
+/// Settle every game whose scheduling deadline has passed by `now` without an agreed time.
+/// Games without a deadline are settled once their window has ended.
+/// They keep their default `played_at`, and whatever was still proposed for them expires.
+/// Returns how many games were settled.
+pub async fn settle_overdue_schedules(
+    client: &impl GenericClient,
+    now: DateTime<Utc>,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "UPDATE game_time_proposals SET status=$2, responded_at=$1 \
+        WHERE status=$3 AND gameid IN (SELECT id FROM games \
+        WHERE time_agreed_at IS NULL AND COALESCE(schedule_deadline, window_ends_at) <= $1)",
+        &[
+            &now,
+            &ProposalStatus::Expired.as_str(),
+            &ProposalStatus::Pending.as_str(),
+        ],
+    )
+    .await?;
+    execute(
+        client,
+        "UPDATE games SET time_agreed_at=$1 \
+        WHERE time_agreed_at IS NULL AND COALESCE(schedule_deadline, window_ends_at) <= $1",
+        &[&now],
+    )
+    .await
+}
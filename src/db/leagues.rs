@@ -1,78 +1,691 @@
+// Leagues, their divisions, and who runs each division.
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
-use deadpool_postgres::Client;
-use tokio_pg_mapper::FromTokioPostgresRow;
+use deadpool_postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+use tokio_pg_mapper_derive::PostgresMapper;
 
+use super::{
+    execute, query_all, query_one, query_scalar,
+    teams::{get_teams_for_div_ids, DeepTeamDivAssociation},
+};
 use crate::{
+    admin::MiniDivision,
     errors::MyError,
-    models::{
-        Authorization, Division, League, MiniLeague, MiniTeam, MiniUser, Team, TeamDivAssociation,
-    },
+    models::{Division, DivisionAdmin, League, MiniLeague, WrappedDivisionAdmin},
 };
 
-pub async fn get_teams_with_divisionid(
-    client: &Client,
-    divisionid: i64,
-) -> Result<Vec<TeamDivAssociation>, MyError> {
-    let _stmt = "SELECT $table_fields FROM teamDivAssociations WHERE divisionid=$1";
-    let _stmt = _stmt.replace("$table_fields", &Team::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
+/// Which hidden leagues, and the private rosters in them, a read gets to see.
+///
+/// Every query that public routes read leagues, rosters or games through takes one of these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Visibility {
+    /// Only what's public.
+    Public,
+    /// Everything, for anyone who can manage leagues and for reads that aren't shown to anyone.
+    All,
+    /// What's public, and everything in these leagues.
+    Leagues(Vec<i64>),
+}
+
+impl Visibility {
+    /// As query parameters: whether everything is visible, and which hidden leagues are.
+    /// Queries filter leagues with `(NOT leagues.is_hidden OR $a OR leagues.id=any($b))`,
+    /// and rosters with `(NOT teamDivAssociations.is_private OR $a OR leagues.id=any($b))`.
+    pub(super) fn params(&self) -> (bool, Vec<i64>) {
+        match self {
+            Visibility::Public => (false, Vec::new()),
+            Visibility::All => (true, Vec::new()),
+            Visibility::Leagues(leagueids) => (false, leagueids.clone()),
+        }
+    }
+}
+
+pub async fn get_leagues(
+    client: &impl GenericClient,
+    visibility: &Visibility,
+) -> Result<Vec<League>, MyError> {
+    let (all, leagueids) = visibility.params();
+    query_all(
+        client,
+        sql!(
+            League,
+            "SELECT $table_fields FROM leagues WHERE (NOT is_hidden OR $1 OR id=any($2));"
+        ),
+        &[&all, &leagueids],
+    )
+    .await
+}
+
+pub async fn get_league_from_id(
+    client: &impl GenericClient,
+    leagueid: i64,
+) -> Result<League, MyError> {
+    log::debug!("Getting league {leagueid}");
+    query_one(
+        client,
+        sql!(League, "SELECT $table_fields FROM leagues WHERE id=$1;"),
+        &[&leagueid],
+    )
+    .await
+}
+
+/// Lock league `leagueid` until the end of the transaction, so a tag check in it can't race
+/// a sign up or a rename.
+pub async fn lock_league(client: &impl GenericClient, leagueid: i64) -> Result<(), MyError> {
+    match execute(
+        client,
+        "SELECT id FROM leagues WHERE id=$1 FOR UPDATE",
+        &[&leagueid],
+    )
+    .await?
+    {
+        0 => Err(MyError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// `leagueid`, unless `visibility` doesn't show it, in which case it's [MyError::NotFound]
+/// just like a missing league.
+pub async fn get_visible_league_from_id(
+    client: &impl GenericClient,
+    leagueid: i64,
+    visibility: &Visibility,
+) -> Result<League, MyError> {
+    let (all, leagueids) = visibility.params();
+    query_one(
+        client,
+        sql!(
+            League,
+            "SELECT $table_fields FROM leagues WHERE id=$1 AND (NOT is_hidden OR $2 OR id=any($3));"
+        ),
+        &[&leagueid, &all, &leagueids],
+    )
+    .await
+}
+
+pub async fn add_league(
+    client: &impl GenericClient,
+    league: MiniLeague,
+) -> Result<League, MyError> {
+    let time_now = chrono::offset::Utc::now();
+
+    query_one(
+        client,
+        sql!(
+            League,
+            "INSERT INTO leagues(name, accepting_teams, is_hidden, created_at, seasonid) \
+            VALUES ($1, $2, $3, $4, $5) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &league.name,
+            &league.accepting_teams,
+            &league.is_hidden,
+            &time_now,
+            &league.seasonid,
+        ],
+    )
+    .await
+}
 
-    let results = client
-        .query(&stmt, &[&divisionid])
+/// Write the name, sign up and visibility flags, and archive time of `league`.
+pub async fn update_league(
+    client: &impl GenericClient,
+    league: &League,
+) -> Result<League, MyError> {
+    query_one(
+        client,
+        sql!(
+            League,
+            "UPDATE leagues SET name=$2, accepting_teams=$3, is_hidden=$4, archived_at=$5 \
+            WHERE id=$1 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &league.id,
+            &league.name,
+            &league.accepting_teams,
+            &league.is_hidden,
+            &league.archived_at,
+        ],
+    )
+    .await
+}
+
+/// How many games have been played in `leagueid`.
+pub async fn count_games_for_league_id(
+    client: &impl GenericClient,
+    leagueid: i64,
+) -> Result<i64, MyError> {
+    query_scalar(
+        client,
+        "SELECT COUNT(*) FROM games WHERE leagueid=$1",
+        &[&leagueid],
+    )
+    .await
+}
+
+/// How many rosters have signed up to, or asked to play in, any of `divisionids`.
+pub async fn count_rosters_for_div_ids(
+    client: &impl GenericClient,
+    divisionids: &[i64],
+) -> Result<i64, MyError> {
+    query_scalar(
+        client,
+        "SELECT COUNT(*) FROM teamDivAssociations \
+        WHERE divisionid=any($1) OR requested_divisionid=any($1)",
+        &[&divisionids],
+    )
+    .await
+}
+
+/// Delete every division in `divisionids`, along with their admins and rosters,
+/// and everyone's membership of those rosters.
+/// Rosters placed somewhere else forget they asked for one of these.
+pub async fn delete_divisions(
+    client: &impl GenericClient,
+    divisionids: &[i64],
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "UPDATE teamDivAssociations SET requested_divisionid=NULL \
+        WHERE requested_divisionid=any($1)",
+        &[&divisionids],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM team_invites WHERE teamdivid IN \
+        (SELECT id FROM teamDivAssociations WHERE divisionid=any($1))",
+        &[&divisionids],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM userTeamAssociation WHERE teamdivid IN \
+        (SELECT id FROM teamDivAssociations WHERE divisionid=any($1))",
+        &[&divisionids],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM teamDivAssociations WHERE divisionid=any($1)",
+        &[&divisionids],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM division_admins WHERE divisionid=any($1)",
+        &[&divisionids],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM divisions WHERE id=any($1)",
+        &[&divisionids],
+    )
+    .await
+}
+
+/// Delete league `leagueid`, and everything in it: divisions, rosters, games and join requests.
+pub async fn delete_league(client: &impl GenericClient, leagueid: i64) -> Result<(), MyError> {
+    let divids: Vec<i64> = get_divs_for_league_id(client, leagueid)
         .await?
         .iter()
-        .map(|row| TeamDivAssociation::from_row_ref(row).unwrap())
-        .collect::<Vec<TeamDivAssociation>>();
-    Ok(results)
+        .map(|div| div.id)
+        .collect();
+    delete_divisions(client, &divids).await?;
+    execute(client, "DELETE FROM games WHERE leagueid=$1", &[&leagueid]).await?;
+    execute(
+        client,
+        "DELETE FROM team_join_requests WHERE leagueid=$1",
+        &[&leagueid],
+    )
+    .await?;
+    match execute(client, "DELETE FROM leagues WHERE id=$1", &[&leagueid]).await? {
+        0 => Err(MyError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Every league in the season `seasonid` that `visibility` shows.
+pub async fn get_leagues_for_season_id(
+    client: &impl GenericClient,
+    seasonid: i64,
+    visibility: &Visibility,
+) -> Result<Vec<League>, MyError> {
+    let (all, leagueids) = visibility.params();
+    query_all(
+        client,
+        sql!(
+            League,
+            "SELECT $table_fields FROM leagues \
+            WHERE seasonid=$1 AND (NOT is_hidden OR $2 OR id=any($3)) \
+            ORDER BY id ASC"
+        ),
+        &[&seasonid, &all, &leagueids],
+    )
+    .await
+}
+
+/// Open or close sign ups to every league in the season `seasonid`.
+pub async fn set_accepting_teams_for_season_id(
+    client: &impl GenericClient,
+    seasonid: i64,
+    accepting_teams: bool,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "UPDATE leagues SET accepting_teams=$2 WHERE seasonid=$1",
+        &[&seasonid, &accepting_teams],
+    )
+    .await
+}
+
+/// Archive every league in the season `seasonid` that isn't already, which also stops their sign ups.
+pub async fn archive_leagues_for_season_id(
+    client: &impl GenericClient,
+    seasonid: i64,
+    archived_at: DateTime<Utc>,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "UPDATE leagues SET archived_at=COALESCE(archived_at, $2), accepting_teams=FALSE \
+        WHERE seasonid=$1",
+        &[&seasonid, &archived_at],
+    )
+    .await
+}
+
+/// Copy every division (name and priority, no teams or admins) of league `from` into league `to`.
+pub async fn copy_divisions(
+    client: &impl GenericClient,
+    from: i64,
+    to: i64,
+) -> Result<Vec<Division>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Division,
+            "INSERT INTO divisions(leagueid, name, prio, created_at) \
+            SELECT $2, name, prio, $3 FROM divisions WHERE leagueid=$1 ORDER BY id ASC \
+            RETURNING $table_fields"
+        ),
+        &[&from, &to, &chrono::offset::Utc::now()],
+    )
+    .await
 }
 
 pub async fn get_divs_for_league_id(
-    client: &Client,
+    client: &impl GenericClient,
     leagueid: i64,
 ) -> Result<Vec<Division>, MyError> {
-    let _stmt = "SELECT $table_fields FROM divisions WHERE leagueid=$1;";
-    let _stmt = _stmt.replace("$table_fields", &Division::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
+    query_all(
+        client,
+        sql!(
+            Division,
+            "SELECT $table_fields FROM divisions WHERE leagueid=$1;"
+        ),
+        &[&leagueid],
+    )
+    .await
+}
 
-    let results = client
-        .query(&stmt, &[&leagueid])
-        .await?
-        .iter()
-        .map(|row| Division::from_row_ref(row).unwrap())
-        .collect::<Vec<Division>>();
+/// Every division in any of `leagueids`.
+pub async fn get_divs_for_league_ids(
+    client: &impl GenericClient,
+    leagueids: &[i64],
+) -> Result<Vec<Division>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Division,
+            "SELECT $table_fields FROM divisions WHERE leagueid=any($1) ORDER BY id ASC"
+        ),
+        &[&leagueids],
+    )
+    .await
+}
 
-    Ok(results)
+pub async fn add_division(
+    client: &impl GenericClient,
+    division: MiniDivision,
+) -> Result<Division, MyError> {
+    query_one(
+        client,
+        sql!(
+            Division,
+            "INSERT INTO divisions(leagueid, name, prio, created_at) \
+            VALUES ($1, $2, 1, $3) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &division.leagueid,
+            &division.name,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
 }
 
-pub async fn get_leagues(client: &Client) -> Result<Vec<League>, MyError> {
-    let _stmt = "SELECT $table_fields FROM leagues;";
-    let _stmt = _stmt.replace("$table_fields", &League::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
+pub async fn get_division_from_id(
+    client: &impl GenericClient,
+    divisionid: i64,
+) -> Result<Division, MyError> {
+    query_one(
+        client,
+        sql!(Division, "SELECT $table_fields FROM divisions WHERE id=$1"),
+        &[&divisionid],
+    )
+    .await
+}
 
-    let results = client
-        .query(&stmt, &[])
-        .await?
-        .iter()
-        .map(|row| League::from_row_ref(row).unwrap())
-        .collect::<Vec<League>>();
-    Ok(results)
+/// Write the name and priority of `division`.
+pub async fn update_division(
+    client: &impl GenericClient,
+    division: &Division,
+) -> Result<Division, MyError> {
+    query_one(
+        client,
+        sql!(
+            Division,
+            "UPDATE divisions SET name=$2, prio=$3 WHERE id=$1 RETURNING $table_fields"
+        ),
+        &[&division.id, &division.name, &division.prio],
+    )
+    .await
 }
 
-pub async fn get_league_from_id(client: &Client, leagueid: i64) -> Result<League, MyError> {
-    log::debug!("Getting league {leagueid}");
-    let _stmt = "SELECT $table_fields FROM leagues WHERE id=$1;";
-    let _stmt = _stmt.replace("$table_fields", &League::sql_table_fields());
-    log::debug!("Running this statement: {0}", _stmt);
-    let stmt = client.prepare(&_stmt).await.unwrap();
+pub async fn get_admins_for_div_id(
+    client: &impl GenericClient,
+    divisionid: i64,
+) -> Result<Vec<DivisionAdmin>, MyError> {
+    query_all(
+        client,
+        sql!(
+            DivisionAdmin,
+            "SELECT $table_fields FROM division_admins WHERE divisionid=$1"
+        ),
+        &[&divisionid],
+    )
+    .await
+}
 
-    let results = client
-        .query(&stmt, &[&leagueid])
-        .await?
-        .iter()
-        .map(|row| League::from_row_ref(row).unwrap())
-        .collect::<Vec<League>>()
-        .pop()
-        .ok_or(MyError::NotFound)?;
+/// Every league where `userid` runs at least one division.
+pub async fn get_admin_league_ids_for_user_id(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<Vec<i64>, MyError> {
+    query_scalar(
+        client,
+        "SELECT COALESCE(array_agg(DISTINCT divisions.leagueid), '{}') \
+        FROM division_admins \
+        INNER JOIN divisions ON divisions.id = division_admins.divisionid \
+        WHERE division_admins.userid=$1",
+        &[&userid],
+    )
+    .await
+}
+
+/// A division admin joined with the user's name and avatar.
+#[derive(Deserialize, PostgresMapper)]
+#[pg_mapper(table = "division_admins")]
+struct DivisionAdminRow {
+    id: i64,
+    divisionid: i64,
+    userid: i64,
+    relation: String,
+    username: String,
+    avatarurl: String,
+}
+
+impl From<DivisionAdminRow> for WrappedDivisionAdmin {
+    fn from(row: DivisionAdminRow) -> Self {
+        WrappedDivisionAdmin {
+            inner: DivisionAdmin {
+                id: row.id,
+                divisionid: row.divisionid,
+                userid: row.userid,
+                relation: row.relation,
+            },
+            username: row.username,
+            avatarurl: row.avatarurl,
+        }
+    }
+}
+
+/// The admins of every division in `divisionids`, with their names and avatars.
+pub async fn get_admins_for_div_ids_wrapped(
+    client: &impl GenericClient,
+    divisionids: &[i64],
+) -> Result<Vec<WrappedDivisionAdmin>, MyError> {
+    let rows: Vec<DivisionAdminRow> = query_all(
+        client,
+        "SELECT division_admins.id, division_admins.divisionid, division_admins.userid, \
+        division_admins.relation, users.username, users.avatarurl \
+        FROM division_admins \
+        INNER JOIN users ON users.id = division_admins.userid \
+        WHERE division_admins.divisionid=any($1)",
+        &[&divisionids],
+    )
+    .await?;
+
+    Ok(rows.into_iter().map(WrappedDivisionAdmin::from).collect())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DivisionOptionalTeams {
+    pub info: Division,
+    pub admins: Vec<WrappedDivisionAdmin>,
+    pub teams: Option<Vec<DeepTeamDivAssociation>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LeagueReturn {
+    pub info: League,
+    pub divisions: Vec<DivisionOptionalTeams>,
+}
+
+/// Fill in the divisions and admins of every league in `leagues`, and their teams if `with_teams`.
+/// Private rosters are left out unless `visibility` shows them.
+///
+/// Runs the same number of queries however many leagues, divisions or teams there are.
+pub async fn get_league_trees(
+    client: &impl GenericClient,
+    leagues: Vec<League>,
+    with_teams: bool,
+    visibility: &Visibility,
+) -> Result<Vec<LeagueReturn>, MyError> {
+    let leagueids: Vec<i64> = leagues.iter().map(|league| league.id).collect();
+    let divs = get_divs_for_league_ids(client, &leagueids).await?;
+    let divids: Vec<i64> = divs.iter().map(|div| div.id).collect();
+
+    let mut admins: HashMap<i64, Vec<WrappedDivisionAdmin>> = HashMap::new();
+    for admin in get_admins_for_div_ids_wrapped(client, &divids).await? {
+        admins
+            .entry(admin.inner.divisionid)
+            .or_default()
+            .push(admin);
+    }
+
+    let mut teams: HashMap<i64, Vec<DeepTeamDivAssociation>> = HashMap::new();
+    if with_teams {
+        for team in get_teams_for_div_ids(client, &divids, visibility).await? {
+            teams
+                .entry(team.association_info.divisionid)
+                .or_default()
+                .push(team);
+        }
+    }
+
+    let mut divisions: HashMap<i64, Vec<DivisionOptionalTeams>> = HashMap::new();
+    for div in divs {
+        divisions
+            .entry(div.leagueid)
+            .or_default()
+            .push(DivisionOptionalTeams {
+                admins: admins.remove(&div.id).unwrap_or_default(),
+                teams: with_teams.then(|| teams.remove(&div.id).unwrap_or_default()),
+                info: div,
+            });
+    }
+
+    let trees = leagues
+        .into_iter()
+        .map(|league| LeagueReturn {
+            divisions: divisions.remove(&league.id).unwrap_or_default(),
+            info: league,
+        })
+        .collect();
+    Ok(trees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{count_queries, teams, test_db::TestDb, users};
+    use crate::models::{MiniTeam, MiniUser};
+
+    /// Make a league with `divs` divisions, each with `admins` admins and `teams` teams.
+    async fn seed_league(
+        client: &impl GenericClient,
+        name: &str,
+        divs: usize,
+        admins: usize,
+        team_count: usize,
+    ) -> League {
+        let league = add_league(
+            client,
+            MiniLeague {
+                name: name.to_owned(),
+                accepting_teams: true,
+                is_hidden: false,
+                seasonid: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        for d in 0..divs {
+            let div = add_division(
+                client,
+                MiniDivision {
+                    leagueid: league.id,
+                    name: format!("div {d}"),
+                },
+            )
+            .await
+            .unwrap();
+
+            for a in 0..admins.max(team_count) {
+                let user = users::add_user(
+                    client,
+                    MiniUser {
+                        steamid: format!("test-{0}-{d}-{a}", league.id),
+                        permissions: None,
+                        avatarurl: String::new(),
+                        username: format!("user {a}"),
+                    },
+                )
+                .await
+                .unwrap();
+
+                if a < admins {
+                    client
+                        .execute(
+                            "INSERT INTO division_admins(divisionid, userid) VALUES ($1, $2)",
+                            &[&div.id, &user.id],
+                        )
+                        .await
+                        .unwrap();
+                }
+                if a < team_count {
+                    let team = teams::add_team(
+                        client,
+                        &MiniTeam {
+                            owner_id: user.id,
+                            team_name: format!("team {a}"),
+                            team_tag: format!("T{a}"),
+                        },
+                    )
+                    .await
+                    .unwrap();
+                    teams::add_team_div_assoc(
+                        client,
+                        teams::MiniTeamDivAssociation {
+                            roster_name: None,
+                            teamid: team.id,
+                            divisionid: div.id,
+                            is_private: false,
+                            requested_divisionid: None,
+                        },
+                    )
+                    .await
+                    .unwrap();
+                }
+            }
+        }
+        league
+    }
+
+    #[tokio::test]
+    async fn league_trees_take_constant_queries() {
+        let Some(db) = TestDb::new().await else {
+            return;
+        };
+        let mut client = db.pool.get().await.unwrap();
+        let transaction = client.transaction().await.unwrap();
+
+        let small = seed_league(&transaction, "small", 1, 1, 1).await;
+        let big = seed_league(&transaction, "big", 4, 3, 5).await;
+
+        let (small_tree, small_queries) = count_queries(get_league_trees(
+            &transaction,
+            vec![small],
+            true,
+            &Visibility::All,
+        ))
+        .await;
+        let (big_tree, big_queries) = count_queries(get_league_trees(
+            &transaction,
+            vec![big],
+            true,
+            &Visibility::All,
+        ))
+        .await;
+        assert_eq!(small_queries, 4);
+        assert_eq!(big_queries, 4);
+
+        let small_tree = &small_tree.unwrap()[0];
+        assert_eq!(small_tree.divisions.len(), 1);
+        let big_tree = &big_tree.unwrap()[0];
+        assert_eq!(big_tree.divisions.len(), 4);
+        for div in &big_tree.divisions {
+            assert_eq!(div.admins.len(), 3);
+            assert!(div
+                .admins
+                .iter()
+                .all(|admin| admin.inner.divisionid == div.info.id));
+            let teams = div.teams.as_ref().unwrap();
+            assert_eq!(teams.len(), 5);
+            assert!(teams
+                .iter()
+                .all(|team| team.association_info.divisionid == div.info.id
+                    && team.association_info.teamid == team.team_info.id));
+        }
 
-    Ok(results)
+        // The league list doesn't include teams, so it's one query less.
+        let (all, all_queries) = count_queries(async {
+            let leagues = get_leagues(&transaction, &Visibility::Public).await?;
+            get_league_trees(&transaction, leagues, false, &Visibility::Public).await
+        })
+        .await;
+        assert_eq!(all_queries, 1 + 2);
+        assert!(all
+            .unwrap()
+            .iter()
+            .all(|tree| tree.divisions.iter().all(|div| div.teams.is_none())));
+    }
 }
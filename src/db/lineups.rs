@@ -0,0 +1,169 @@
+// Who played in each game, and the mercenaries teams asked to play.
+use deadpool_postgres::GenericClient;
+
+use super::{execute, query_all, query_one, query_scalar, teams};
+use crate::{
+    errors::MyError,
+    models::{Game, LineupEntry, MercenaryRequest, MercenaryStatus},
+};
+
+/// Both lineups of game `gameid`, grouped by team.
+pub async fn get_lineup_for_game_id(
+    client: &impl GenericClient,
+    gameid: i64,
+) -> Result<Vec<LineupEntry>, MyError> {
+    query_all(
+        client,
+        sql!(
+            LineupEntry,
+            "SELECT $table_fields FROM game_lineups WHERE gameid=$1 ORDER BY teamid, id"
+        ),
+        &[&gameid],
+    )
+    .await
+}
+
+/// Who played for team `teamid` in `game`: its lineup, or if it never declared one,
+/// everyone on its rosters in the league when the game was played.
+pub async fn get_player_ids_for_game(
+    client: &impl GenericClient,
+    game: &Game,
+    teamid: i64,
+) -> Result<Vec<i64>, MyError> {
+    let lineup: Vec<i64> = query_scalar(
+        client,
+        "SELECT COALESCE(array_agg(userid), '{}') FROM game_lineups \
+        WHERE gameid=$1 AND teamid=$2",
+        &[&game.id, &teamid],
+    )
+    .await?;
+    if !lineup.is_empty() {
+        return Ok(lineup);
+    }
+    teams::get_player_ids_in_league_at(client, teamid, game.leagueid, game.played_at).await
+}
+
+/// Replace team `teamid`'s lineup for game `gameid` with `userids`.
+/// Errors with [MyError::Conflict] if any of them is already in the game's other lineup,
+/// e.g. because the other team declared them at the same time.
+pub async fn set_lineup(
+    client: &impl GenericClient,
+    gameid: i64,
+    teamid: i64,
+    userids: &[i64],
+) -> Result<Vec<LineupEntry>, MyError> {
+    execute(
+        client,
+        "DELETE FROM game_lineups WHERE gameid=$1 AND teamid=$2",
+        &[&gameid, &teamid],
+    )
+    .await?;
+    let lineup: Vec<LineupEntry> = query_all(
+        client,
+        sql!(
+            LineupEntry,
+            "INSERT INTO game_lineups(gameid, teamid, userid, created_at) \
+            SELECT $1, $2, userid, $4 FROM unnest($3::BIGINT[]) WITH ORDINALITY AS u(userid, n) \
+            ORDER BY n \
+            ON CONFLICT (gameid, userid) DO NOTHING \
+            RETURNING $table_fields"
+        ),
+        &[&gameid, &teamid, &userids, &chrono::offset::Utc::now()],
+    )
+    .await?;
+    if lineup.len() < userids.len() {
+        return Err(MyError::Conflict(
+            "Someone in this lineup is already in the other team's lineup".to_owned(),
+        ));
+    }
+    Ok(lineup)
+}
+
+/// Every mercenary request for game `gameid`, oldest first.
+pub async fn get_mercenary_requests_for_game_id(
+    client: &impl GenericClient,
+    gameid: i64,
+) -> Result<Vec<MercenaryRequest>, MyError> {
+    query_all(
+        client,
+        sql!(
+            MercenaryRequest,
+            "SELECT $table_fields FROM mercenary_requests WHERE gameid=$1 \
+            ORDER BY created_at ASC, id ASC"
+        ),
+        &[&gameid],
+    )
+    .await
+}
+
+pub async fn get_mercenary_request_from_id(
+    client: &impl GenericClient,
+    requestid: i64,
+) -> Result<MercenaryRequest, MyError> {
+    query_one(
+        client,
+        sql!(
+            MercenaryRequest,
+            "SELECT $table_fields FROM mercenary_requests WHERE id=$1"
+        ),
+        &[&requestid],
+    )
+    .await
+}
+
+pub async fn add_mercenary_request(
+    client: &impl GenericClient,
+    gameid: i64,
+    teamid: i64,
+    userid: i64,
+    requested_by: i64,
+    note: Option<&str>,
+) -> Result<MercenaryRequest, MyError> {
+    query_one(
+        client,
+        sql!(
+            MercenaryRequest,
+            "INSERT INTO mercenary_requests(gameid, teamid, userid, requested_by, note, \
+            status, created_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &gameid,
+            &teamid,
+            &userid,
+            &requested_by,
+            &note,
+            &MercenaryStatus::Pending.as_str(),
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+/// Approve or deny pending request `requestid`.
+/// Errors with [MyError::NotFound] if it doesn't exist or was already reviewed.
+pub async fn review_mercenary_request(
+    client: &impl GenericClient,
+    requestid: i64,
+    status: MercenaryStatus,
+    reviewed_by: i64,
+) -> Result<MercenaryRequest, MyError> {
+    query_one(
+        client,
+        sql!(
+            MercenaryRequest,
+            "UPDATE mercenary_requests SET status=$2, reviewed_by=$3, reviewed_at=$4 \
+            WHERE id=$1 AND status=$5 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &requestid,
+            &status.as_str(),
+            &reviewed_by,
+            &chrono::offset::Utc::now(),
+            &MercenaryStatus::Pending.as_str(),
+        ],
+    )
+    .await
+}
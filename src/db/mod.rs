@@ -1,477 +1,223 @@
 // Code that acts as a wrapper for database values.
-use chrono::{DateTime, Utc};
-use deadpool_postgres::Client;
-use divisions::DeepTeamDivAssociation;
-use serde::{Deserialize, Serialize};
+//
+// Every query goes through the helpers below, which cache prepared statements
+// per connection and turn rows into models without panicking.
+// Queries are grouped by aggregate: `users`, `teams`, `leagues`, `seasons`, `games`, `lineups`, `stats` and `ratings`.
+//
+// Everything takes a `GenericClient`, so a pooled client and a transaction work the same.
+// Flows that write more than once should open a transaction with `client.transaction()`,
+// pass `&transaction` to each call and `commit()` at the end;
+// dropping it early (e.g. on `?`) rolls everything back.
+use std::cell::Cell;
+use std::future::Future;
+
+use deadpool_postgres::GenericClient;
 use tokio_pg_mapper::FromTokioPostgresRow;
+use tokio_postgres::types::{FromSql, ToSql};
+
+use crate::errors::MyError;
+
+/// Build a query from a template once and reuse it for every call after,
+/// replacing `$table_fields` with the (table qualified) fields of `$model`.
+///
+/// `sql!(User, "SELECT $table_fields FROM users WHERE id=$1")`
+macro_rules! sql {
+    ($model:ty, $template:expr) => {{
+        static SQL: ::std::sync::OnceLock<String> = ::std::sync::OnceLock::new();
+        SQL.get_or_init(|| {
+            $template.replace(
+                "$table_fields",
+                &<$model as ::tokio_pg_mapper::FromTokioPostgresRow>::sql_table_fields(),
+            )
+        })
+        .as_str()
+    }};
+}
 
-use crate::{
-    authorization::create_authorization_for_user,
-    errors::MyError,
-    models::{
-        Authorization, League, MiniLeague, MiniTeam, MiniUser, Team, TeamDivAssociation, User,
-        UserTeam,
-    },
-    permission::UserPermission,
-};
-
-pub mod divisions;
+pub mod demos;
+pub mod games;
 pub mod leagues;
-pub mod team_div_assocs;
+pub mod lineups;
+pub mod ratings;
+pub mod seasons;
+pub mod stats;
 pub mod teams;
+#[cfg(test)]
+pub(crate) mod test_db;
+pub mod tickets;
+pub mod users;
 
-pub async fn add_test_data(client: &Client) -> Result<(), MyError> {
-    let _stmt = include_str!("../../sql/test_data.sql");
-
-    client.batch_execute(_stmt).await?;
-    Ok(())
-}
-pub async fn initdb(client: &Client) -> Result<(), MyError> {
-    let _stmt = include_str!("../../sql/initdb.sql");
-
-    client.batch_execute(_stmt).await?;
-    Ok(())
-}
-
-pub async fn revoke_user_authorization(client: &Client, user: &User) -> Result<u64, MyError> {
-    let stmt = client
-        .prepare("DELETE FROM authorizations WHERE userid=$1;")
-        .await
-        .unwrap();
-
-    client
-        .execute(&stmt, &[&user.id])
-        .await
-        .map_err(MyError::PGError)
-}
-
-pub async fn get_team_from_id(client: &Client, team_id: i64) -> Result<Team, MyError> {
-    let _stmt = "SELECT $table_fields FROM teams WHERE id=$1";
-    let _stmt = _stmt.replace("$table_fields", &Team::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let results = client
-        .query(&stmt, &[&team_id])
-        .await?
-        .iter()
-        .map(|row| Team::from_row_ref(row).unwrap())
-        .collect::<Vec<Team>>()
-        .pop()
-        .ok_or(MyError::NotFound);
-
-    results
-}
-
-pub async fn get_teamdivassociation_from_id(
-    client: &Client,
-    assoc_id: i64,
-) -> Result<TeamDivAssociation, MyError> {
-    let _stmt = "SELECT $table_fields FROM teamDivAssociations WHERE id=$1";
-    let _stmt = _stmt.replace("$table_fields", &TeamDivAssociation::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let results = client
-        .query(&stmt, &[&assoc_id])
-        .await?
-        .iter()
-        .map(|row| TeamDivAssociation::from_row_ref(row).unwrap())
-        .collect::<Vec<TeamDivAssociation>>()
-        .pop()
-        .ok_or(MyError::NotFound);
-
-    results
-}
-
-pub async fn add_team(client: &Client, team: &MiniTeam) -> Result<Team, MyError> {
-    let _stmt = "INSERT INTO teams(team_tag, team_name, created_at, owner_id)
-    VALUES
-    ($1, $2, $3, $4)
-    RETURNING 
-    $table_fields";
-    let _stmt = _stmt.replace("$table_fields", &Team::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
+type Params<'a> = &'a [&'a (dyn ToSql + Sync)];
 
-    let time_now = chrono::offset::Utc::now();
-
-    client
-        .query(
-            &stmt,
-            &[&team.team_tag, &team.team_name, &time_now, &team.owner_id],
-        )
-        .await?
-        .iter()
-        .map(|row| Team::from_row_ref(row).unwrap())
-        .collect::<Vec<Team>>()
-        .pop()
-        .ok_or(MyError::NotFound)
+tokio::task_local! {
+    static QUERY_COUNT: Cell<usize>;
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct UserAndAssoc {
-    pub user: User,
-    pub assoc: UserTeam,
-}
-
-pub async fn get_team_players(
-    client: &Client,
-    team: &TeamDivAssociation,
-) -> Result<Vec<UserAndAssoc>, MyError> {
-    let _stmt = "SELECT $table_fields FROM userTeamAssociation WHERE teamdivid=$1";
-    let _stmt = _stmt.replace("$table_fields", &UserTeam::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let userassocs: Vec<UserTeam> = client
-        .query(&stmt, &[&team.id])
-        .await?
-        .iter()
-        .map(|row| UserTeam::from_row_ref(row).unwrap())
-        .collect();
-
-    let userids: Vec<i64> = (&userassocs).iter().map(|item| item.userid).collect();
-    let users = mass_get_user_from_internal_id(client, &userids).await?;
-
-    let mut out: Vec<UserAndAssoc> = Vec::with_capacity(users.len());
-
-    let mut assoc_iter = userassocs.into_iter();
-    for user in users {
-        out.push(UserAndAssoc {
-            user,
-            assoc: assoc_iter
-                .next()
-                .expect("there should not be more users than associations"),
+/// Run `future`, counting how many queries it sends through the helpers below.
+///
+/// Used to check read paths run a fixed number of queries, however much data there is.
+pub async fn count_queries<F: Future>(future: F) -> (F::Output, usize) {
+    QUERY_COUNT
+        .scope(Cell::new(0), async {
+            let output = future.await;
+            (output, QUERY_COUNT.with(Cell::get))
         })
-    }
-    Ok(out)
-}
-
-pub async fn get_user_from_internal_id(client: &Client, userid: i64) -> Result<User, MyError> {
-    let _stmt = "SELECT $table_fields FROM users WHERE id=$1";
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let row = client
-        .query_one(&stmt, &[&userid])
         .await
-        .map_err(|_| MyError::NotFound)?;
-    Ok(User::from_row_ref(&row).unwrap())
 }
 
-pub async fn mass_get_user_from_internal_id(
-    client: &Client,
-    userids: &Vec<i64>,
-) -> Result<Vec<User>, MyError> {
-    let _stmt = "SELECT $table_fields FROM users WHERE id=any($1)";
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let users: Vec<User> = client
-        .query(&stmt, &[&userids])
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect();
-
-    Ok(users)
-}
-
-// pub async fn add_team(client: &Client, league: League, team: &MiniTeam) -> Result<Team, MyError> {
-//     let _stmt = "INSERT INTO teams(leagueid, teamname)"
-// }
-
-pub async fn add_league(client: &Client, league: MiniLeague) -> Result<League, MyError> {
-    let _stmt = "INSERT INTO leagues(name, accepting_teams, is_hidden, created_at) VALUES ($1, $2, $3, $4) RETURNING $table_fields";
-    let _stmt = _stmt.replace("$table_fields", &League::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let time_now = chrono::offset::Utc::now();
-
-    let results = client
-        .query(
-            &stmt,
-            &[
-                &league.name,
-                &league.accepting_teams,
-                &league.is_hidden,
-                &time_now,
-            ],
-        )
-        .await?
-        .iter()
-        .map(|row| League::from_row_ref(row).unwrap())
-        .collect::<Vec<League>>()
-        .pop()
-        .unwrap();
-    Ok(results)
+fn count_query() {
+    // Outside of `count_queries` there's nothing to count into.
+    let _ = QUERY_COUNT.try_with(|count| count.set(count.get() + 1));
 }
-pub async fn get_user_from_auth_token(client: &Client, token: &str) -> Result<User, MyError> {
-    let _stmt = include_str!("../../sql/get_user_from_authtoken.sql");
-    let stmt = client.prepare(_stmt).await.unwrap();
 
-    let received_auth = client
-        .query(&stmt, &[&token])
-        .await?
-        .iter()
-        .map(|row| Authorization::from_row_ref(row).unwrap())
-        .collect::<Vec<Authorization>>()
-        .pop()
-        .ok_or(MyError::NotFound)?;
-
-    let _stmt = "SELECT $table_fields FROM users WHERE id=$1;";
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
+/// Run `sql` and map every returned row to a `T`.
+pub(crate) async fn query_all<T: FromTokioPostgresRow>(
+    client: &impl GenericClient,
+    sql: &str,
+    params: Params<'_>,
+) -> Result<Vec<T>, MyError> {
+    let stmt = client.prepare_cached(sql).await?;
+    count_query();
 
     client
-        .query(&stmt, &[&received_auth.userid])
+        .query(&stmt, params)
         .await?
         .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect::<Vec<User>>()
-        .pop()
-        .ok_or(MyError::NotFound)
+        .map(|row| T::from_row_ref(row).map_err(MyError::from))
+        .collect()
 }
-pub async fn get_user_from_steamid(client: &Client, steamid: &str) -> Result<User, MyError> {
-    let _stmt = include_str!("../../sql/get_user_from_steamid.sql");
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
 
-    client
-        .query(&stmt, &[&steamid])
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect::<Vec<User>>()
-        .pop()
-        .ok_or(MyError::NotFound)
-}
-
-pub async fn get_authorization_for_user(
-    client: &Client,
-    user: &User,
-) -> Result<Authorization, MyError> {
-    let _stmt = include_str!("../../sql/get_auth_token.sql");
-    let _stmt = _stmt.replace("$fields", &Authorization::sql_fields());
-    let stmt = client.prepare(&_stmt).await?;
+/// Run `sql`, which should return at most one row, and map it to a `T` if it did.
+pub(crate) async fn query_opt<T: FromTokioPostgresRow>(
+    client: &impl GenericClient,
+    sql: &str,
+    params: Params<'_>,
+) -> Result<Option<T>, MyError> {
+    let stmt = client.prepare_cached(sql).await?;
+    count_query();
 
-    if let Ok(some) = client
-        .query(&stmt, &[&user.id])
-        .await?
-        .iter()
-        .map(|row| Authorization::from_row_ref(row).unwrap())
-        .collect::<Vec<Authorization>>()
-        .pop()
-        .ok_or(MyError::NotFound)
-    {
-        Ok(some)
-    } else {
-        create_authorization_for_user(client, user).await
+    match client.query_opt(&stmt, params).await? {
+        Some(row) => Ok(Some(T::from_row(row)?)),
+        None => Ok(None),
     }
 }
 
-pub async fn register_authorization(
-    client: &Client,
-    token: &str,
-    user: &User,
-    expiry: DateTime<Utc>,
-) -> Result<Authorization, MyError> {
-    let _stmt = include_str!("../../sql/register_auth_token.sql");
-    // $table_fields didn't work with this for some reason when i tested it
-    let _stmt = _stmt.replace("$fields", &Authorization::sql_fields());
-    log::debug!("Registering authorization {token} for {0}", &user.id);
-    let stmt = client.prepare(&_stmt).await?;
-    let time_now = chrono::offset::Utc::now();
-
-    client
-        .query(&stmt, &[&user.id, &token, &time_now, &expiry])
+/// Run `sql`, which should return exactly one row, and map it to a `T`.
+/// Errors with [MyError::NotFound] if there was no row.
+pub(crate) async fn query_one<T: FromTokioPostgresRow>(
+    client: &impl GenericClient,
+    sql: &str,
+    params: Params<'_>,
+) -> Result<T, MyError> {
+    query_opt(client, sql, params)
         .await?
-        .iter()
-        .map(|row| Authorization::from_row_ref(row).unwrap())
-        .collect::<Vec<Authorization>>()
-        .pop()
         .ok_or(MyError::NotFound)
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct SuperDeepTeamDivAssociation {
-    pub user: UserTeam,
-    pub team: DeepTeamDivAssociation,
-}
-pub async fn get_rosters_for_user_id(
-    client: &Client,
-    userid: i64,
-) -> Result<Vec<SuperDeepTeamDivAssociation>, MyError> {
-    let sql_string = "SELECT 
-        $tda, $t, $u
-        FROM userTeamAssociation
-            INNER JOIN teamDivAssociations 
-                ON userTeamAssociation.teamdivid = teamDivAssociations.id
-            INNER JOIN teams
-                ON teamDivAssociations.teamid = teams.id
-        WHERE userTeamAssociation.userid=$1
-    ";
-    let sql_string = sql_string
-        .replace("$tda", &TeamDivAssociation::sql_table_fields())
-        .replace("$t", &Team::sql_table_fields())
-        .replace("$u", &UserTeam::sql_table_fields());
-    let stmt = client.prepare(&sql_string).await.unwrap();
-
-    let rows = client.query(&stmt, &[&userid]).await?;
-
-    let mut resp: Vec<SuperDeepTeamDivAssociation> = Vec::with_capacity(rows.len());
+/// Run `sql`, which should return exactly one row, and get its first column.
+pub(crate) async fn query_scalar<V: for<'a> FromSql<'a>>(
+    client: &impl GenericClient,
+    sql: &str,
+    params: Params<'_>,
+) -> Result<V, MyError> {
+    let stmt = client.prepare_cached(sql).await?;
+    count_query();
 
-    for row in rows.into_iter() {
-        resp.push(SuperDeepTeamDivAssociation {
-            user: UserTeam::from_row_ref(&row).unwrap(),
-            team: DeepTeamDivAssociation {
-                association_info: TeamDivAssociation::from_row_ref(&row).unwrap(),
-                team_info: Team::from_row_ref(&row).unwrap(),
-            },
-        })
-    }
-
-    Ok(resp)
-}
-pub async fn get_ownerships_for_user_id(
-    client: &Client,
-    userid: i64,
-) -> Result<Vec<Team>, MyError> {
-    let sql_string = "SELECT 
-        $table_fields
-    FROM 
-        teams
-    WHERE
-        owner_id=$1
-    ";
-    let sql_string = sql_string.replace("$table_fields", &Team::sql_table_fields());
-    let stmt = client.prepare(&sql_string).await.unwrap();
-
-    let results = client
-        .query(&stmt, &[&userid])
-        .await?
-        .iter()
-        .map(|row| Team::from_row_ref(row).unwrap())
-        .collect();
-    Ok(results)
-}
-
-pub async fn get_users(client: &Client) -> Result<Vec<User>, MyError> {
-    let sql_string = include_str!("../../sql/get_users.sql");
-    let sql_string = sql_string.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&sql_string).await.unwrap();
-
-    let results = client
-        .query(&stmt, &[])
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect::<Vec<User>>();
-    Ok(results)
+    let row = client.query_one(&stmt, params).await?;
+    Ok(row.try_get(0)?)
 }
 
-pub async fn set_user_permissions(
-    client: &Client,
-    user: &User,
-    permissions: i64,
-) -> Result<User, MyError> {
-    let _stmt = "UPDATE users SET permissions=$1 WHERE id=$2 RETURNING $table_fields";
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let resp = client
-        .query(&stmt, &[&permissions, &user.id])
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect::<Vec<User>>()
-        .pop()
-        .ok_or(MyError::NotFound);
-
-    resp
-}
+/// Run `sql`, returning how many rows it changed.
+pub(crate) async fn execute(
+    client: &impl GenericClient,
+    sql: &str,
+    params: Params<'_>,
+) -> Result<u64, MyError> {
+    let stmt = client.prepare_cached(sql).await?;
+    count_query();
 
-pub async fn set_super_user(client: &Client, user: &User) -> Result<User, MyError> {
-    set_user_permissions(client, user, UserPermission::Admin.bits()).await
+    Ok(client.execute(&stmt, params).await?)
 }
 
-pub async fn add_user(client: &Client, user_info: MiniUser) -> Result<User, MyError> {
-    let _stmt = include_str!("../../sql/add_user.sql");
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let date = chrono::offset::Utc::now();
+pub async fn add_test_data(client: &impl GenericClient) -> Result<(), MyError> {
+    let _stmt = include_str!("../../sql/test_data.sql");
 
-    let resp = client
-        .query(
-            &stmt,
-            &[
-                &user_info.steamid,
-                &user_info.username,
-                &user_info.avatarurl,
-                &date,
-            ],
-        )
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect::<Vec<User>>()
-        .pop()
-        .ok_or(MyError::NotFound); // more applicable for SELECTs
-    resp
+    client.batch_execute(_stmt).await?;
+    Ok(())
 }
+pub async fn initdb(client: &impl GenericClient) -> Result<(), MyError> {
+    let _stmt = include_str!("../../sql/initdb.sql");
 
-pub async fn get_user_count(client: &Client) -> Result<i64, MyError> {
-    let stmt = client.prepare("SELECT COUNT(*) FROM users").await.unwrap();
-
-    let resp: i64 = client.query_one(&stmt, &[]).await?.get(0);
-
-    Ok(resp)
+    client.batch_execute(_stmt).await?;
+    Ok(())
 }
-
-pub async fn get_user_page(
-    client: &Client,
-    page: u32,
-    amount: std::num::NonZero<u32>,
-) -> Result<Vec<User>, MyError> {
-    log::trace!("Getting page {page} amount {amount}");
-    let _stmt = include_str!("../../sql/get_users_paged.sql");
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let amount: u32 = amount.into();
-    let amount: i64 = amount.into();
-    let page: i64 = page.into();
-    let offset: i64 = page * amount;
-
-    let resp = client
-        .query(&stmt, &[&offset, &amount])
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect();
-    Ok(resp)
+/// Bring a database made by an older version up to the current schema.
+///
+/// Makes any tables that are missing, then adds the columns added to existing ones since
+/// and remakes the views. Safe to run on every startup, and on an empty database.
+pub async fn migrate(client: &impl GenericClient) -> Result<(), MyError> {
+    initdb(client).await?;
+    client
+        .batch_execute(include_str!("../../sql/migrate.sql"))
+        .await?;
+    Ok(())
 }
 
-pub async fn search_usernames(
-    client: &Client,
-    search_term: &str,
-    page: u32,
-    amount: std::num::NonZero<u32>,
-) -> Result<Vec<User>, MyError> {
-    log::trace!("Searching DB with term {search_term}");
-
-    let amount: u32 = amount.into();
-    let amount: i64 = amount.into();
-    let page: i64 = page.into();
-    let offset: i64 = page * amount;
-
-    let _stmt = include_str!("../../sql/fuzzy_search.sql");
-    let _stmt = _stmt.replace("$table_fields", &User::sql_table_fields());
-    let stmt = client.prepare(&_stmt).await.unwrap();
-
-    let resp = client
-        .query(&stmt, &[&search_term, &amount, &offset])
-        .await?
-        .iter()
-        .map(|row| User::from_row_ref(row).unwrap())
-        .collect();
-    Ok(resp)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_db::TestDb;
+
+    #[tokio::test]
+    async fn migrate_upgrades_old_databases() {
+        let Some(db) = TestDb::new().await else {
+            return;
+        };
+        let client = db.pool.get().await.unwrap();
+        // Roughly what a database made before seasons, placements and bans looks like.
+        client
+            .batch_execute(
+                "DROP TABLE user_bans;
+                ALTER TABLE users DROP COLUMN rating;
+                ALTER TABLE leagues DROP COLUMN seasonid, DROP COLUMN archived_at;
+                ALTER TABLE teamDivAssociations DROP COLUMN placement_status, DROP COLUMN placed_by;
+                ALTER TABLE games DROP COLUMN confirmed_at, DROP COLUMN time_agreed_at;
+                ALTER TABLE username_history ALTER COLUMN userid SET DEFAULT 1;",
+            )
+            .await
+            .unwrap();
+
+        migrate(&client).await.unwrap();
+        migrate(&client).await.unwrap();
+
+        client
+            .batch_execute(
+                "SELECT rating FROM users;
+                SELECT seasonid, archived_at FROM leagues;
+                SELECT placement_status, placed_by FROM teamDivAssociations;
+                SELECT confirmed_at, time_agreed_at FROM games;
+                SELECT leagueid FROM user_bans;
+                SELECT divisionid FROM player_division_stats;",
+            )
+            .await
+            .unwrap();
+        let placed: Vec<String> = client
+            .query("SELECT placement_status FROM teamDivAssociations", &[])
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert!(placed.iter().all(|status| status == "placed"));
+        let userid_default: Option<String> = client
+            .query_one(
+                "SELECT column_default::TEXT FROM information_schema.columns
+                WHERE table_name = 'username_history' AND column_name = 'userid'",
+                &[],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(userid_default, None);
+    }
 }
@@ -0,0 +1,222 @@
+// Player and team ratings, and their history. The maths is in [crate::ratings].
+use deadpool_postgres::GenericClient;
+
+use super::{execute, lineups, query_all, teams::lock_teams};
+use crate::{
+    errors::MyError,
+    models::{Game, RatingHistory, User},
+    ratings::{self, Outcome},
+};
+
+/// Every change to user `userid`'s rating, newest first.
+pub async fn get_rating_history_for_user_id(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<Vec<RatingHistory>, MyError> {
+    query_all(
+        client,
+        sql!(
+            RatingHistory,
+            "SELECT $table_fields FROM rating_history WHERE userid=$1 \
+            ORDER BY created_at DESC, id DESC"
+        ),
+        &[&userid],
+    )
+    .await
+}
+
+/// Every change to team `teamid`'s rating, newest first.
+pub async fn get_rating_history_for_team_id(
+    client: &impl GenericClient,
+    teamid: i64,
+) -> Result<Vec<RatingHistory>, MyError> {
+    query_all(
+        client,
+        sql!(
+            RatingHistory,
+            "SELECT $table_fields FROM rating_history WHERE teamid=$1 \
+            ORDER BY created_at DESC, id DESC"
+        ),
+        &[&teamid],
+    )
+    .await
+}
+
+async fn set_user_rating(
+    client: &impl GenericClient,
+    userid: i64,
+    gameid: i64,
+    before: f64,
+    after: f64,
+) -> Result<(), MyError> {
+    execute(
+        client,
+        "UPDATE users SET rating=$2 WHERE id=$1",
+        &[&userid, &after],
+    )
+    .await?;
+    execute(
+        client,
+        "INSERT INTO rating_history(userid, gameid, rating_before, rating_after, created_at) \
+        VALUES ($1, $2, $3, $4, $5)",
+        &[
+            &userid,
+            &gameid,
+            &before,
+            &after,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+async fn set_team_rating(
+    client: &impl GenericClient,
+    teamid: i64,
+    gameid: i64,
+    before: f64,
+    after: f64,
+) -> Result<(), MyError> {
+    execute(
+        client,
+        "UPDATE teams SET rating=$2 WHERE id=$1",
+        &[&teamid, &after],
+    )
+    .await?;
+    execute(
+        client,
+        "INSERT INTO rating_history(teamid, gameid, rating_before, rating_after, created_at) \
+        VALUES ($1, $2, $3, $4, $5)",
+        &[
+            &teamid,
+            &gameid,
+            &before,
+            &after,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Users `userids`, locked like [lock_teams] so nothing else rates them meanwhile.
+///
+/// Rating a game locks its teams before its players, each in id order,
+/// so rating two games with someone in common at once waits instead of deadlocking.
+async fn lock_users(client: &impl GenericClient, userids: &[i64]) -> Result<Vec<User>, MyError> {
+    query_all(
+        client,
+        sql!(
+            User,
+            "SELECT $table_fields FROM users WHERE id=any($1) ORDER BY id FOR UPDATE"
+        ),
+        &[&userids],
+    )
+    .await
+}
+
+/// Take back what rating `game` did to its teams' and players' ratings, and forget it happened,
+/// so a new result for it can be rated. Games rated since keep the changes they made.
+pub async fn reverse_game_result(client: &impl GenericClient, game: &Game) -> Result<(), MyError> {
+    lock_teams(client, &[game.teamhomeid, game.teamawayid]).await?;
+    let history: Vec<RatingHistory> = query_all(
+        client,
+        sql!(
+            RatingHistory,
+            "SELECT $table_fields FROM rating_history WHERE gameid=$1"
+        ),
+        &[&game.id],
+    )
+    .await?;
+    let userids: Vec<i64> = history.iter().filter_map(|change| change.userid).collect();
+    lock_users(client, &userids).await?;
+
+    execute(
+        client,
+        "UPDATE teams SET rating=teams.rating-(rating_history.rating_after-rating_history.rating_before) \
+        FROM rating_history WHERE rating_history.teamid=teams.id AND rating_history.gameid=$1",
+        &[&game.id],
+    )
+    .await?;
+    execute(
+        client,
+        "UPDATE users SET rating=users.rating-(rating_history.rating_after-rating_history.rating_before) \
+        FROM rating_history WHERE rating_history.userid=users.id AND rating_history.gameid=$1",
+        &[&game.id],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM rating_history WHERE gameid=$1",
+        &[&game.id],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Update the ratings of both teams in a confirmed `game`, and of everyone who played for them:
+/// their lineups, or their rosters when the game was played if they never declared one.
+///
+/// Every new rating is worked out from the ratings from before the game.
+pub async fn apply_game_result(client: &impl GenericClient, game: &Game) -> Result<(), MyError> {
+    let (Some(home_score), Some(away_score)) = (game.home_score, game.away_score) else {
+        return Ok(());
+    };
+    let home_outcome = Outcome::from_scores(home_score, away_score);
+    let away_outcome = Outcome::from_scores(away_score, home_score);
+
+    let teams = lock_teams(client, &[game.teamhomeid, game.teamawayid]).await?;
+    let team = |teamid| {
+        teams
+            .iter()
+            .find(|team| team.id == teamid)
+            .ok_or(MyError::NotFound)
+    };
+    let (home, away) = (team(game.teamhomeid)?, team(game.teamawayid)?);
+    let home_ids = lineups::get_player_ids_for_game(client, game, home.id).await?;
+    let away_ids = lineups::get_player_ids_for_game(client, game, away.id).await?;
+    let (home_players, away_players): (Vec<User>, Vec<User>) =
+        lock_users(client, &[home_ids.as_slice(), &away_ids].concat())
+            .await?
+            .into_iter()
+            .partition(|player| home_ids.contains(&player.id));
+
+    let home_average = ratings::average(
+        &home_players.iter().map(|p| p.rating).collect::<Vec<_>>(),
+        home.rating,
+    );
+    let away_average = ratings::average(
+        &away_players.iter().map(|p| p.rating).collect::<Vec<_>>(),
+        away.rating,
+    );
+
+    set_team_rating(
+        client,
+        home.id,
+        game.id,
+        home.rating,
+        ratings::updated(home.rating, away.rating, home_outcome),
+    )
+    .await?;
+    set_team_rating(
+        client,
+        away.id,
+        game.id,
+        away.rating,
+        ratings::updated(away.rating, home.rating, away_outcome),
+    )
+    .await?;
+
+    let sides = [
+        (&home_players, away_average, home_outcome),
+        (&away_players, home_average, away_outcome),
+    ];
+    for (players, opponents, outcome) in sides {
+        for player in players {
+            let after = ratings::updated(player.rating, opponents, outcome);
+            set_user_rating(client, player.id, game.id, player.rating, after).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+// Seasons, which group the leagues that run at the same time.
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
+
+use super::{leagues, query_all, query_one};
+use crate::{
+    errors::MyError,
+    models::{League, MiniLeague, MiniSeason, Season, SeasonStatus},
+};
+
+/// Every season, newest first.
+pub async fn get_seasons(client: &impl GenericClient) -> Result<Vec<Season>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Season,
+            "SELECT $table_fields FROM seasons ORDER BY created_at DESC, id DESC"
+        ),
+        &[],
+    )
+    .await
+}
+
+pub async fn get_season_from_id(
+    client: &impl GenericClient,
+    seasonid: i64,
+) -> Result<Season, MyError> {
+    query_one(
+        client,
+        sql!(Season, "SELECT $table_fields FROM seasons WHERE id=$1"),
+        &[&seasonid],
+    )
+    .await
+}
+
+/// Add a season, as a draft.
+pub async fn add_season(
+    client: &impl GenericClient,
+    season: &MiniSeason,
+) -> Result<Season, MyError> {
+    query_one(
+        client,
+        sql!(
+            Season,
+            "INSERT INTO seasons(name, status, starts_at, ends_at, \
+            registration_opens_at, registration_closes_at, created_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &season.name,
+            &SeasonStatus::Draft.as_str(),
+            &season.starts_at,
+            &season.ends_at,
+            &season.registration_opens_at,
+            &season.registration_closes_at,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+/// Overwrite the name and dates of season `seasonid`, and set its status.
+pub async fn update_season(
+    client: &impl GenericClient,
+    seasonid: i64,
+    season: &MiniSeason,
+    status: SeasonStatus,
+) -> Result<Season, MyError> {
+    query_one(
+        client,
+        sql!(
+            Season,
+            "UPDATE seasons SET name=$2, status=$3, starts_at=$4, ends_at=$5, \
+            registration_opens_at=$6, registration_closes_at=$7 \
+            WHERE id=$1 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &seasonid,
+            &season.name,
+            &status.as_str(),
+            &season.starts_at,
+            &season.ends_at,
+            &season.registration_opens_at,
+            &season.registration_closes_at,
+        ],
+    )
+    .await
+}
+
+/// Mark season `seasonid` as completed at `completed_at`.
+pub async fn set_completed_at(
+    client: &impl GenericClient,
+    seasonid: i64,
+    completed_at: DateTime<Utc>,
+) -> Result<Season, MyError> {
+    query_one(
+        client,
+        sql!(
+            Season,
+            "UPDATE seasons SET completed_at=$2 WHERE id=$1 RETURNING $table_fields"
+        ),
+        &[&seasonid, &completed_at],
+    )
+    .await
+}
+
+/// Copy every league of season `from`, and their divisions, into season `to`.
+///
+/// Leagues archived on their own, rather than along with the season, are left behind.
+/// The copies keep their names, visibility and division priorities,
+/// but start without teams or admins, and don't take sign ups until the new season opens them.
+pub async fn clone_season_structure(
+    client: &impl GenericClient,
+    from: &Season,
+    to: i64,
+) -> Result<Vec<League>, MyError> {
+    let mut cloned = Vec::new();
+    for league in
+        leagues::get_leagues_for_season_id(client, from.id, &leagues::Visibility::All).await?
+    {
+        let retired = match (league.archived_at, from.completed_at) {
+            (Some(archived_at), Some(completed_at)) => archived_at < completed_at,
+            (archived_at, _) => archived_at.is_some(),
+        };
+        if retired {
+            continue;
+        }
+        let copy = leagues::add_league(
+            client,
+            MiniLeague {
+                name: league.name,
+                accepting_teams: false,
+                is_hidden: league.is_hidden,
+                seasonid: Some(to),
+            },
+        )
+        .await?;
+        leagues::copy_divisions(client, league.id, copy.id).await?;
+        cloned.push(copy);
+    }
+    Ok(cloned)
+}
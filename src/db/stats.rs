@@ -0,0 +1,220 @@
+// Logs imported for games, the per-player stats read from them, and their rollups.
+use deadpool_postgres::GenericClient;
+
+use super::{execute, query_all, query_one, query_opt, query_scalar};
+use crate::{
+    errors::MyError,
+    logstf::PassTimeStats,
+    models::{
+        GameLog, LeaderboardStat, MiniGameLog, PlayerGameStats, PlayerStatTotals, SeasonStatTotals,
+        StatTotals,
+    },
+};
+
+pub async fn get_log_for_game_id(
+    client: &impl GenericClient,
+    gameid: i64,
+) -> Result<Option<GameLog>, MyError> {
+    query_opt(
+        client,
+        sql!(
+            GameLog,
+            "SELECT $table_fields FROM game_logs WHERE gameid=$1"
+        ),
+        &[&gameid],
+    )
+    .await
+}
+
+/// Everyone's stats in game `gameid`, grouped by team.
+pub async fn get_stats_for_game_id(
+    client: &impl GenericClient,
+    gameid: i64,
+) -> Result<Vec<PlayerGameStats>, MyError> {
+    query_all(
+        client,
+        sql!(
+            PlayerGameStats,
+            "SELECT $table_fields FROM player_game_stats WHERE gameid=$1 ORDER BY teamid, userid"
+        ),
+        &[&gameid],
+    )
+    .await
+}
+
+/// Attach `log` to its game, replacing any log and stats imported before.
+pub async fn replace_log(
+    client: &impl GenericClient,
+    log: &MiniGameLog,
+) -> Result<GameLog, MyError> {
+    execute(
+        client,
+        "DELETE FROM player_game_stats WHERE gameid=$1",
+        &[&log.gameid],
+    )
+    .await?;
+    execute(
+        client,
+        "DELETE FROM game_logs WHERE gameid=$1",
+        &[&log.gameid],
+    )
+    .await?;
+    query_one(
+        client,
+        sql!(
+            GameLog,
+            "INSERT INTO game_logs(gameid, logid, map, title, red_teamid, blue_teamid, \
+            imported_by, created_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &log.gameid,
+            &log.logid,
+            &log.map,
+            &log.title,
+            &log.red_teamid,
+            &log.blue_teamid,
+            &log.imported_by,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+pub async fn add_player_stats(
+    client: &impl GenericClient,
+    gameid: i64,
+    userid: i64,
+    teamid: i64,
+    stats: &PassTimeStats,
+    deaths: i32,
+) -> Result<PlayerGameStats, MyError> {
+    query_one(
+        client,
+        sql!(
+            PlayerGameStats,
+            "INSERT INTO player_game_stats(gameid, userid, teamid, goals, assists, intercepts, \
+            steals, passes, deaths) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &gameid,
+            &userid,
+            &teamid,
+            &stats.goals,
+            &stats.assists,
+            &stats.intercepts,
+            &stats.steals,
+            &stats.passes,
+            &deaths,
+        ],
+    )
+    .await
+}
+
+/// Rebuild the rollups of `teamids` in league `leagueid` from their games' stats.
+///
+/// Callers lock the teams with [super::teams::lock_teams] before writing any of their stats,
+/// so two logs imported at once for games of the same team rebuild its rollups one after
+/// the other, the second with the first's stats.
+pub async fn refresh_rollups(
+    client: &impl GenericClient,
+    leagueid: i64,
+    teamids: &[i64],
+) -> Result<(), MyError> {
+    execute(
+        client,
+        "DELETE FROM player_league_stats WHERE leagueid=$1 AND teamid=ANY($2)",
+        &[&leagueid, &teamids],
+    )
+    .await?;
+    execute(
+        client,
+        "INSERT INTO player_league_stats(userid, teamid, leagueid, games_played, goals, \
+        assists, intercepts, steals, passes, deaths) \
+        SELECT s.userid, s.teamid, g.leagueid, COUNT(*), SUM(s.goals), SUM(s.assists), \
+        SUM(s.intercepts), SUM(s.steals), SUM(s.passes), SUM(s.deaths) \
+        FROM player_game_stats s JOIN games g ON g.id = s.gameid \
+        WHERE g.leagueid=$1 AND s.teamid=ANY($2) \
+        GROUP BY s.userid, s.teamid, g.leagueid",
+        &[&leagueid, &teamids],
+    )
+    .await?;
+    Ok(())
+}
+
+/// What `userid` did in each season, in leagues that aren't hidden.
+/// Leagues outside any season are summed up last, with no `seasonid`.
+pub async fn get_season_totals(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<Vec<SeasonStatTotals>, MyError> {
+    query_all(
+        client,
+        include_str!("../../sql/get_season_totals.sql"),
+        &[&userid],
+    )
+    .await
+}
+
+/// Everything team `teamid`'s players did for it. `games_played` counts the team's logged games.
+pub async fn get_team_totals(
+    client: &impl GenericClient,
+    teamid: i64,
+) -> Result<StatTotals, MyError> {
+    query_one(
+        client,
+        include_str!("../../sql/get_team_totals.sql"),
+        &[&teamid],
+    )
+    .await
+}
+
+/// What each player did for team `teamid`, top scorers first.
+pub async fn get_team_player_totals(
+    client: &impl GenericClient,
+    teamid: i64,
+) -> Result<Vec<PlayerStatTotals>, MyError> {
+    query_all(
+        client,
+        include_str!("../../sql/get_team_player_totals.sql"),
+        &[&teamid],
+    )
+    .await
+}
+
+/// How many players have stats in division `divisionid`.
+pub async fn get_leaderboard_count(
+    client: &impl GenericClient,
+    divisionid: i64,
+) -> Result<i64, MyError> {
+    query_scalar(
+        client,
+        include_str!("../../sql/get_leaderboard_count.sql"),
+        &[&divisionid],
+    )
+    .await
+}
+
+/// One page of the players in division `divisionid`, ordered by `stat` and then by id.
+pub async fn get_leaderboard_page(
+    client: &impl GenericClient,
+    divisionid: i64,
+    stat: LeaderboardStat,
+    page: u32,
+    amount: std::num::NonZero<u32>,
+) -> Result<Vec<PlayerStatTotals>, MyError> {
+    let amount: u32 = amount.into();
+    let amount: i64 = amount.into();
+    let page: i64 = page.into();
+    let offset: i64 = page * amount;
+
+    query_all(
+        client,
+        include_str!("../../sql/get_leaderboard_paged.sql"),
+        &[&divisionid, &stat.as_str(), &offset, &amount],
+    )
+    .await
+}
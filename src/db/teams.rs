@@ -1,44 +1,736 @@
-use deadpool_postgres::Client;
+// Teams, their rosters in each division, and the players on those rosters.
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
 use serde::{Deserialize, Serialize};
-use tokio_pg_mapper::FromTokioPostgresRow;
-use tokio_postgres::types::ToSql;
 
-use crate::errors::MyError;
-use crate::models::UserTeam;
+use super::{
+    execute, leagues::Visibility, query_all, query_one, query_scalar,
+    users::mass_get_user_from_internal_id,
+};
+use crate::{
+    errors::MyError,
+    models::{
+        MiniTeam, PlacementStatus, Team, TeamDivAssociation, TeamNameHistory, User, UserTeam,
+    },
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum UserTeamAffiliation {
     Leader = 20,
     Officer = 10,
     Member = 0,
 }
 
+pub async fn get_team_from_id(client: &impl GenericClient, team_id: i64) -> Result<Team, MyError> {
+    query_one(
+        client,
+        sql!(Team, "SELECT $table_fields FROM teams WHERE id=$1"),
+        &[&team_id],
+    )
+    .await
+}
+
+pub async fn add_team(client: &impl GenericClient, team: &MiniTeam) -> Result<Team, MyError> {
+    let time_now = chrono::offset::Utc::now();
+
+    query_one(
+        client,
+        sql!(
+            Team,
+            "INSERT INTO teams(team_tag, team_name, created_at, owner_id) \
+            VALUES ($1, $2, $3, $4) \
+            RETURNING $table_fields"
+        ),
+        &[&team.team_tag, &team.team_name, &time_now, &team.owner_id],
+    )
+    .await
+}
+
+/// Change the team's name and tag. `None` leaves that one as it is.
+pub async fn update_team(
+    client: &impl GenericClient,
+    teamid: i64,
+    team_name: Option<&str>,
+    team_tag: Option<&str>,
+) -> Result<Team, MyError> {
+    query_one(
+        client,
+        sql!(
+            Team,
+            "UPDATE teams \
+            SET team_name=COALESCE($2, team_name), team_tag=COALESCE($3, team_tag) \
+            WHERE id=$1 \
+            RETURNING $table_fields"
+        ),
+        &[&teamid, &team_name, &team_tag],
+    )
+    .await
+}
+
+pub async fn set_team_logo(
+    client: &impl GenericClient,
+    teamid: i64,
+    logo_url: Option<&str>,
+) -> Result<Team, MyError> {
+    query_one(
+        client,
+        sql!(
+            Team,
+            "UPDATE teams SET logo_url=$2 WHERE id=$1 RETURNING $table_fields"
+        ),
+        &[&teamid, &logo_url],
+    )
+    .await
+}
+
+pub async fn set_team_owner(
+    client: &impl GenericClient,
+    teamid: i64,
+    owner_id: i64,
+) -> Result<Team, MyError> {
+    query_one(
+        client,
+        sql!(
+            Team,
+            "UPDATE teams SET owner_id=$2 WHERE id=$1 RETURNING $table_fields"
+        ),
+        &[&teamid, &owner_id],
+    )
+    .await
+}
+
+/// Set `userid`'s affiliation on every roster of `teamid` they're currently on.
+pub async fn set_active_affiliation(
+    client: &impl GenericClient,
+    teamid: i64,
+    userid: i64,
+    affiliation: UserTeamAffiliation,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "UPDATE userTeamAssociation SET affiliation=$3 \
+        WHERE userid=$2 AND ended_at IS NULL \
+        AND teamdivid IN (SELECT id FROM teamDivAssociations WHERE teamid=$1)",
+        &[&teamid, &userid, &(affiliation as i32)],
+    )
+    .await
+}
+
+/// How many rosters of `teamid` `userid` is currently on, but `other_userid` isn't.
+pub async fn count_rosters_without_other_user(
+    client: &impl GenericClient,
+    teamid: i64,
+    userid: i64,
+    other_userid: i64,
+) -> Result<i64, MyError> {
+    query_scalar(
+        client,
+        "SELECT COUNT(*) FROM userTeamAssociation AS ours \
+        INNER JOIN teamDivAssociations ON teamDivAssociations.id = ours.teamdivid \
+        WHERE teamDivAssociations.teamid=$1 AND ours.userid=$2 AND ours.ended_at IS NULL \
+        AND NOT EXISTS (SELECT 1 FROM userTeamAssociation AS theirs \
+            WHERE theirs.teamdivid = ours.teamdivid AND theirs.userid=$3 \
+            AND theirs.ended_at IS NULL)",
+        &[&teamid, &userid, &other_userid],
+    )
+    .await
+}
+
+/// Record that `team` started going by its current name and tag at `started_at`.
+pub async fn record_team_name(
+    client: &impl GenericClient,
+    team: &Team,
+    started_at: DateTime<Utc>,
+) -> Result<TeamNameHistory, MyError> {
+    query_one(
+        client,
+        sql!(
+            TeamNameHistory,
+            "INSERT INTO team_name_history(teamid, team_name, team_tag, started_at) \
+            VALUES ($1, $2, $3, $4) \
+            RETURNING $table_fields"
+        ),
+        &[&team.id, &team.team_name, &team.team_tag, &started_at],
+    )
+    .await
+}
+
+/// Every name and tag `teamid` has gone by, most recent first.
+pub async fn get_team_name_history(
+    client: &impl GenericClient,
+    teamid: i64,
+) -> Result<Vec<TeamNameHistory>, MyError> {
+    query_all(
+        client,
+        sql!(
+            TeamNameHistory,
+            "SELECT $table_fields FROM team_name_history WHERE teamid=$1 \
+            ORDER BY started_at DESC, id DESC"
+        ),
+        &[&teamid],
+    )
+    .await
+}
+
+/// The entry of `history` (most recent first) the team went by at `at`.
+///
+/// Times before the first entry get the first entry, since teams made before
+/// names were recorded only have their name from then on.
+pub fn name_at(history: &[TeamNameHistory], at: DateTime<Utc>) -> Option<&TeamNameHistory> {
+    history
+        .iter()
+        .find(|entry| entry.started_at <= at)
+        .or(history.last())
+}
+
+/// Mark the team as disbanded. Errors with [MyError::NotFound] if it already was.
+pub async fn disband_team(client: &impl GenericClient, teamid: i64) -> Result<Team, MyError> {
+    query_one(
+        client,
+        sql!(
+            Team,
+            "UPDATE teams SET disbanded_at=$2 \
+            WHERE id=$1 AND disbanded_at IS NULL \
+            RETURNING $table_fields"
+        ),
+        &[&teamid, &chrono::offset::Utc::now()],
+    )
+    .await
+}
+
+/// Take every player off every roster of `teamid` they're currently on.
+pub async fn end_active_memberships(
+    client: &impl GenericClient,
+    teamid: i64,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "UPDATE userTeamAssociation SET ended_at=$2 \
+        WHERE ended_at IS NULL \
+        AND teamdivid IN (SELECT id FROM teamDivAssociations WHERE teamid=$1)",
+        &[&teamid, &chrono::offset::Utc::now()],
+    )
+    .await
+}
+
+pub async fn get_ownerships_for_user_id(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<Vec<Team>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Team,
+            "SELECT $table_fields FROM teams WHERE owner_id=$1 AND disbanded_at IS NULL"
+        ),
+        &[&userid],
+    )
+    .await
+}
+
+/// Roster `id`, unless `visibility` doesn't show it, in which case it's [MyError::NotFound]
+/// just like a missing roster. Like in [get_teams_for_div_ids], only the league's admins see
+/// private rosters and ones still waiting to be placed.
+pub async fn get_team_div_assoc_from_id(
+    client: &impl GenericClient,
+    id: i64,
+    visibility: &Visibility,
+) -> Result<TeamDivAssociation, MyError> {
+    let (all, leagueids) = visibility.params();
+    query_one(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "SELECT $table_fields FROM teamDivAssociations \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            INNER JOIN leagues ON leagues.id = divisions.leagueid \
+            WHERE teamDivAssociations.id=$1 \
+            AND ($2 OR leagues.id=any($3) \
+            OR (NOT leagues.is_hidden AND NOT teamDivAssociations.is_private \
+            AND teamDivAssociations.placement_status<>$4))"
+        ),
+        &[&id, &all, &leagueids, &PlacementStatus::Pending.as_str()],
+    )
+    .await
+}
+
+/// Every roster `teamid` has had that `visibility` shows, in any division.
+pub async fn get_team_tdas_teamid(
+    client: &impl GenericClient,
+    teamid: i64,
+    visibility: &Visibility,
+) -> Result<Vec<TeamDivAssociation>, MyError> {
+    let (all, leagueids) = visibility.params();
+    query_all(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "SELECT $table_fields FROM teamDivAssociations \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            INNER JOIN leagues ON leagues.id = divisions.leagueid \
+            WHERE teamDivAssociations.teamid=$1 \
+            AND ($2 OR leagues.id=any($3) \
+            OR (NOT leagues.is_hidden AND NOT teamDivAssociations.is_private))"
+        ),
+        &[&teamid, &all, &leagueids],
+    )
+    .await
+}
+
+pub async fn get_teamassociations_for_div_id(
+    client: &impl GenericClient,
+    divisionid: i64,
+) -> Result<Vec<TeamDivAssociation>, MyError> {
+    query_all(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "SELECT $table_fields FROM teamDivAssociations WHERE divisionid=$1"
+        ),
+        &[&divisionid],
+    )
+    .await
+}
+
+/// Lock team `teamid` until the end of the transaction, returning it as it is now.
+///
+/// Tag checks take this before locking any league, so they always lock in the same order.
+pub async fn lock_team(client: &impl GenericClient, teamid: i64) -> Result<Team, MyError> {
+    query_one(
+        client,
+        sql!(
+            Team,
+            "SELECT $table_fields FROM teams WHERE id=$1 FOR UPDATE"
+        ),
+        &[&teamid],
+    )
+    .await
+}
+
+/// Lock teams `teamids` until the end of the transaction, returning them as they are now.
+///
+/// Locks them in id order, so two transactions locking some of the same teams wait for each
+/// other instead of deadlocking.
+pub async fn lock_teams(
+    client: &impl GenericClient,
+    teamids: &[i64],
+) -> Result<Vec<Team>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Team,
+            "SELECT $table_fields FROM teams WHERE id=any($1) ORDER BY id FOR UPDATE"
+        ),
+        &[&teamids],
+    )
+    .await
+}
+
+/// Lock every league team `teamid` is signed up to until the end of the transaction,
+/// so a tag check in them can't race a sign up or another rename.
+pub async fn lock_team_leagues(client: &impl GenericClient, teamid: i64) -> Result<(), MyError> {
+    execute(
+        client,
+        "SELECT id FROM leagues WHERE id IN (\
+            SELECT divisions.leagueid FROM teamDivAssociations \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            WHERE teamDivAssociations.teamid=$1\
+        ) ORDER BY id FOR UPDATE",
+        &[&teamid],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Whether any team other than `teamid` signed up to a division of `leagueid` has the tag `tag`,
+/// ignoring case. Disbanded teams have given up their tags.
+pub async fn is_tag_taken_in_league(
+    client: &impl GenericClient,
+    leagueid: i64,
+    tag: &str,
+    teamid: i64,
+) -> Result<bool, MyError> {
+    query_scalar(
+        client,
+        "SELECT EXISTS (\
+            SELECT 1 FROM teams \
+            INNER JOIN teamDivAssociations ON teamDivAssociations.teamid = teams.id \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            WHERE divisions.leagueid=$1 AND lower(teams.team_tag)=lower($2) AND teams.id<>$3 \
+            AND teams.disbanded_at IS NULL\
+        )",
+        &[&leagueid, &tag, &teamid],
+    )
+    .await
+}
+
+/// Whether any other team uses `tag` (ignoring case) in a league `teamid` is signed up to.
+/// Disbanded teams have given up their tags.
+pub async fn is_tag_taken_in_team_leagues(
+    client: &impl GenericClient,
+    teamid: i64,
+    tag: &str,
+) -> Result<bool, MyError> {
+    query_scalar(
+        client,
+        "SELECT EXISTS (\
+            SELECT 1 FROM teams \
+            INNER JOIN teamDivAssociations ON teamDivAssociations.teamid = teams.id \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            WHERE lower(teams.team_tag)=lower($2) AND teams.id<>$1 \
+            AND teams.disbanded_at IS NULL \
+            AND divisions.leagueid IN (\
+                SELECT divisions.leagueid FROM teamDivAssociations \
+                INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+                WHERE teamDivAssociations.teamid=$1\
+            )\
+        )",
+        &[&teamid, &tag],
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MiniTeamDivAssociation {
+    pub roster_name: Option<String>,
+    pub teamid: i64,
+    pub divisionid: i64,
+    pub is_private: bool,
+    /// Set for sign ups, which then wait in `divisionid` for an admin to place them.
+    pub requested_divisionid: Option<i64>,
+}
+pub async fn add_team_div_assoc(
+    client: &impl GenericClient,
+    teamdiv: MiniTeamDivAssociation,
+) -> Result<TeamDivAssociation, MyError> {
+    let status = match teamdiv.requested_divisionid {
+        Some(_) => PlacementStatus::Pending,
+        None => PlacementStatus::Placed,
+    };
+    query_one(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "INSERT INTO \
+            teamDivAssociations (roster_name, teamid, divisionid, created_at, is_private, \
+            requested_divisionid, placement_status) \
+            VALUES($1, $2, $3, $4, $5, $6, $7) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &teamdiv.roster_name,
+            &teamdiv.teamid,
+            &teamdiv.divisionid,
+            &chrono::offset::Utc::now(),
+            &teamdiv.is_private,
+            &teamdiv.requested_divisionid,
+            &status.as_str(),
+        ],
+    )
+    .await
+}
+
+/// Move roster `teamdivid` into `divisionid` for good, noting who did it and why.
+pub async fn place_roster(
+    client: &impl GenericClient,
+    teamdivid: i64,
+    divisionid: i64,
+    note: Option<&str>,
+    placed_by: i64,
+) -> Result<TeamDivAssociation, MyError> {
+    query_one(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "UPDATE teamDivAssociations \
+            SET divisionid=$2, placement_status=$3, placement_note=$4, placed_at=$5, placed_by=$6 \
+            WHERE id=$1 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &teamdivid,
+            &divisionid,
+            &PlacementStatus::Placed.as_str(),
+            &note,
+            &chrono::offset::Utc::now(),
+            &placed_by,
+        ],
+    )
+    .await
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeepTeamDivAssociation {
+    pub team_info: Team,
+    pub association_info: TeamDivAssociation,
+}
+/// Every roster in any of `divisionids` that `visibility` shows, along with its team.
+/// Sign ups still waiting to be placed are only shown along with private rosters.
+///
+/// Rosters of disbanded teams are only listed in archived leagues, where they're part of
+/// the league's history. Anywhere else the team is gone.
+pub async fn get_teams_for_div_ids(
+    client: &impl GenericClient,
+    divisionids: &[i64],
+    visibility: &Visibility,
+) -> Result<Vec<DeepTeamDivAssociation>, MyError> {
+    let (all, leagueids) = visibility.params();
+    let assocs: Vec<TeamDivAssociation> = query_all(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "SELECT $table_fields FROM teamDivAssociations \
+            INNER JOIN teams ON teams.id = teamDivAssociations.teamid \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            INNER JOIN leagues ON leagues.id = divisions.leagueid \
+            WHERE teamDivAssociations.divisionid=any($1) \
+            AND (teams.disbanded_at IS NULL OR leagues.archived_at IS NOT NULL) \
+            AND ($2 OR leagues.id=any($3) \
+            OR (NOT leagues.is_hidden AND NOT teamDivAssociations.is_private \
+            AND teamDivAssociations.placement_status<>$4))"
+        ),
+        &[
+            &divisionids,
+            &all,
+            &leagueids,
+            &PlacementStatus::Pending.as_str(),
+        ],
+    )
+    .await?;
+
+    let teamids: Vec<i64> = assocs.iter().map(|assoc| assoc.teamid).collect();
+    let teams: HashMap<i64, Team> = query_all(
+        client,
+        sql!(Team, "SELECT $table_fields FROM teams WHERE id=any($1)"),
+        &[&teamids],
+    )
+    .await?
+    .into_iter()
+    .map(|team: Team| (team.id, team))
+    .collect();
+
+    let ret = assocs
+        .into_iter()
+        .filter_map(|assoc| {
+            Some(DeepTeamDivAssociation {
+                team_info: teams.get(&assoc.teamid)?.clone(),
+                association_info: assoc,
+            })
+        })
+        .collect();
+    Ok(ret)
+}
+
 pub async fn add_user_team_id(
-    client: &Client,
+    client: &impl GenericClient,
     userid: i64,
     teamdivid: i64,
     status: UserTeamAffiliation,
 ) -> Result<UserTeam, MyError> {
-    let _stmt = "INSERT INTO \
-    userTeamAssociation(userid, teamdivid, created_at, affiliation) \
-    VALUES($1, $2, $3, $4) \
-    RETURNING $table_fields"
-        .replace("$table_fields", &UserTeam::sql_table_fields());
-
-    let stmt = client.prepare(&_stmt).await?;
-
-    let row = client
-        .query_one(
-            &stmt,
-            &[
-                &userid,
-                &teamdivid,
-                &chrono::offset::Utc::now(),
-                &(status as i32),
-            ],
-        )
-        .await
-        .map_err(|_| MyError::NotFound)?;
-
-    Ok(UserTeam::from_row(row).unwrap())
+    query_one(
+        client,
+        sql!(
+            UserTeam,
+            "INSERT INTO \
+            userTeamAssociation(userid, teamdivid, created_at, affiliation) \
+            VALUES($1, $2, $3, $4) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &userid,
+            &teamdivid,
+            &chrono::offset::Utc::now(),
+            &(status as i32),
+        ],
+    )
+    .await
+}
+
+/// Everyone currently on team `teamid`'s rosters in league `leagueid`.
+pub async fn get_active_player_ids_in_league(
+    client: &impl GenericClient,
+    teamid: i64,
+    leagueid: i64,
+) -> Result<Vec<i64>, MyError> {
+    query_scalar(
+        client,
+        "SELECT COALESCE(array_agg(DISTINCT userTeamAssociation.userid), '{}') \
+        FROM userTeamAssociation \
+        INNER JOIN teamDivAssociations ON teamDivAssociations.id = userTeamAssociation.teamdivid \
+        INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+        WHERE teamDivAssociations.teamid=$1 AND divisions.leagueid=$2 \
+        AND userTeamAssociation.ended_at IS NULL",
+        &[&teamid, &leagueid],
+    )
+    .await
+}
+
+/// Everyone who was on team `teamid`'s rosters in league `leagueid` at `at`.
+pub async fn get_player_ids_in_league_at(
+    client: &impl GenericClient,
+    teamid: i64,
+    leagueid: i64,
+    at: DateTime<Utc>,
+) -> Result<Vec<i64>, MyError> {
+    query_scalar(
+        client,
+        "SELECT COALESCE(array_agg(DISTINCT userTeamAssociation.userid), '{}') \
+        FROM userTeamAssociation \
+        INNER JOIN teamDivAssociations ON teamDivAssociations.id = userTeamAssociation.teamdivid \
+        INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+        WHERE teamDivAssociations.teamid=$1 AND divisions.leagueid=$2 \
+        AND userTeamAssociation.created_at <= $3 \
+        AND (userTeamAssociation.ended_at IS NULL OR userTeamAssociation.ended_at > $3)",
+        &[&teamid, &leagueid, &at],
+    )
+    .await
+}
+
+/// Whether `userid` has ever been on a roster in league `leagueid`.
+pub async fn has_played_in_league(
+    client: &impl GenericClient,
+    userid: i64,
+    leagueid: i64,
+) -> Result<bool, MyError> {
+    query_scalar(
+        client,
+        "SELECT EXISTS(SELECT 1 FROM userTeamAssociation \
+        INNER JOIN teamDivAssociations ON teamDivAssociations.id = userTeamAssociation.teamdivid \
+        INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+        WHERE userTeamAssociation.userid=$1 AND divisions.leagueid=$2)",
+        &[&userid, &leagueid],
+    )
+    .await
+}
+
+/// `userid`'s highest affiliation on team `teamid`'s current rosters in league `leagueid`,
+/// or `None` if they aren't on any of them.
+pub async fn get_affiliation_in_league(
+    client: &impl GenericClient,
+    userid: i64,
+    teamid: i64,
+    leagueid: i64,
+) -> Result<Option<i32>, MyError> {
+    query_scalar(
+        client,
+        "SELECT MAX(userTeamAssociation.affiliation) \
+        FROM userTeamAssociation \
+        INNER JOIN teamDivAssociations ON teamDivAssociations.id = userTeamAssociation.teamdivid \
+        INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+        WHERE userTeamAssociation.userid=$1 AND teamDivAssociations.teamid=$2 \
+        AND divisions.leagueid=$3 AND userTeamAssociation.ended_at IS NULL",
+        &[&userid, &teamid, &leagueid],
+    )
+    .await
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserAndAssoc {
+    pub user: User,
+    pub assoc: UserTeam,
+}
+
+pub async fn get_team_players(
+    client: &impl GenericClient,
+    team: &TeamDivAssociation,
+) -> Result<Vec<UserAndAssoc>, MyError> {
+    let userassocs: Vec<UserTeam> = query_all(
+        client,
+        sql!(
+            UserTeam,
+            "SELECT $table_fields FROM userTeamAssociation WHERE teamdivid=$1"
+        ),
+        &[&team.id],
+    )
+    .await?;
+
+    let userids: Vec<i64> = userassocs.iter().map(|item| item.userid).collect();
+    let users: HashMap<i64, User> = mass_get_user_from_internal_id(client, &userids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect();
+
+    let out = userassocs
+        .into_iter()
+        .filter_map(|assoc| {
+            let user = users.get(&assoc.userid)?.clone();
+            Some(UserAndAssoc { user, assoc })
+        })
+        .collect();
+    Ok(out)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SuperDeepTeamDivAssociation {
+    pub user: UserTeam,
+    pub team: DeepTeamDivAssociation,
+}
+/// Every roster `userid` has been on that `visibility` shows, along with the team it belongs to.
+pub async fn get_rosters_for_user_id(
+    client: &impl GenericClient,
+    userid: i64,
+    visibility: &Visibility,
+) -> Result<Vec<SuperDeepTeamDivAssociation>, MyError> {
+    let userassocs: Vec<UserTeam> = query_all(
+        client,
+        sql!(
+            UserTeam,
+            "SELECT $table_fields FROM userTeamAssociation WHERE userid=$1"
+        ),
+        &[&userid],
+    )
+    .await?;
+
+    let tdaids: Vec<i64> = userassocs.iter().map(|assoc| assoc.teamdivid).collect();
+    let (all, leagueids) = visibility.params();
+    let tdas: HashMap<i64, TeamDivAssociation> = query_all(
+        client,
+        sql!(
+            TeamDivAssociation,
+            "SELECT $table_fields FROM teamDivAssociations \
+            INNER JOIN divisions ON divisions.id = teamDivAssociations.divisionid \
+            INNER JOIN leagues ON leagues.id = divisions.leagueid \
+            WHERE teamDivAssociations.id=any($1) \
+            AND ($2 OR leagues.id=any($3) \
+            OR (NOT leagues.is_hidden AND NOT teamDivAssociations.is_private))"
+        ),
+        &[&tdaids, &all, &leagueids],
+    )
+    .await?
+    .into_iter()
+    .map(|tda: TeamDivAssociation| (tda.id, tda))
+    .collect();
+
+    let teamids: Vec<i64> = tdas.values().map(|tda| tda.teamid).collect();
+    let teams: HashMap<i64, Team> = query_all(
+        client,
+        sql!(Team, "SELECT $table_fields FROM teams WHERE id=any($1)"),
+        &[&teamids],
+    )
+    .await?
+    .into_iter()
+    .map(|team: Team| (team.id, team))
+    .collect();
+
+    let mut resp: Vec<SuperDeepTeamDivAssociation> = Vec::with_capacity(userassocs.len());
+    for assoc in userassocs {
+        let Some(tda) = tdas.get(&assoc.teamdivid) else {
+            continue;
+        };
+        let Some(team) = teams.get(&tda.teamid) else {
+            continue;
+        };
+        resp.push(SuperDeepTeamDivAssociation {
+            user: assoc,
+            team: DeepTeamDivAssociation {
+                association_info: tda.clone(),
+                team_info: team.clone(),
+            },
+        })
+    }
+
+    Ok(resp)
 }
@@ -0,0 +1,105 @@
+// Throwaway databases for tests.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use deadpool_postgres::{Manager, Pool};
+use tokio_postgres::{Config, NoTls};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A freshly made database, with the schema and `sql/test_data.sql` loaded,
+/// that is dropped again when this is.
+///
+/// Made on the server in `TEST_DATABASE_URL`, which only needs to be able to `CREATE DATABASE`.
+pub struct TestDb {
+    pub pool: Pool,
+    name: String,
+    admin_config: Config,
+}
+
+impl TestDb {
+    /// `None` (and the test should be skipped) if `SKIP_DB_TESTS` is set.
+    ///
+    /// Panics if `TEST_DATABASE_URL` isn't set otherwise, so a run without a database
+    /// doesn't look like one where everything passed.
+    pub async fn new() -> Option<TestDb> {
+        if std::env::var_os("SKIP_DB_TESTS").is_some() {
+            eprintln!("SKIP_DB_TESTS is set, skipping database test");
+            return None;
+        }
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL should be set, or SKIP_DB_TESTS to skip database tests");
+        let admin_config: Config = url
+            .parse()
+            .expect("TEST_DATABASE_URL should be a postgres connection string");
+
+        let name = format!(
+            "lucyleague_test_{0}_{1}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let (admin, connection) = admin_config
+            .connect(NoTls)
+            .await
+            .expect("should be able to connect to TEST_DATABASE_URL");
+        tokio::spawn(connection);
+        // Left over from a run that was killed before it could clean up.
+        admin
+            .batch_execute(&format!("DROP DATABASE IF EXISTS {name}"))
+            .await
+            .unwrap();
+        admin
+            .batch_execute(&format!("CREATE DATABASE {name}"))
+            .await
+            .unwrap();
+
+        let mut config = admin_config.clone();
+        config.dbname(&name);
+        let pool = Pool::builder(Manager::new(config, NoTls))
+            .max_size(4)
+            .build()
+            .unwrap();
+
+        let client = pool.get().await.unwrap();
+        super::migrate(&client).await.unwrap();
+        super::add_test_data(&client).await.unwrap();
+
+        Some(TestDb {
+            pool,
+            name,
+            admin_config,
+        })
+    }
+
+    /// The database's name, unique to this test.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        self.pool.close();
+        let config = self.admin_config.clone();
+        let name = self.name.clone();
+
+        // Drop can't be async, and we may be inside a runtime already, so use a new one.
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let (admin, connection) = config.connect(NoTls).await?;
+                    tokio::spawn(connection);
+                    admin
+                        .batch_execute(&format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"))
+                        .await
+                })
+        })
+        .join();
+
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("Couldn't drop test database {0}", self.name);
+        }
+    }
+}
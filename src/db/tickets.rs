@@ -0,0 +1,183 @@
+// Disputes and support tickets, and the messages in their threads.
+use deadpool_postgres::GenericClient;
+
+use super::{execute, query_all, query_one, query_opt};
+use crate::{
+    errors::MyError,
+    models::{MiniTicket, Ticket, TicketMessage, TicketOutcome, TicketStatus},
+};
+
+pub async fn get_ticket_from_id(
+    client: &impl GenericClient,
+    ticketid: i64,
+) -> Result<Ticket, MyError> {
+    query_one(
+        client,
+        sql!(Ticket, "SELECT $table_fields FROM tickets WHERE id=$1"),
+        &[&ticketid],
+    )
+    .await
+}
+
+/// Every ticket, optionally only those with `status`. Most recently active first.
+pub async fn get_tickets(
+    client: &impl GenericClient,
+    status: Option<&str>,
+) -> Result<Vec<Ticket>, MyError> {
+    query_all(
+        client,
+        sql!(
+            Ticket,
+            "SELECT $table_fields FROM tickets \
+            WHERE ($1::VARCHAR IS NULL OR status=$1) \
+            ORDER BY updated_at DESC, id DESC"
+        ),
+        &[&status],
+    )
+    .await
+}
+
+/// The tickets `userid` can see: those in leagues `leagueids` they run, those about them,
+/// and those involving a team they're currently on.
+pub async fn get_tickets_for_user_id(
+    client: &impl GenericClient,
+    userid: i64,
+    leagueids: &[i64],
+    status: Option<&str>,
+) -> Result<Vec<Ticket>, MyError> {
+    query_all(
+        client,
+        sql!(Ticket, include_str!("../../sql/get_tickets_for_user.sql")),
+        &[&userid, &leagueids, &status],
+    )
+    .await
+}
+
+/// The thread of ticket `ticketid`, oldest first.
+pub async fn get_messages_for_ticket_id(
+    client: &impl GenericClient,
+    ticketid: i64,
+) -> Result<Vec<TicketMessage>, MyError> {
+    query_all(
+        client,
+        sql!(
+            TicketMessage,
+            "SELECT $table_fields FROM ticket_messages WHERE ticketid=$1 ORDER BY created_at, id"
+        ),
+        &[&ticketid],
+    )
+    .await
+}
+
+/// Open a ticket. Its first message has to be added separately, see [add_message].
+pub async fn add_ticket(
+    client: &impl GenericClient,
+    ticket: &MiniTicket,
+) -> Result<Ticket, MyError> {
+    let now = chrono::offset::Utc::now();
+    query_one(
+        client,
+        sql!(
+            Ticket,
+            "INSERT INTO tickets(leagueid, teamid, opened_by, gameid, reported_userid, category, \
+            subject, created_at, updated_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8) \
+            RETURNING $table_fields"
+        ),
+        &[
+            &ticket.leagueid,
+            &ticket.teamid,
+            &ticket.opened_by,
+            &ticket.gameid,
+            &ticket.reported_userid,
+            &ticket.category.as_str(),
+            &ticket.subject,
+            &now,
+        ],
+    )
+    .await
+}
+
+/// Add to the thread of ticket `ticketid`, marking it as just active.
+pub async fn add_message(
+    client: &impl GenericClient,
+    ticketid: i64,
+    userid: i64,
+    body: &str,
+    evidence: &[String],
+) -> Result<TicketMessage, MyError> {
+    let now = chrono::offset::Utc::now();
+    execute(
+        client,
+        "UPDATE tickets SET updated_at=$2 WHERE id=$1",
+        &[&ticketid, &now],
+    )
+    .await?;
+    query_one(
+        client,
+        sql!(
+            TicketMessage,
+            "INSERT INTO ticket_messages(ticketid, userid, body, evidence, created_at) \
+            VALUES ($1, $2, $3, $4, $5) \
+            RETURNING $table_fields"
+        ),
+        &[&ticketid, &userid, &body, &evidence, &now],
+    )
+    .await
+}
+
+/// Move ticket `ticketid` to `status`. Resolved tickets stay resolved, since what their
+/// resolution did isn't undone.
+pub async fn set_ticket_status(
+    client: &impl GenericClient,
+    ticketid: i64,
+    status: TicketStatus,
+) -> Result<Ticket, MyError> {
+    query_opt(
+        client,
+        sql!(
+            Ticket,
+            "UPDATE tickets SET status=$2, updated_at=$3 \
+            WHERE id=$1 AND status<>$4 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &ticketid,
+            &status.as_str(),
+            &chrono::offset::Utc::now(),
+            &TicketStatus::Resolved.as_str(),
+        ],
+    )
+    .await?
+    .ok_or_else(|| MyError::Conflict("This ticket was already resolved".to_owned()))
+}
+
+/// Resolve ticket `ticketid` with `outcome`, unless someone else already has.
+pub async fn resolve_ticket(
+    client: &impl GenericClient,
+    ticketid: i64,
+    outcome: TicketOutcome,
+    note: Option<&str>,
+    resolved_by: i64,
+) -> Result<Ticket, MyError> {
+    query_opt(
+        client,
+        sql!(
+            Ticket,
+            "UPDATE tickets SET status=$2, outcome=$3, resolution_note=$4, \
+            resolved_by=$5, resolved_at=$6, updated_at=$6 \
+            WHERE id=$1 AND status<>$2 \
+            RETURNING $table_fields"
+        ),
+        &[
+            &ticketid,
+            &TicketStatus::Resolved.as_str(),
+            &outcome.as_str(),
+            &note,
+            &resolved_by,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await?
+    .ok_or_else(|| MyError::Conflict("This ticket was already resolved".to_owned()))
+}
@@ -0,0 +1,396 @@
+// Users, their sessions, and what we know about their Steam accounts.
+use chrono::{DateTime, Utc};
+use deadpool_postgres::GenericClient;
+
+use super::{execute, query_all, query_one, query_opt, query_scalar};
+use crate::{
+    authorization::create_authorization_for_user,
+    errors::MyError,
+    models::{Authorization, MiniUser, User, UserReviewFlag, UsernameHistory},
+    permission::UserPermission,
+};
+
+pub async fn get_user_from_internal_id(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<User, MyError> {
+    query_one(
+        client,
+        sql!(User, "SELECT $table_fields FROM users WHERE id=$1"),
+        &[&userid],
+    )
+    .await
+}
+
+/// Get every user in `userids`. Ids with no user are skipped, and the order isn't kept.
+pub async fn mass_get_user_from_internal_id(
+    client: &impl GenericClient,
+    userids: &[i64],
+) -> Result<Vec<User>, MyError> {
+    query_all(
+        client,
+        sql!(User, "SELECT $table_fields FROM users WHERE id=any($1)"),
+        &[&userids],
+    )
+    .await
+}
+
+/// Every user with one of `steamids`. Unknown ones are left out, so don't rely on the order.
+pub async fn mass_get_users_from_steamids(
+    client: &impl GenericClient,
+    steamids: &[String],
+) -> Result<Vec<User>, MyError> {
+    query_all(
+        client,
+        sql!(
+            User,
+            "SELECT $table_fields FROM users WHERE steamid=any($1)"
+        ),
+        &[&steamids],
+    )
+    .await
+}
+
+pub async fn get_user_from_steamid(
+    client: &impl GenericClient,
+    steamid: &str,
+) -> Result<User, MyError> {
+    query_one(
+        client,
+        sql!(User, include_str!("../../sql/get_user_from_steamid.sql")),
+        &[&steamid],
+    )
+    .await
+}
+
+pub async fn get_user_from_auth_token(
+    client: &impl GenericClient,
+    token: &str,
+) -> Result<User, MyError> {
+    query_one(
+        client,
+        sql!(User, include_str!("../../sql/get_user_from_authtoken.sql")),
+        &[&token],
+    )
+    .await
+}
+
+pub async fn get_users(client: &impl GenericClient) -> Result<Vec<User>, MyError> {
+    query_all(
+        client,
+        sql!(User, include_str!("../../sql/get_users.sql")),
+        &[],
+    )
+    .await
+}
+
+pub async fn get_user_count(client: &impl GenericClient) -> Result<i64, MyError> {
+    query_scalar(client, "SELECT COUNT(*) FROM users", &[]).await
+}
+
+pub async fn get_user_page(
+    client: &impl GenericClient,
+    page: u32,
+    amount: std::num::NonZero<u32>,
+) -> Result<Vec<User>, MyError> {
+    log::trace!("Getting page {page} amount {amount}");
+    let amount: u32 = amount.into();
+    let amount: i64 = amount.into();
+    let page: i64 = page.into();
+    let offset: i64 = page * amount;
+
+    query_all(
+        client,
+        sql!(User, include_str!("../../sql/get_users_paged.sql")),
+        &[&offset, &amount],
+    )
+    .await
+}
+
+/// Search by username, steamid, or any name the user has gone by before.
+///
+/// Matches on current names come first.
+pub async fn search_usernames(
+    client: &impl GenericClient,
+    search_term: &str,
+    page: u32,
+    amount: std::num::NonZero<u32>,
+) -> Result<Vec<User>, MyError> {
+    log::trace!("Searching DB with term {search_term}");
+
+    let amount: u32 = amount.into();
+    let amount: i64 = amount.into();
+    let page: i64 = page.into();
+    let offset: i64 = page * amount;
+
+    query_all(
+        client,
+        sql!(User, include_str!("../../sql/fuzzy_search.sql")),
+        &[&search_term, &amount, &offset],
+    )
+    .await
+}
+
+/// Get up to `amount` users with an id greater than `after_id`, in id order.
+///
+/// Used to walk through every user in batches without an OFFSET.
+pub async fn get_users_after_id(
+    client: &impl GenericClient,
+    after_id: i64,
+    amount: i64,
+) -> Result<Vec<User>, MyError> {
+    query_all(
+        client,
+        sql!(
+            User,
+            "SELECT $table_fields FROM users WHERE id > $1 ORDER BY id ASC LIMIT $2"
+        ),
+        &[&after_id, &amount],
+    )
+    .await
+}
+
+pub async fn add_user(client: &impl GenericClient, user_info: MiniUser) -> Result<User, MyError> {
+    let date = chrono::offset::Utc::now();
+
+    query_one(
+        client,
+        sql!(User, include_str!("../../sql/add_user.sql")),
+        &[
+            &user_info.steamid,
+            &user_info.username,
+            &user_info.avatarurl,
+            &date,
+        ],
+    )
+    .await
+}
+
+pub async fn update_user_profile(
+    client: &impl GenericClient,
+    userid: i64,
+    username: &str,
+    avatarurl: &str,
+) -> Result<User, MyError> {
+    query_one(
+        client,
+        sql!(
+            User,
+            "UPDATE users SET username=$1, avatarurl=$2 WHERE id=$3 RETURNING $table_fields"
+        ),
+        &[&username, &avatarurl, &userid],
+    )
+    .await
+}
+
+pub async fn set_user_permissions(
+    client: &impl GenericClient,
+    user: &User,
+    permissions: i64,
+) -> Result<User, MyError> {
+    query_one(
+        client,
+        sql!(
+            User,
+            "UPDATE users SET permissions=$1 WHERE id=$2 RETURNING $table_fields"
+        ),
+        &[&permissions, &user.id],
+    )
+    .await
+}
+
+pub async fn set_super_user(client: &impl GenericClient, user: &User) -> Result<User, MyError> {
+    set_user_permissions(client, user, UserPermission::Admin.bits()).await
+}
+
+/// Every name `userid` has been seen with, most recent first.
+pub async fn get_username_history(
+    client: &impl GenericClient,
+    userid: i64,
+) -> Result<Vec<UsernameHistory>, MyError> {
+    query_all(
+        client,
+        sql!(
+            UsernameHistory,
+            "SELECT $table_fields FROM username_history WHERE userid=$1 ORDER BY last_seen DESC"
+        ),
+        &[&userid],
+    )
+    .await
+}
+
+/// Record that `userid` went by `username` at `seen_at`,
+/// extending the last seen time if we've seen this name before.
+pub async fn record_username(
+    client: &impl GenericClient,
+    userid: i64,
+    username: &str,
+    seen_at: DateTime<Utc>,
+) -> Result<(), MyError> {
+    execute(
+        client,
+        "INSERT INTO username_history(userid, username, first_seen, last_seen) \
+        VALUES ($1, $2, $3, $3) \
+        ON CONFLICT (userid, username) \
+        DO UPDATE SET last_seen = GREATEST(username_history.last_seen, EXCLUDED.last_seen)",
+        &[&userid, &username, &seen_at],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Get the user's current authorization, or make them a new one if they don't have one.
+pub async fn get_authorization_for_user(
+    client: &impl GenericClient,
+    user: &User,
+) -> Result<Authorization, MyError> {
+    let existing = query_opt(
+        client,
+        sql!(Authorization, include_str!("../../sql/get_auth_token.sql")),
+        &[&user.id],
+    )
+    .await?;
+
+    match existing {
+        Some(auth) => Ok(auth),
+        None => create_authorization_for_user(client, user).await,
+    }
+}
+
+pub async fn register_authorization(
+    client: &impl GenericClient,
+    token: &str,
+    user: &User,
+    expiry: DateTime<Utc>,
+) -> Result<Authorization, MyError> {
+    log::debug!("Registering authorization {token} for {0}", &user.id);
+    let time_now = chrono::offset::Utc::now();
+
+    query_one(
+        client,
+        sql!(
+            Authorization,
+            include_str!("../../sql/register_auth_token.sql")
+        ),
+        &[&user.id, &token, &time_now, &expiry],
+    )
+    .await
+}
+
+pub async fn revoke_user_authorization(
+    client: &impl GenericClient,
+    user: &User,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "DELETE FROM authorizations WHERE userid=$1;",
+        &[&user.id],
+    )
+    .await
+}
+
+/// Ban `userid` from playing in league `leagueid`, because of ticket `ticketid` if there was one.
+pub async fn add_ban(
+    client: &impl GenericClient,
+    userid: i64,
+    leagueid: i64,
+    ticketid: Option<i64>,
+    banned_by: i64,
+) -> Result<u64, MyError> {
+    execute(
+        client,
+        "INSERT INTO user_bans(userid, leagueid, ticketid, banned_by, created_at) \
+        VALUES ($1, $2, $3, $4, $5)",
+        &[
+            &userid,
+            &leagueid,
+            &ticketid,
+            &banned_by,
+            &chrono::offset::Utc::now(),
+        ],
+    )
+    .await
+}
+
+/// Which of `userids` are banned from league `leagueid`.
+pub async fn get_banned_user_ids(
+    client: &impl GenericClient,
+    leagueid: i64,
+    userids: &[i64],
+) -> Result<Vec<i64>, MyError> {
+    query_scalar(
+        client,
+        "SELECT COALESCE(array_agg(DISTINCT userid), '{}') FROM user_bans \
+        WHERE leagueid=$1 AND userid = ANY($2)",
+        &[&leagueid, &userids],
+    )
+    .await
+}
+
+pub async fn add_review_flag(
+    client: &impl GenericClient,
+    userid: i64,
+    reasons: &[String],
+) -> Result<UserReviewFlag, MyError> {
+    query_one(
+        client,
+        sql!(
+            UserReviewFlag,
+            "INSERT INTO user_review_flags(userid, reasons, created_at) \
+            VALUES ($1, $2, $3) \
+            RETURNING $table_fields"
+        ),
+        &[&userid, &reasons, &chrono::offset::Utc::now()],
+    )
+    .await
+}
+
+/// Every flag no admin has looked at yet, oldest first.
+pub async fn get_open_review_flags(
+    client: &impl GenericClient,
+) -> Result<Vec<UserReviewFlag>, MyError> {
+    query_all(
+        client,
+        sql!(
+            UserReviewFlag,
+            "SELECT $table_fields FROM user_review_flags \
+            WHERE reviewed_at IS NULL \
+            ORDER BY created_at ASC"
+        ),
+        &[],
+    )
+    .await
+}
+
+/// Which of `userids` have a review flag nobody has looked at yet.
+pub async fn get_flagged_user_ids(
+    client: &impl GenericClient,
+    userids: &[i64],
+) -> Result<Vec<i64>, MyError> {
+    query_scalar(
+        client,
+        "SELECT COALESCE(array_agg(DISTINCT userid), '{}') FROM user_review_flags \
+        WHERE reviewed_at IS NULL AND userid = ANY($1)",
+        &[&userids],
+    )
+    .await
+}
+
+pub async fn mark_review_flag_reviewed(
+    client: &impl GenericClient,
+    flagid: i64,
+    reviewerid: i64,
+) -> Result<UserReviewFlag, MyError> {
+    query_one(
+        client,
+        sql!(
+            UserReviewFlag,
+            "UPDATE user_review_flags \
+            SET reviewed_at=$1, reviewed_by=$2 \
+            WHERE id=$3 AND reviewed_at IS NULL \
+            RETURNING $table_fields"
+        ),
+        &[&chrono::offset::Utc::now(), &reviewerid, &flagid],
+    )
+    .await
+}
@@ -0,0 +1,51 @@
+// Background task that deletes demos once they're older than DEMO_RETENTION_DAYS.
+use std::path::{Path, PathBuf};
+
+use chrono::TimeDelta;
+use deadpool_postgres::Pool;
+use tokio::time::{Duration, Instant};
+
+use crate::db;
+use crate::errors::MyError;
+use crate::models::GameDemo;
+
+/// How often to look for expired demos.
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Delete demos older than `retention` every hour, forever.
+pub async fn run(pool: Pool, demo_dir: PathBuf, retention: TimeDelta) {
+    let mut ticker = tokio::time::interval_at(Instant::now() + INTERVAL, INTERVAL);
+    loop {
+        ticker.tick().await;
+        match clean_up(&pool, &demo_dir, retention).await {
+            Ok(0) => (),
+            Ok(deleted) => log::info!("Deleted {deleted} expired demos"),
+            Err(err) => log::warn!("Deleting expired demos failed: {err:?}"),
+        }
+    }
+}
+
+/// Delete every demo uploaded more than `retention` ago, and its file, unless a ticket about
+/// its game is still open. Returns how many were deleted.
+pub async fn clean_up(
+    pool: &Pool,
+    demo_dir: &Path,
+    retention: TimeDelta,
+) -> Result<usize, MyError> {
+    let client = pool.get().await?;
+    let before = chrono::offset::Utc::now() - retention;
+    let expired = db::demos::delete_demos_before(&client, before).await?;
+    remove_files(demo_dir, &expired).await;
+    Ok(expired.len())
+}
+
+/// Remove the files of `demos`, which were already deleted from the database.
+pub async fn remove_files(demo_dir: &Path, demos: &[GameDemo]) {
+    for demo in demos {
+        match tokio::fs::remove_file(demo_dir.join(&demo.stored_name)).await {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => log::warn!("Couldn't remove demo {0}: {err}", demo.stored_name),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Checking whether a Steam account may sign up, to keep alt accounts out.
+use chrono::Utc;
+use derive_more::Display;
+use serde::Serialize;
+
+use crate::config::EligibilityConfig;
+use crate::steamapi::{ApiError, PlayerSummaryAccess, SteamApi};
+
+const TF2_APPID: u32 = 440;
+
+/// Why an account failed the eligibility rules.
+#[derive(Debug, Display, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum IneligibilityReason {
+    #[display("account is {age_days} days old, needs to be {required_days}")]
+    AccountTooNew { age_days: i64, required_days: u32 },
+    #[display("account age couldn't be checked")]
+    AccountAgeUnknown,
+    #[display("profile is not public")]
+    PrivateProfile,
+    #[display("game details are not public")]
+    GamesHidden,
+    #[display("does not own TF2")]
+    NoTf2,
+    #[display("has {hours} hours of TF2, needs {required_hours}")]
+    NotEnoughTf2Hours { hours: u64, required_hours: u32 },
+    #[display("VAC banned {days_since_last_ban} days ago")]
+    VacBanned { days_since_last_ban: u32 },
+    #[display("game banned {days_since_last_ban} days ago")]
+    GameBanned { days_since_last_ban: u32 },
+}
+
+/// Check `summary`, the account trying to sign up, against every rule in `config`.
+///
+/// Only asks Steam for owned games and bans if a rule needs them.
+/// An empty list means the account is eligible.
+pub async fn check_eligibility(
+    config: &EligibilityConfig,
+    steam: &dyn SteamApi,
+    summary: &PlayerSummaryAccess,
+) -> Result<Vec<IneligibilityReason>, ApiError> {
+    let mut reasons = Vec::new();
+
+    let (public, private) = match summary {
+        PlayerSummaryAccess::All { public, private } => (public, Some(private)),
+        PlayerSummaryAccess::Private { public } => (public, None),
+    };
+    let steamid = public.steamid.as_str();
+
+    if config.require_public_profile && private.is_none() {
+        reasons.push(IneligibilityReason::PrivateProfile);
+    }
+
+    if config.min_account_age_days > 0 {
+        match private.and_then(|private| private.timecreated) {
+            Some(timecreated) => {
+                let age_days = (Utc::now().timestamp() - timecreated) / (60 * 60 * 24);
+                if age_days < i64::from(config.min_account_age_days) {
+                    reasons.push(IneligibilityReason::AccountTooNew {
+                        age_days,
+                        required_days: config.min_account_age_days,
+                    });
+                }
+            }
+            None => reasons.push(IneligibilityReason::AccountAgeUnknown),
+        }
+    }
+
+    if config.require_tf2 || config.min_tf2_hours > 0 {
+        match steam.get_owned_games(steamid).await? {
+            None => reasons.push(IneligibilityReason::GamesHidden),
+            Some(games) => match games.iter().find(|game| game.appid == TF2_APPID) {
+                None => reasons.push(IneligibilityReason::NoTf2),
+                Some(tf2) => {
+                    let hours = tf2.playtime_forever / 60;
+                    if hours < u64::from(config.min_tf2_hours) {
+                        reasons.push(IneligibilityReason::NotEnoughTf2Hours {
+                            hours,
+                            required_hours: config.min_tf2_hours,
+                        });
+                    }
+                }
+            },
+        }
+    }
+
+    if config.reject_vac_banned || config.reject_game_banned {
+        let bans = steam.get_player_bans(steamid).await?;
+        let recent =
+            config.ban_lookback_days == 0 || bans.days_since_last_ban <= config.ban_lookback_days;
+        let days_since_last_ban = bans.days_since_last_ban;
+
+        if config.reject_vac_banned && bans.vac_banned && recent {
+            reasons.push(IneligibilityReason::VacBanned {
+                days_since_last_ban,
+            });
+        }
+        if config.reject_game_banned && bans.number_of_game_bans > 0 && recent {
+            reasons.push(IneligibilityReason::GameBanned {
+                days_since_last_ban,
+            });
+        }
+    }
+
+    Ok(reasons)
+}
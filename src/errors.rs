@@ -1,29 +1,136 @@
 // Errors that our crate may throw.
+//
+// Every error the API hands back is JSON shaped like `{code, message, details}`,
+// where `code` is stable and meant for machines, and `message` is meant for people.
+// Internal errors are logged and never shown to the client as-is.
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use deadpool_postgres::PoolError;
 use derive_more::{Display, Error, From};
+use serde::Serialize;
 use tokio_pg_mapper::Error as PGMError;
 use tokio_postgres::error::Error as PGError;
 
+use crate::apiv1::validation::FieldError;
+use crate::eligibility::IneligibilityReason;
+use crate::logstf::LogsError;
 use crate::steamapi::ApiError;
 
 #[derive(Debug, Display, Error, From)]
 pub enum MyError {
+    #[display("Not found")]
     NotFound,
+    /// Something in the request was malformed or didn't make sense.
+    #[from(ignore)]
+    #[display("{_0}")]
+    Validation(#[error(not(source))] String),
+    /// Fields in the request body broke the rules in [crate::apiv1::validation].
+    #[from(ignore)]
+    #[display("Some fields are invalid")]
+    InvalidFields(#[error(not(source))] Vec<FieldError>),
+    /// There is no session, or it isn't valid anymore.
+    #[from(ignore)]
+    #[display("{_0}")]
+    Unauthorized(#[error(not(source))] &'static str),
+    /// The session is valid, but isn't allowed to do this.
+    #[from(ignore)]
+    #[display("{_0}")]
+    Forbidden(#[error(not(source))] &'static str),
+    /// The request clashes with what's already there, e.g. signing up twice.
+    #[from(ignore)]
+    #[display("{_0}")]
+    Conflict(#[error(not(source))] String),
     ExternalApiError(ApiError),
+    /// logs.tf couldn't be reached, or gave back something we don't understand.
+    LogsApiError(LogsError),
     PGError(PGError),
     PGMError(PGMError),
     PoolError(PoolError),
+    IoError(std::io::Error),
+    /// The Steam account trying to sign up broke the eligibility rules.
+    #[from(ignore)]
+    #[display("Ineligible")]
+    Ineligible(#[error(not(source))] Vec<IneligibilityReason>),
+}
+
+/// The body of every error response.
+#[derive(Serialize, Debug)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl MyError {
+    /// A single [MyError::InvalidFields], for rules that need more than the body to check.
+    pub fn invalid_field(field: &str, message: impl Into<String>) -> Self {
+        MyError::InvalidFields(vec![FieldError {
+            field: field.to_owned(),
+            message: message.into(),
+        }])
+    }
+
+    /// The stable, machine readable name for this kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MyError::NotFound => "not_found",
+            MyError::Validation(_) | MyError::InvalidFields(_) => "validation_failed",
+            MyError::Unauthorized(_) => "unauthorized",
+            MyError::Forbidden(_) => "forbidden",
+            MyError::Conflict(_) => "conflict",
+            MyError::Ineligible(_) => "ineligible",
+            MyError::ExternalApiError(_) => "steam_unavailable",
+            MyError::LogsApiError(_) => "logs_unavailable",
+            MyError::PGError(_)
+            | MyError::PGMError(_)
+            | MyError::PoolError(_)
+            | MyError::IoError(_) => "internal_error",
+        }
+    }
+
+    fn body(&self) -> ErrorBody {
+        let (message, details) = match self {
+            MyError::Ineligible(reasons) => (
+                "This Steam account isn't eligible to sign up".to_owned(),
+                serde_json::to_value(reasons).ok(),
+            ),
+            MyError::InvalidFields(fields) => (self.to_string(), serde_json::to_value(fields).ok()),
+            MyError::ExternalApiError(_) => ("Couldn't reach Steam".to_owned(), None),
+            MyError::LogsApiError(_) => ("Couldn't get the log from logs.tf".to_owned(), None),
+            MyError::PGError(_)
+            | MyError::PGMError(_)
+            | MyError::PoolError(_)
+            | MyError::IoError(_) => ("Internal server error".to_owned(), None),
+            other => (other.to_string(), None),
+        };
+        ErrorBody {
+            code: self.code(),
+            message,
+            details,
+        }
+    }
 }
 
 impl ResponseError for MyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            MyError::NotFound => StatusCode::NOT_FOUND,
+            MyError::Validation(_) | MyError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            MyError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            MyError::Forbidden(_) | MyError::Ineligible(_) => StatusCode::FORBIDDEN,
+            MyError::Conflict(_) => StatusCode::CONFLICT,
+            MyError::ExternalApiError(_) | MyError::LogsApiError(_) => StatusCode::BAD_GATEWAY,
+            MyError::PGError(_)
+            | MyError::PGMError(_)
+            | MyError::PoolError(_)
+            | MyError::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
-        match *self {
-            MyError::NotFound => HttpResponse::NotFound().finish(),
-            MyError::PoolError(ref err) => {
-                HttpResponse::InternalServerError().body(err.to_string())
-            }
-            _ => HttpResponse::InternalServerError().finish(),
+        if self.status_code().is_server_error() {
+            log::error!("Responding with {self:?}");
         }
+        HttpResponse::build(self.status_code()).json(self.body())
     }
 }
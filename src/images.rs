@@ -0,0 +1,245 @@
+// Recognising uploaded images from their headers, and shrinking them down to size.
+use std::io::Cursor;
+
+use derive_more::Display;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageReader, Limits};
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    #[display("PNG")]
+    Png,
+    #[display("JPEG")]
+    Jpeg,
+    #[display("WebP")]
+    Webp,
+}
+
+impl ImageFormat {
+    /// Work out the format from the file's magic bytes.
+    pub fn sniff(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_mime(mime: &str) -> Option<ImageFormat> {
+        match mime {
+            "image/png" => Some(ImageFormat::Png),
+            "image/jpeg" => Some(ImageFormat::Jpeg),
+            "image/webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    fn to_image_format(self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+        }
+    }
+
+    /// Decode the whole image, using no more than `max_bytes` of memory for its pixels.
+    pub fn decode(self, bytes: &[u8], max_bytes: u64) -> Result<DynamicImage, ImageError> {
+        let mut reader = ImageReader::with_format(Cursor::new(bytes), self.to_image_format());
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(max_bytes);
+        reader.limits(limits);
+        reader.decode()
+    }
+
+    /// Encode `image` in this format. WebP is encoded losslessly.
+    pub fn encode(self, image: &DynamicImage) -> Result<Vec<u8>, ImageError> {
+        let mut bytes = Cursor::new(Vec::new());
+        // Neither JPEG nor lossless WebP can hold every pixel format, so convert to one they can.
+        let image = match self {
+            ImageFormat::Png => image.clone(),
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+            ImageFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8()),
+        };
+        image.write_to(&mut bytes, self.to_image_format())?;
+        Ok(bytes.into_inner())
+    }
+
+    /// Width and height in pixels, read from the header.
+    /// `None` if the header is cut short or malformed.
+    pub fn dimensions(self, bytes: &[u8]) -> Option<(u32, u32)> {
+        match self {
+            ImageFormat::Png => png_dimensions(bytes),
+            ImageFormat::Jpeg => jpeg_dimensions(bytes),
+            ImageFormat::Webp => webp_dimensions(bytes),
+        }
+    }
+}
+
+/// Scale `image` down to fit in a `max_side` square, keeping its aspect ratio.
+/// `None` if it already fits.
+pub fn shrink_to_fit(image: &DynamicImage, max_side: u32) -> Option<DynamicImage> {
+    if image.width() <= max_side && image.height() <= max_side {
+        return None;
+    }
+    Some(image.resize(max_side, max_side, FilterType::Lanczos3))
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 2)?;
+    Some(u32::from(u16::from_be_bytes([b[0], b[1]])))
+}
+
+fn le_u24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+}
+
+fn png_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    // The IHDR chunk always comes first, right after the signature.
+    if bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(bytes.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(bytes.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    // Walk the segments until a start of frame, which holds the size.
+    let mut at = 2;
+    loop {
+        if *bytes.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(at + 1)?;
+        match marker {
+            // Padding before a marker.
+            0xFF => at += 1,
+            // Markers without a length.
+            0x01 | 0xD0..=0xD8 => at += 2,
+            // Start of frame, except DHT, JPG and DAC which share the range.
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = be_u16(bytes, at + 5)?;
+                let width = be_u16(bytes, at + 7)?;
+                return Some((width, height));
+            }
+            _ => at += 2 + usize::try_from(be_u16(bytes, at + 2)?).ok()?,
+        }
+    }
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match bytes.get(12..16)? {
+        // Lossy: a keyframe header after the 3 byte frame tag.
+        b"VP8 " => {
+            if bytes.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            let width = u32::from(u16::from_le_bytes(bytes.get(26..28)?.try_into().ok()?));
+            let height = u32::from(u16::from_le_bytes(bytes.get(28..30)?.try_into().ok()?));
+            Some((width & 0x3FFF, height & 0x3FFF))
+        }
+        // Lossless: 14 bits each of width and height, minus one.
+        b"VP8L" => {
+            if *bytes.get(20)? != 0x2F {
+                return None;
+            }
+            let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        // Extended: 24 bits each of canvas width and height, minus one.
+        b"VP8X" => Some((le_u24(bytes, 24)? + 1, le_u24(bytes, 27)? + 1)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn png() {
+        let bytes = png_header(300, 200);
+        assert_eq!(ImageFormat::sniff(&bytes), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::Png.dimensions(&bytes), Some((300, 200)));
+        assert_eq!(ImageFormat::Png.dimensions(&bytes[..20]), None);
+    }
+
+    #[test]
+    fn jpeg() {
+        let mut bytes = vec![0xFF, 0xD8];
+        // An APP0 segment to skip over, then a baseline start of frame.
+        bytes.extend([0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46]);
+        bytes.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0x00, 0x02, 0x80, 0x03]);
+        assert_eq!(ImageFormat::sniff(&bytes), Some(ImageFormat::Jpeg));
+        assert_eq!(ImageFormat::Jpeg.dimensions(&bytes), Some((640, 256)));
+        assert_eq!(ImageFormat::Jpeg.dimensions(&bytes[..10]), None);
+    }
+
+    #[test]
+    fn webp() {
+        let mut lossy = b"RIFF\0\0\0\0WEBPVP8 \0\0\0\0".to_vec();
+        lossy.extend([0x30, 0x01, 0x00, 0x9D, 0x01, 0x2A]);
+        lossy.extend(400u16.to_le_bytes());
+        lossy.extend(300u16.to_le_bytes());
+        assert_eq!(ImageFormat::sniff(&lossy), Some(ImageFormat::Webp));
+        assert_eq!(ImageFormat::Webp.dimensions(&lossy), Some((400, 300)));
+
+        let mut lossless = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2F".to_vec();
+        let bits: u32 = (128 - 1) | ((64 - 1) << 14);
+        lossless.extend(bits.to_le_bytes());
+        assert_eq!(ImageFormat::Webp.dimensions(&lossless), Some((128, 64)));
+
+        let mut extended = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0".to_vec();
+        extended.extend([0xFF, 0x01, 0x00, 0x1F, 0x00, 0x00]);
+        assert_eq!(ImageFormat::Webp.dimensions(&extended), Some((512, 32)));
+    }
+
+    #[test]
+    fn shrinks_big_images() {
+        let image = DynamicImage::new_rgba8(2000, 500);
+        let shrunk = shrink_to_fit(&image, 1000).unwrap();
+        assert_eq!((shrunk.width(), shrunk.height()), (1000, 250));
+        assert!(shrink_to_fit(&shrunk, 1000).is_none());
+    }
+
+    #[test]
+    fn round_trips() {
+        let image = DynamicImage::new_rgba8(40, 30);
+        for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::Webp] {
+            let bytes = format.encode(&image).unwrap();
+            assert_eq!(ImageFormat::sniff(&bytes), Some(format));
+            assert_eq!(format.dimensions(&bytes), Some((40, 30)));
+            let decoded = format.decode(&bytes, 1 << 20).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (40, 30));
+            assert!(format.decode(&bytes, 100).is_err());
+        }
+    }
+
+    #[test]
+    fn other_files() {
+        assert_eq!(ImageFormat::sniff(b"GIF89a"), None);
+        assert_eq!(ImageFormat::sniff(b"<svg></svg>"), None);
+        assert_eq!(ImageFormat::sniff(b""), None);
+    }
+}
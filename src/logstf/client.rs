@@ -0,0 +1,53 @@
+// A client for logs.tf's public JSON API.
+use async_trait::async_trait;
+use tokio::time::Duration;
+
+use super::{parse_log, Log, LogsError};
+
+const LOGS_TF_ROOT: &str = "https://logs.tf";
+
+/// Everything we ask of logs.tf.
+///
+/// Routes should only talk to logs.tf through this trait (via `AppState::logs`),
+/// so that tests can swap in a fake.
+#[async_trait]
+pub trait LogsApi: Send + Sync {
+    /// The log with id `logid`. Errors with [LogsError::NotFound] if there isn't one.
+    async fn get_log(&self, logid: i64) -> Result<Log, LogsError>;
+}
+
+/// The real [LogsApi].
+pub struct LogsTfClient {
+    http: reqwest::Client,
+}
+
+impl LogsTfClient {
+    pub fn new(timeout: Duration) -> Result<Self, LogsError> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .https_only(true)
+            .build()?;
+        Ok(LogsTfClient { http })
+    }
+}
+
+#[async_trait]
+impl LogsApi for LogsTfClient {
+    async fn get_log(&self, logid: i64) -> Result<Log, LogsError> {
+        log::debug!("Fetching log {logid} from logs.tf");
+        let resp = self
+            .http
+            .get(format!("{LOGS_TF_ROOT}/json/{logid}"))
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(LogsError::NotFound);
+        }
+        if !status.is_success() {
+            return Err(LogsError::Status(status.as_u16()));
+        }
+        parse_log(&resp.bytes().await?)
+    }
+}
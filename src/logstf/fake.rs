@@ -0,0 +1,31 @@
+// A stand-in for logs.tf, for tests.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{Log, LogsApi, LogsError};
+
+/// Answers with logs added with [FakeLogs::add_log], and never touches the network.
+#[derive(Default)]
+pub struct FakeLogs {
+    logs: Mutex<HashMap<i64, Log>>,
+}
+
+impl FakeLogs {
+    pub fn add_log(&self, logid: i64, log: Log) {
+        self.logs.lock().unwrap().insert(logid, log);
+    }
+}
+
+#[async_trait]
+impl LogsApi for FakeLogs {
+    async fn get_log(&self, logid: i64) -> Result<Log, LogsError> {
+        self.logs
+            .lock()
+            .unwrap()
+            .get(&logid)
+            .cloned()
+            .ok_or(LogsError::NotFound)
+    }
+}
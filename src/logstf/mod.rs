@@ -0,0 +1,178 @@
+// Match logs in logs.tf's JSON format, and the PASS Time stats we keep from them.
+//
+// We only read what we store: which side everyone played on, their deaths,
+// and the `passtime` block PASS Time servers add to each player.
+// Players are keyed by SteamID3 (`[U:1:64774718]`), as logs.tf writes them.
+use std::collections::HashMap;
+
+use derive_more::{Display, Error, From};
+use serde::{Deserialize, Serialize};
+
+use crate::steamid::SteamId;
+
+mod client;
+#[cfg(test)]
+pub mod fake;
+
+pub use client::{LogsApi, LogsTfClient};
+
+/// An error caused by our interacting with logs.tf.
+#[derive(Debug, Display, Error, From)]
+pub enum LogsError {
+    Reqwest(reqwest::Error),
+    /// logs.tf has no log with that id.
+    NotFound,
+    Serde(serde_json::Error),
+    /// logs.tf answered with a non-success status code.
+    #[from(ignore)]
+    Status(#[error(not(source))] u16),
+}
+
+/// The sides of a log, as logs.tf names them.
+pub const SIDES: [&str; 2] = ["Red", "Blue"];
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct LogInfo {
+    #[serde(default)]
+    pub map: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+/// What one player did in PASS Time.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PassTimeStats {
+    pub goals: i32,
+    pub assists: i32,
+    pub intercepts: i32,
+    pub steals: i32,
+    pub passes: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LogPlayer {
+    /// One of [SIDES].
+    pub team: String,
+    #[serde(default)]
+    pub deaths: i32,
+    #[serde(default)]
+    pub passtime: PassTimeStats,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Log {
+    #[serde(default)]
+    pub info: LogInfo,
+    /// Keyed by SteamID3.
+    pub players: HashMap<String, LogPlayer>,
+    /// The names players went by in the log, keyed like `players`.
+    #[serde(default)]
+    pub names: HashMap<String, String>,
+}
+
+/// A player from a log, with their SteamID parsed.
+pub struct SidePlayer<'a> {
+    /// The key they had in the log.
+    pub key: &'a str,
+    pub steamid: SteamId,
+    pub stats: &'a LogPlayer,
+}
+
+/// Why a log can't be split into its two sides.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SideError {
+    /// The player with this key doesn't have a SteamID we understand.
+    BadSteamId(String),
+    /// The player with this key isn't on `Red` or `Blue`.
+    UnknownSide(String),
+    /// One of the sides has nobody on it.
+    EmptySide(&'static str),
+}
+
+pub fn parse_log(body: &[u8]) -> Result<Log, LogsError> {
+    Ok(serde_json::from_slice(body)?)
+}
+
+impl Log {
+    /// Everyone in the log, by side, in the order of [SIDES]. Each side is sorted by SteamID.
+    pub fn sides(&self) -> Result<[Vec<SidePlayer<'_>>; 2], SideError> {
+        let mut sides = [Vec::new(), Vec::new()];
+        for (key, stats) in &self.players {
+            let steamid = key
+                .parse::<SteamId>()
+                .map_err(|_| SideError::BadSteamId(key.clone()))?;
+            let Some(side) = SIDES.iter().position(|side| *side == stats.team) else {
+                return Err(SideError::UnknownSide(key.clone()));
+            };
+            sides[side].push(SidePlayer {
+                key,
+                steamid,
+                stats,
+            });
+        }
+        for (side, players) in sides.iter_mut().enumerate() {
+            if players.is_empty() {
+                return Err(SideError::EmptySide(SIDES[side]));
+            }
+            players.sort_by_key(|player| player.steamid);
+        }
+        Ok(sides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"{
+        "info": {"map": "pass_arena2", "title": "Speakers vs Rivals"},
+        "players": {
+            "[U:1:64774718]": {"team": "Red", "deaths": 3, "passtime": {"goals": 2, "passes": 7}},
+            "[U:1:2]": {"team": "Blue", "deaths": 1, "kills": 4},
+            "[U:1:3]": {"team": "Blue", "passtime": {"steals": 1, "intercepts": 2, "assists": 1}}
+        },
+        "names": {"[U:1:64774718]": "lucy"}
+    }"#;
+
+    #[test]
+    fn reads_passtime_stats() {
+        let log = parse_log(LOG.as_bytes()).unwrap();
+        assert_eq!(log.info.map, "pass_arena2");
+        let [red, blue] = log.sides().unwrap();
+        assert_eq!(red.len(), 1);
+        assert_eq!(red[0].steamid.steamid64(), 76561198025040446);
+        assert_eq!(red[0].stats.deaths, 3);
+        assert_eq!(red[0].stats.passtime.goals, 2);
+        assert_eq!(red[0].stats.passtime.passes, 7);
+        // Missing stats are zero.
+        assert_eq!(blue[0].stats.passtime, PassTimeStats::default());
+        assert_eq!(blue[1].stats.passtime.intercepts, 2);
+        assert_eq!(blue[1].stats.deaths, 0);
+    }
+
+    #[test]
+    fn rejects_odd_logs() {
+        assert!(parse_log(b"not json").is_err());
+        assert!(parse_log(b"{\"info\": {}}").is_err());
+
+        let mut log = parse_log(LOG.as_bytes()).unwrap();
+        log.players.get_mut("[U:1:2]").unwrap().team = "Spectator".to_owned();
+        assert_eq!(
+            log.sides().err(),
+            Some(SideError::UnknownSide("[U:1:2]".to_owned()))
+        );
+
+        let mut log = parse_log(LOG.as_bytes()).unwrap();
+        log.players.retain(|_, player| player.team == "Blue");
+        assert_eq!(log.sides().err(), Some(SideError::EmptySide("Red")));
+
+        let mut log = parse_log(LOG.as_bytes()).unwrap();
+        let player = log.players.remove("[U:1:2]").unwrap();
+        log.players.insert("BOT".to_owned(), player);
+        assert_eq!(
+            log.sides().err(),
+            Some(SideError::BadSteamId("BOT".to_owned()))
+        );
+    }
+}
@@ -34,14 +34,25 @@ mod apiv1;
 mod authorization;
 mod config;
 mod db;
+mod demos;
+mod eligibility;
 mod errors;
+mod images;
+mod logstf;
 mod models;
 mod openid;
 mod permission;
+mod ratings;
+mod scheduling;
 mod steamapi;
+mod steamid;
+mod steamsync;
 
+use self::apiv1::validation::{NameFilter, WordBlocklist};
 use self::apiv1::*;
-use self::steamapi::PlayerSummaryAccess;
+use self::logstf::LogsApi;
+use self::steamapi::{PlayerSummaryAccess, SteamApi};
+use std::sync::Arc;
 
 #[derive(Debug)]
 struct CurrentHost {
@@ -116,6 +127,30 @@ async fn main() -> io::Result<()> {
     let steam_setup = openid::SteamOpenId::new(steam_config, config.clone());
     let auth_url = steam_setup.get_auth_url();
 
+    log::trace!("Creating a Steam API client");
+    let mut steam_config = steamapi::SteamClientConfig::new(config.steam_api_key.clone());
+    steam_config.timeout = Duration::from_secs(config.steam_api_timeout_secs);
+    steam_config.requests_per_second = config.steam_api_requests_per_second;
+    steam_config.summary_ttl = Duration::from_secs(config.steam_summary_cache_secs);
+    let steam: Arc<dyn SteamApi> =
+        Arc::new(steamapi::SteamClient::new(steam_config).expect("Error building Steam client"));
+    let logs: Arc<dyn LogsApi> = Arc::new(
+        logstf::LogsTfClient::new(Duration::from_secs(config.logs_tf_timeout_secs))
+            .expect("Error building logs.tf client"),
+    );
+
+    let name_filter: Arc<dyn NameFilter> = match &config.name_blocklist_file {
+        Some(path) => {
+            Arc::new(WordBlocklist::from_file(path).expect("Error reading NAME_BLOCKLIST_FILE"))
+        }
+        None => Arc::new(WordBlocklist::default()),
+    };
+
+    let upload_dir = std::path::PathBuf::from(&config.upload_dir);
+    std::fs::create_dir_all(upload_dir.join(apiv1::teams::LOGO_DIR))?;
+    let demo_dir = std::path::PathBuf::from(&config.demo_dir);
+    std::fs::create_dir_all(&demo_dir)?;
+
     log::trace!("Creating a database pool using deadpool_postgres");
     let pool = config.pg.create_pool(None, NoTls).unwrap();
 
@@ -125,7 +160,7 @@ async fn main() -> io::Result<()> {
     let client = pool.get().await.unwrap();
     let stmt = client.prepare(stmt_text).await.unwrap();
     let rows = client.query(&stmt, &[]).await.unwrap();
-    let value: bool = rows.get(0).expect("should have one row returned").get(0);
+    let value: bool = rows.first().expect("should have one row returned").get(0);
     log::debug!("Return from Postgres: {value}");
     if !value {
         let ans = match inquire::Confirm::new(
@@ -149,8 +184,11 @@ async fn main() -> io::Result<()> {
         };
 
         if ans {
-            db::initdb(&client).await.unwrap();
+            db::migrate(&client).await.unwrap();
         }
+    } else {
+        log::info!("Bringing the database schema up to date");
+        db::migrate(&client).await.unwrap();
     }
 
     log::debug!("Checking if users table has any entries");
@@ -196,6 +234,37 @@ async fn main() -> io::Result<()> {
         _ => panic!("invalid argument provided to --cors"),
     };
 
+    if config.steam_sync_interval_secs > 0 {
+        log::info!(
+            "Syncing Steam profiles every {0} seconds",
+            config.steam_sync_interval_secs
+        );
+        actix_web::rt::spawn(steamsync::run(
+            pool.clone(),
+            steam.clone(),
+            Duration::from_secs(config.steam_sync_interval_secs),
+        ));
+    }
+    if config.schedule_check_interval_secs > 0 {
+        log::info!(
+            "Settling overdue game schedules every {0} seconds",
+            config.schedule_check_interval_secs
+        );
+        actix_web::rt::spawn(scheduling::run(
+            pool.clone(),
+            Duration::from_secs(config.schedule_check_interval_secs),
+        ));
+    }
+
+    if config.demo_retention_days > 0 {
+        log::info!("Deleting demos after {0} days", config.demo_retention_days);
+        actix_web::rt::spawn(demos::run(
+            pool.clone(),
+            demo_dir.clone(),
+            chrono::TimeDelta::days(config.demo_retention_days as i64),
+        ));
+    }
+
     let workers: usize = if debug {
         2
     } else {
@@ -214,24 +283,19 @@ async fn main() -> io::Result<()> {
                 },
                 pool: pool.clone(),
                 steam_auth_url: auth_url.clone(),
-                steam_api_key: config.steam_api_key.clone(),
+                steam: steam.clone(),
+                logs: logs.clone(),
                 root_user_steamid: config.root_user_steamid.clone(),
+                auth_mode: config.auth_mode,
+                session_cookie_domain: config.session_cookie_domain.clone(),
+                eligibility: config.eligibility.clone(),
+                name_filter: name_filter.clone(),
+                upload_dir: upload_dir.clone(),
+                demo_dir: demo_dir.clone(),
+                max_demo_bytes: config.max_demo_size_mib * 1024 * 1024,
             }))
-            .service(teams::get_team)
-            .service(teams::get_team_div_assoc)
-            .service(teams::post_team)
-            .service(add_teams::post_team_to_league)
-            .service(users::get_user_from_steamid)
-            .service(users::get_user_from_auth_token)
-            .service(users::get_users_paged)
-            .service(users::search_users)
-            // .service(admin::add_user) // this is unauthenticated...
-            .service(leagues::get_league)
-            .service(leagues::get_all_leagues)
-            .service(admin::post_league)
-            .service(admin::post_league_divisions)
-            .service(verify_openid_login)
-            .service(logout)
+            .configure(apiv1::configure)
+            .service(actix_files::Files::new("/uploads", &upload_dir))
     })
     .keep_alive(Duration::from_secs(0))
     .bind((config.server_addr.clone(), config.server_port))?
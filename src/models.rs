@@ -16,7 +16,7 @@ pub struct MiniUser {
 /// A basic user / player.
 ///
 ///
-#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
 #[pg_mapper(table = "users")] // singular 'user' is a keyword..
 pub struct User {
     /// Postgres' Primary Key, for all intents and purposes use `steamid`.
@@ -27,6 +27,31 @@ pub struct User {
     pub steamid: String,
     pub username: String,
     pub created_at: DateTime<Utc>,
+    /// Elo rating, see [crate::ratings].
+    pub rating: f64,
+}
+
+/// A Steam name a user has gone by.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "username_history")]
+pub struct UsernameHistory {
+    pub id: i64,
+    pub userid: i64,
+    pub username: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// An account flagged at signup for failing the eligibility rules.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "user_review_flags")]
+pub struct UserReviewFlag {
+    pub id: i64,
+    pub userid: i64,
+    pub reasons: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<i64>,
 }
 
 impl From<User> for MiniUser {
@@ -45,6 +70,9 @@ pub struct MiniLeague {
     pub name: String,
     pub accepting_teams: bool,
     pub is_hidden: bool,
+    /// The season this league runs in, if any.
+    #[serde(default)]
+    pub seasonid: Option<i64>,
 }
 #[derive(Debug, Deserialize, PostgresMapper, Serialize)]
 #[pg_mapper(table = "division_admins")]
@@ -80,6 +108,95 @@ pub struct League {
     pub accepting_teams: bool,
     pub created_at: DateTime<Utc>,
     pub is_hidden: bool,
+    pub seasonid: Option<i64>,
+    /// When the league was archived, if it has been. Archived leagues don't take sign ups.
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+/// Where a season is in its lifecycle. It only ever moves forward, in this order.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SeasonStatus {
+    Draft,
+    Registration,
+    Active,
+    Playoffs,
+    Completed,
+}
+
+impl SeasonStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SeasonStatus::Draft => "draft",
+            SeasonStatus::Registration => "registration",
+            SeasonStatus::Active => "active",
+            SeasonStatus::Playoffs => "playoffs",
+            SeasonStatus::Completed => "completed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<SeasonStatus> {
+        match status {
+            "draft" => Some(SeasonStatus::Draft),
+            "registration" => Some(SeasonStatus::Registration),
+            "active" => Some(SeasonStatus::Active),
+            "playoffs" => Some(SeasonStatus::Playoffs),
+            "completed" => Some(SeasonStatus::Completed),
+            _ => None,
+        }
+    }
+}
+
+/// A season's name and dates, without the id, status or created_at.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MiniSeason {
+    pub name: String,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub registration_opens_at: Option<DateTime<Utc>>,
+    pub registration_closes_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
+#[pg_mapper(table = "seasons")]
+pub struct Season {
+    pub id: i64,
+    pub name: String,
+    /// One of [SeasonStatus], as a string.
+    pub status: String,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub registration_opens_at: Option<DateTime<Utc>>,
+    pub registration_closes_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// When it moved to [SeasonStatus::Completed], which archived every league still in it.
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl Season {
+    pub fn mini(&self) -> MiniSeason {
+        MiniSeason {
+            name: self.name.clone(),
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            registration_opens_at: self.registration_opens_at,
+            registration_closes_at: self.registration_closes_at,
+        }
+    }
+
+    pub fn status(&self) -> SeasonStatus {
+        // The table's CHECK constraint keeps anything else out.
+        SeasonStatus::parse(&self.status).unwrap_or(SeasonStatus::Draft)
+    }
+
+    /// Whether teams can sign up to this season's leagues at `now`.
+    pub fn is_registering(&self, now: DateTime<Utc>) -> bool {
+        self.status() == SeasonStatus::Registration
+            && self.registration_opens_at.is_none_or(|opens| opens <= now)
+            && self
+                .registration_closes_at
+                .is_none_or(|closes| now < closes)
+    }
 }
 
 #[derive(Debug, Deserialize, PostgresMapper, Serialize)]
@@ -90,7 +207,7 @@ pub struct MiniTeam {
     pub team_tag: String,
 }
 
-#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
 #[pg_mapper(table = "teams")]
 pub struct Team {
     pub id: i64,
@@ -98,6 +215,23 @@ pub struct Team {
     pub team_name: String,
     pub team_tag: String,
     pub created_at: DateTime<Utc>,
+    /// Where the team's logo is served from, relative to the API's root.
+    pub logo_url: Option<String>,
+    /// When the team was disbanded, if it has been.
+    pub disbanded_at: Option<DateTime<Utc>>,
+    /// Elo rating, see [crate::ratings].
+    pub rating: f64,
+}
+
+/// A name and tag a team went by, from `started_at` until the next one.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
+#[pg_mapper(table = "team_name_history")]
+pub struct TeamNameHistory {
+    pub id: i64,
+    pub teamid: i64,
+    pub team_name: String,
+    pub team_tag: String,
+    pub started_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
@@ -111,7 +245,7 @@ pub struct UserTeam {
     pub affiliation: i32,
 }
 
-#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
 #[pg_mapper(table = "teamDivAssociations")]
 pub struct TeamDivAssociation {
     pub id: i64,
@@ -122,6 +256,482 @@ pub struct TeamDivAssociation {
     pub points_down: i64,
     pub created_at: DateTime<Utc>,
     pub is_private: bool,
+    /// The division the team asked to play in when it signed up.
+    pub requested_divisionid: Option<i64>,
+    /// One of [PlacementStatus], as a string.
+    pub placement_status: String,
+    /// Why the roster was placed where it was, from the admin that placed it.
+    pub placement_note: Option<String>,
+    pub placed_at: Option<DateTime<Utc>>,
+    pub placed_by: Option<i64>,
+}
+
+/// Whether a roster still waits for an admin to pick its division.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementStatus {
+    Pending,
+    Placed,
+}
+
+impl PlacementStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PlacementStatus::Pending => "pending",
+            PlacementStatus::Placed => "placed",
+        }
+    }
+}
+
+/// A match between two teams in a league.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Clone)]
+#[pg_mapper(table = "games")]
+pub struct Game {
+    pub id: i64,
+    pub title: Option<String>,
+    pub leagueid: i64,
+    pub teamhomeid: i64,
+    pub teamawayid: i64,
+    pub created_at: DateTime<Utc>,
+    pub played_at: DateTime<Utc>,
+    pub home_score: Option<i32>,
+    pub away_score: Option<i32>,
+    /// When an admin confirmed the score. Only confirmed results count towards ratings.
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// When the teams may play, if they get to agree on a time themselves.
+    pub window_starts_at: Option<DateTime<Utc>>,
+    pub window_ends_at: Option<DateTime<Utc>>,
+    /// After this, the game is played at `played_at` unless a time was agreed.
+    pub schedule_deadline: Option<DateTime<Utc>>,
+    /// When `played_at` was settled, either by agreement or at the deadline.
+    pub time_agreed_at: Option<DateTime<Utc>>,
+}
+
+impl Game {
+    /// When the teams run out of time to agree on a time: the deadline,
+    /// or without one the end of the window.
+    pub fn negotiation_ends_at(&self) -> Option<DateTime<Utc>> {
+        self.schedule_deadline.or(self.window_ends_at)
+    }
+
+    /// Whether the teams can still propose and accept times for this game at `now`.
+    pub fn is_negotiable(&self, now: DateTime<Utc>) -> bool {
+        self.window_starts_at.is_some()
+            && self.window_ends_at.is_some()
+            && self.time_agreed_at.is_none()
+            && self.confirmed_at.is_none()
+            && self.negotiation_ends_at().is_none_or(|ends| now < ends)
+    }
+
+    /// Whether `time` is inside this game's window.
+    pub fn in_window(&self, time: DateTime<Utc>) -> bool {
+        self.window_starts_at.is_none_or(|starts| starts <= time)
+            && self.window_ends_at.is_none_or(|ends| time <= ends)
+    }
+}
+
+/// Where a proposed game time stands.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProposalStatus {
+    Pending,
+    Accepted,
+    /// The other team (or the same one) proposed something else since.
+    Superseded,
+    /// The deadline passed before anyone accepted it.
+    Expired,
+}
+
+impl ProposalStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProposalStatus::Pending => "pending",
+            ProposalStatus::Accepted => "accepted",
+            ProposalStatus::Superseded => "superseded",
+            ProposalStatus::Expired => "expired",
+        }
+    }
+}
+
+/// The log a game's stats came from.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "game_logs")]
+pub struct GameLog {
+    pub id: i64,
+    pub gameid: i64,
+    /// The logs.tf id, if it was fetched from there rather than uploaded.
+    pub logid: Option<i64>,
+    pub map: String,
+    pub title: String,
+    pub red_teamid: i64,
+    pub blue_teamid: i64,
+    pub imported_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MiniGameLog {
+    pub gameid: i64,
+    pub logid: Option<i64>,
+    pub map: String,
+    pub title: String,
+    pub red_teamid: i64,
+    pub blue_teamid: i64,
+    pub imported_by: i64,
+}
+
+/// What one player did in one game, in PASS Time.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "player_game_stats")]
+pub struct PlayerGameStats {
+    pub id: i64,
+    pub gameid: i64,
+    pub userid: i64,
+    /// The team they played for.
+    pub teamid: i64,
+    pub goals: i32,
+    pub assists: i32,
+    pub intercepts: i32,
+    pub steals: i32,
+    pub passes: i32,
+    pub deaths: i32,
+}
+
+/// An STV demo a team uploaded for a game.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "game_demos")]
+pub struct GameDemo {
+    pub id: i64,
+    pub gameid: i64,
+    pub teamid: i64,
+    pub uploaded_by: i64,
+    /// The name it was uploaded with.
+    pub file_name: String,
+    /// Its name inside the demo directory.
+    #[serde(skip_serializing)]
+    pub stored_name: String,
+    pub size_bytes: i64,
+    /// Hex encoded SHA-256 of the file.
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MiniGameDemo {
+    pub gameid: i64,
+    pub teamid: i64,
+    pub uploaded_by: i64,
+    pub file_name: String,
+    pub stored_name: String,
+    pub size_bytes: i64,
+    pub sha256: String,
+}
+
+/// What a ticket is about.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketCategory {
+    Cheating,
+    NoShow,
+    /// Someone played who wasn't allowed to.
+    Ringer,
+    Other,
+}
+
+impl TicketCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TicketCategory::Cheating => "cheating",
+            TicketCategory::NoShow => "no_show",
+            TicketCategory::Ringer => "ringer",
+            TicketCategory::Other => "other",
+        }
+    }
+}
+
+/// Where a ticket stands. Only [TicketStatus::Open] and [TicketStatus::InReview] take new messages.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketStatus {
+    Open,
+    InReview,
+    Resolved,
+    /// Dropped without an outcome.
+    Closed,
+}
+
+impl TicketStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TicketStatus::Open => "open",
+            TicketStatus::InReview => "in_review",
+            TicketStatus::Resolved => "resolved",
+            TicketStatus::Closed => "closed",
+        }
+    }
+}
+
+/// What an admin decided when resolving a ticket.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketOutcome {
+    ResultOverride,
+    Forfeit,
+    Ban,
+    Warning,
+    NoAction,
+}
+
+impl TicketOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TicketOutcome::ResultOverride => "result_override",
+            TicketOutcome::Forfeit => "forfeit",
+            TicketOutcome::Ban => "ban",
+            TicketOutcome::Warning => "warning",
+            TicketOutcome::NoAction => "no_action",
+        }
+    }
+}
+
+/// A dispute or support request a team opened, see [TicketMessage] for its thread.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "tickets")]
+pub struct Ticket {
+    pub id: i64,
+    pub leagueid: i64,
+    pub teamid: i64,
+    pub opened_by: i64,
+    pub gameid: Option<i64>,
+    pub reported_userid: Option<i64>,
+    /// One of [TicketCategory], as a string.
+    pub category: String,
+    pub subject: String,
+    /// One of [TicketStatus], as a string.
+    pub status: String,
+    /// One of [TicketOutcome], as a string. Only set once resolved.
+    pub outcome: Option<String>,
+    pub resolution_note: Option<String>,
+    pub resolved_by: Option<i64>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Ticket {
+    /// Whether people can still add to the thread.
+    pub fn is_open(&self) -> bool {
+        self.status == TicketStatus::Open.as_str() || self.status == TicketStatus::InReview.as_str()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MiniTicket {
+    pub leagueid: i64,
+    pub teamid: i64,
+    pub opened_by: i64,
+    pub gameid: Option<i64>,
+    pub reported_userid: Option<i64>,
+    pub category: TicketCategory,
+    pub subject: String,
+}
+
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "ticket_messages")]
+pub struct TicketMessage {
+    pub id: i64,
+    pub ticketid: i64,
+    pub userid: i64,
+    pub body: String,
+    pub evidence: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// [PlayerGameStats] summed up over many games.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize, Default, PartialEq, Eq)]
+#[pg_mapper(table = "player_league_stats")]
+pub struct StatTotals {
+    pub games_played: i64,
+    pub goals: i64,
+    pub assists: i64,
+    pub intercepts: i64,
+    pub steals: i64,
+    pub passes: i64,
+    pub deaths: i64,
+}
+
+impl std::ops::AddAssign<&StatTotals> for StatTotals {
+    fn add_assign(&mut self, other: &StatTotals) {
+        self.games_played += other.games_played;
+        self.goals += other.goals;
+        self.assists += other.assists;
+        self.intercepts += other.intercepts;
+        self.steals += other.steals;
+        self.passes += other.passes;
+        self.deaths += other.deaths;
+    }
+}
+
+/// [StatTotals] for one season. `None` stands for leagues outside any season.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "player_league_stats")]
+pub struct SeasonStatTotals {
+    pub seasonid: Option<i64>,
+    pub games_played: i64,
+    pub goals: i64,
+    pub assists: i64,
+    pub intercepts: i64,
+    pub steals: i64,
+    pub passes: i64,
+    pub deaths: i64,
+}
+
+impl From<SeasonStatTotals> for StatTotals {
+    fn from(totals: SeasonStatTotals) -> Self {
+        StatTotals {
+            games_played: totals.games_played,
+            goals: totals.goals,
+            assists: totals.assists,
+            intercepts: totals.intercepts,
+            steals: totals.steals,
+            passes: totals.passes,
+            deaths: totals.deaths,
+        }
+    }
+}
+
+/// [StatTotals] for one player.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "player_league_stats")]
+pub struct PlayerStatTotals {
+    pub userid: i64,
+    pub games_played: i64,
+    pub goals: i64,
+    pub assists: i64,
+    pub intercepts: i64,
+    pub steals: i64,
+    pub passes: i64,
+    pub deaths: i64,
+}
+
+/// What a division leaderboard ranks players by.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaderboardStat {
+    #[default]
+    Goals,
+    Assists,
+    Intercepts,
+    Steals,
+    Passes,
+}
+
+impl LeaderboardStat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LeaderboardStat::Goals => "goals",
+            LeaderboardStat::Assists => "assists",
+            LeaderboardStat::Intercepts => "intercepts",
+            LeaderboardStat::Steals => "steals",
+            LeaderboardStat::Passes => "passes",
+        }
+    }
+}
+
+/// One player who played for `teamid` in a game.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "game_lineups")]
+pub struct LineupEntry {
+    pub id: i64,
+    pub gameid: i64,
+    pub teamid: i64,
+    pub userid: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where a request to play a mercenary stands.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MercenaryStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl MercenaryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MercenaryStatus::Pending => "pending",
+            MercenaryStatus::Approved => "approved",
+            MercenaryStatus::Denied => "denied",
+        }
+    }
+}
+
+/// A team asking a league admin to let `userid`, who isn't on its roster, play in a game.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "mercenary_requests")]
+pub struct MercenaryRequest {
+    pub id: i64,
+    pub gameid: i64,
+    pub teamid: i64,
+    /// The mercenary.
+    pub userid: i64,
+    pub requested_by: i64,
+    pub note: Option<String>,
+    /// One of [MercenaryStatus], as a string.
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<i64>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "game_time_proposals")]
+pub struct GameTimeProposal {
+    pub id: i64,
+    pub gameid: i64,
+    /// The team that proposed it.
+    pub teamid: i64,
+    pub proposed_by: i64,
+    pub proposed_time: DateTime<Utc>,
+    /// One of [ProposalStatus], as a string.
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub responded_by: Option<i64>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+/// One change to a player's or a team's rating.
+#[derive(Debug, Deserialize, PostgresMapper, Serialize)]
+#[pg_mapper(table = "rating_history")]
+pub struct RatingHistory {
+    pub id: i64,
+    /// Set for a player's rating.
+    pub userid: Option<i64>,
+    /// Set for a team's rating.
+    pub teamid: Option<i64>,
+    /// The game whose result caused it, unless the game has since been deleted.
+    pub gameid: Option<i64>,
+    pub rating_before: f64,
+    pub rating_after: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MiniGame {
+    pub title: Option<String>,
+    pub leagueid: i64,
+    pub teamhomeid: i64,
+    pub teamawayid: i64,
+    /// The default time, used unless the teams agree on another.
+    pub played_at: DateTime<Utc>,
+    #[serde(default)]
+    pub window_starts_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub window_ends_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub schedule_deadline: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize, PostgresMapper, Serialize)]
@@ -136,7 +746,7 @@ pub struct TeamInvite {
 }
 
 #[derive(Debug, Deserialize, PostgresMapper, Serialize)]
-#[pg_mapper(table = "authorization")]
+#[pg_mapper(table = "authorizations")]
 pub struct Authorization {
     pub userid: i64,
     pub token: String,
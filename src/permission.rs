@@ -9,6 +9,7 @@ use bitflags::bitflags;
 use num_derive::FromPrimitive;
 use serde::Serialize;
 
+use crate::errors::MyError;
 use crate::models::User;
 bitflags! {
     #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -25,6 +26,8 @@ bitflags! {
         const CreateGame = 1 << 3;
         /// Can create new Teams and manage existing ones.
         const CreateTeam = 1 << 4;
+        /// Can review accounts flagged by the eligibility checks at signup.
+        const ReviewUsers = 1 << 5;
         // # Premade Permissions
         // Some example premade permission shorthands in order to check multiple permissions at once,
         // or to quickly set a user's permission without specifying each line manually.
@@ -40,6 +43,13 @@ impl User {
         (self.check_has_permission(UserPermission::Admin))
             || (self.check_has_permission(permission))
     }
+    /// Like [User::admin_or_perm], but as an error to return from a handler.
+    pub fn require_admin_or_perm(&self, permission: UserPermission) -> Result<(), MyError> {
+        match self.admin_or_perm(permission) {
+            true => Ok(()),
+            false => Err(MyError::Forbidden("Insufficient permissions")),
+        }
+    }
     /// Perform bitwise AND operation on the permission bitfield to see if it contains `permission`.
     pub fn check_has_permission(&self, permission: UserPermission) -> bool {
         permission.bits() & self.permissions != 0
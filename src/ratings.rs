@@ -0,0 +1,181 @@
+// Elo ratings for players and teams, updated from confirmed game results.
+//
+// Everyone starts at `STARTING_RATING`. After a game, each side moves by
+// `K` times how much better (or worse) it did than its rating predicted.
+// A player is rated against the average of the players on the other roster.
+
+/// What a new player or team starts at. Matches the columns' default in `initdb.sql`.
+pub const STARTING_RATING: f64 = 1500.0;
+
+/// How far a single game can move a rating.
+pub const K: f64 = 32.0;
+
+/// How a game went, from one side's point of view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl Outcome {
+    /// The outcome for the side that scored `ours` against `theirs`.
+    pub fn from_scores(ours: i32, theirs: i32) -> Outcome {
+        match ours.cmp(&theirs) {
+            std::cmp::Ordering::Greater => Outcome::Win,
+            std::cmp::Ordering::Equal => Outcome::Draw,
+            std::cmp::Ordering::Less => Outcome::Loss,
+        }
+    }
+
+    /// What the outcome is worth in a standings table: 1 for a win, ½ for a draw.
+    pub fn score(self) -> f64 {
+        match self {
+            Outcome::Win => 1.0,
+            Outcome::Draw => 0.5,
+            Outcome::Loss => 0.0,
+        }
+    }
+}
+
+/// The chance `rating` beats `opponent`, between 0 and 1.
+pub fn expected(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// `rating` after a game against `opponent` that ended in `outcome`.
+pub fn updated(rating: f64, opponent: f64, outcome: Outcome) -> f64 {
+    rating + K * (outcome.score() - expected(rating, opponent))
+}
+
+/// The average of `ratings`, or `fallback` if there are none.
+pub fn average(ratings: &[f64], fallback: f64) -> f64 {
+    match ratings.len() {
+        0 => fallback,
+        n => ratings.iter().sum::<f64>() / n as f64,
+    }
+}
+
+/// A team going into a round of a Swiss system.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Standing {
+    pub teamid: i64,
+    /// The sum of [Outcome::score] over the games it has played.
+    pub points: f64,
+    pub rating: f64,
+}
+
+/// The next round of a Swiss system: home and away team ids, and the team that sits out.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SwissRound {
+    pub pairings: Vec<(i64, i64)>,
+    pub bye: Option<i64>,
+}
+
+/// Pair up `standings` for the next round, trying to avoid rematches of the games in `played`.
+///
+/// Teams are seeded by points, then by rating, so the first round already pairs teams of
+/// similar strength. From the top, every team plays the best seeded team below it that it
+/// hasn't played yet, at home. With an odd number of teams, the lowest seed sits out.
+pub fn swiss_pairings(mut standings: Vec<Standing>, played: &[(i64, i64)]) -> SwissRound {
+    standings.sort_by(|a, b| {
+        b.points
+            .total_cmp(&a.points)
+            .then(b.rating.total_cmp(&a.rating))
+            .then(a.teamid.cmp(&b.teamid))
+    });
+    let mut round = SwissRound::default();
+    if standings.len() % 2 == 1 {
+        round.bye = standings.pop().map(|standing| standing.teamid);
+    }
+    let has_played = |a: i64, b: i64| played.contains(&(a, b)) || played.contains(&(b, a));
+
+    let mut unpaired: Vec<i64> = standings.iter().map(|standing| standing.teamid).collect();
+    while !unpaired.is_empty() {
+        let home = unpaired.remove(0);
+        let i = unpaired
+            .iter()
+            .position(|&away| !has_played(home, away))
+            .unwrap_or(0);
+        round.pairings.push((home, unpaired.remove(i)));
+    }
+    round
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn even_game() {
+        assert_eq!(expected(1500.0, 1500.0), 0.5);
+        assert_eq!(updated(1500.0, 1500.0, Outcome::Win), 1516.0);
+        assert_eq!(updated(1500.0, 1500.0, Outcome::Draw), 1500.0);
+        assert_eq!(updated(1500.0, 1500.0, Outcome::Loss), 1484.0);
+    }
+
+    #[test]
+    fn upsets_move_more() {
+        let favourite = 1700.0;
+        let underdog = 1400.0;
+        let expected_win = updated(favourite, underdog, Outcome::Win) - favourite;
+        let upset = updated(underdog, favourite, Outcome::Win) - underdog;
+        assert!(expected_win > 0.0 && expected_win < K / 2.0);
+        assert!(upset > K / 2.0 && upset < K);
+
+        // Whatever one side gains, the other loses.
+        let loss = updated(favourite, underdog, Outcome::Loss) - favourite;
+        assert!((upset + loss).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outcomes_and_averages() {
+        assert_eq!(Outcome::from_scores(3, 1), Outcome::Win);
+        assert_eq!(Outcome::from_scores(2, 2), Outcome::Draw);
+        assert_eq!(Outcome::from_scores(0, 5), Outcome::Loss);
+        assert_eq!(average(&[1400.0, 1600.0], 0.0), 1500.0);
+        assert_eq!(average(&[], 1234.0), 1234.0);
+    }
+
+    fn standing(teamid: i64, points: f64, rating: f64) -> Standing {
+        Standing {
+            teamid,
+            points,
+            rating,
+        }
+    }
+
+    #[test]
+    fn first_round_is_seeded_by_rating() {
+        let round = swiss_pairings(
+            vec![
+                standing(1, 0.0, 1400.0),
+                standing(2, 0.0, 1700.0),
+                standing(3, 0.0, 1500.0),
+                standing(4, 0.0, 1600.0),
+                standing(5, 0.0, 1300.0),
+            ],
+            &[],
+        );
+        assert_eq!(round.pairings, vec![(2, 4), (3, 1)]);
+        assert_eq!(round.bye, Some(5));
+    }
+
+    #[test]
+    fn later_rounds_avoid_rematches() {
+        let standings = vec![
+            standing(1, 1.0, 1500.0),
+            standing(2, 1.0, 1400.0),
+            standing(3, 0.0, 1600.0),
+            standing(4, 0.0, 1300.0),
+        ];
+        let round = swiss_pairings(standings.clone(), &[(2, 1), (3, 4)]);
+        assert_eq!(round.pairings, vec![(1, 3), (2, 4)]);
+        assert_eq!(round.bye, None);
+
+        // Once everyone has played everyone, the best seeds meet again.
+        let everyone = [(1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+        let round = swiss_pairings(standings, &everyone);
+        assert_eq!(round.pairings, vec![(1, 2), (3, 4)]);
+    }
+}
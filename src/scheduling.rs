@@ -0,0 +1,30 @@
+// Background task that settles game times once teams run out of time to agree on one.
+use deadpool_postgres::Pool;
+use tokio::time::{Duration, Instant};
+
+use crate::db;
+use crate::errors::MyError;
+
+/// Settle overdue schedules every `interval`, forever.
+pub async fn run(pool: Pool, interval: Duration) {
+    let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+    loop {
+        ticker.tick().await;
+        match settle(&pool).await {
+            Ok(0) => (),
+            Ok(settled) => log::info!("Settled {settled} games at their default time"),
+            Err(err) => log::warn!("Settling game schedules failed: {err:?}"),
+        }
+    }
+}
+
+/// Keep the default time of every game whose scheduling deadline has passed,
+/// expiring whatever was still proposed. Returns how many games were settled.
+pub async fn settle(pool: &Pool) -> Result<u64, MyError> {
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+    let settled =
+        db::games::settle_overdue_schedules(&transaction, chrono::offset::Utc::now()).await?;
+    transaction.commit().await?;
+    Ok(settled)
+}
@@ -0,0 +1,465 @@
+// A shared, rate limited and cached client for Steam's Web API.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::time::{Duration, Instant};
+
+use super::{
+    parse_owned_games, parse_player_bans, parse_user_summaries, parse_user_summary,
+    parse_vanity_response, verify_authentication_with_steam, ApiError, OwnedGame, PlayerBans,
+    PlayerSummaryAccess, PubliclyAvailableSummary, MAX_SUMMARIES_PER_REQUEST,
+};
+use crate::steamid::SteamId;
+
+const STEAM_API_ROOT: &str = "https://api.steampowered.com";
+/// The longest `Retry-After` we'll wait out before retrying, rather than giving up.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Everything we ask of Steam's Web API.
+///
+/// Routes and background tasks should only talk to Steam through this trait
+/// (via `AppState::steam`), so that tests can swap in a fake.
+#[async_trait]
+pub trait SteamApi: Send + Sync {
+    /// Get everything we're allowed to see about one player.
+    async fn get_user_summary(&self, steamid: &str) -> Result<PlayerSummaryAccess, ApiError>;
+
+    /// Get the public summaries of up to [MAX_SUMMARIES_PER_REQUEST] players in one request.
+    ///
+    /// Players Steam doesn't know about are left out of the returned list, so don't rely on its order.
+    async fn get_user_summaries(
+        &self,
+        steamids: &[&str],
+    ) -> Result<Vec<PubliclyAvailableSummary>, ApiError>;
+
+    /// Look up whose custom URL `steamcommunity.com/id/{vanity}` is.
+    /// Errors with [ApiError::NotFound] if nobody's is.
+    async fn resolve_vanity_url(&self, vanity: &str) -> Result<SteamId, ApiError>;
+
+    /// Every game a player owns or has played, including free ones.
+    /// `None` if their game details are hidden from us.
+    async fn get_owned_games(&self, steamid: &str) -> Result<Option<Vec<OwnedGame>>, ApiError>;
+
+    /// A player's VAC, game and community bans.
+    async fn get_player_bans(&self, steamid: &str) -> Result<PlayerBans, ApiError>;
+
+    /// Ask Steam whether an OpenID login response (keyed like `openid.identity`) is genuine.
+    async fn verify_openid(&self, key_values: &HashMap<String, String>) -> Result<bool, ApiError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SteamClientConfig {
+    pub api_key: String,
+    /// How long a single request may take before it's abandoned.
+    pub timeout: Duration,
+    /// How many times a failed request is retried, with exponential backoff.
+    pub max_retries: u32,
+    /// The delay before the first retry. Doubles on every retry after.
+    pub retry_base_delay: Duration,
+    /// Steady state requests per second.
+    pub requests_per_second: f64,
+    /// How many requests can be made at once before rate limiting kicks in.
+    pub burst: u32,
+    /// How long a player summary or resolved vanity URL stays cached.
+    pub summary_ttl: Duration,
+}
+
+impl SteamClientConfig {
+    pub fn new(api_key: String) -> Self {
+        SteamClientConfig {
+            api_key,
+            timeout: Duration::from_secs(10),
+            max_retries: 3,
+            retry_base_delay: Duration::from_millis(500),
+            requests_per_second: 1.0,
+            burst: 10,
+            summary_ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+/// The real [SteamApi], backed by one shared HTTP client.
+pub struct SteamClient {
+    http: reqwest::Client,
+    config: SteamClientConfig,
+    bucket: TokenBucket,
+    summaries: TtlCache<PlayerSummaryAccess>,
+    vanities: TtlCache<SteamId>,
+}
+
+impl SteamClient {
+    pub fn new(config: SteamClientConfig) -> Result<Self, ApiError> {
+        let http = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .https_only(true)
+            .build()?;
+        Ok(SteamClient {
+            http,
+            bucket: TokenBucket::new(config.burst, config.requests_per_second),
+            summaries: TtlCache::new(config.summary_ttl),
+            vanities: TtlCache::new(config.summary_ttl),
+            config,
+        })
+    }
+
+    /// GET `path` on the Steam API with our key attached, retrying transient failures.
+    async fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<String, ApiError> {
+        let url = format!("{STEAM_API_ROOT}{path}");
+        let mut attempt = 0;
+        loop {
+            self.bucket.acquire().await;
+            let result = self.try_get(&url, query).await;
+            let Err(err) = &result else {
+                return result;
+            };
+            let Some(delay) = self.retry_delay(attempt, err) else {
+                return result;
+            };
+
+            log::warn!("Steam request to {path} failed ({err:?}), retrying in {delay:?}");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// How long to wait before retrying a request that failed with `err`,
+    /// or `None` if it shouldn't be retried.
+    fn retry_delay(&self, attempt: u32, err: &ApiError) -> Option<Duration> {
+        if attempt >= self.config.max_retries {
+            return None;
+        }
+        let backoff = self.config.retry_base_delay * 2u32.pow(attempt);
+        match err {
+            ApiError::Reqwest(err) if err.is_timeout() || err.is_connect() => Some(backoff),
+            ApiError::Status(status) if *status >= 500 => Some(backoff),
+            // Steam says when to come back. If that's too long to hold a request for, give up.
+            ApiError::RateLimited(Some(retry_after)) => {
+                (*retry_after <= MAX_RETRY_AFTER).then_some(*retry_after)
+            }
+            ApiError::RateLimited(None) => Some(backoff),
+            _ => None,
+        }
+    }
+
+    async fn try_get(&self, url: &str, query: &[(&str, &str)]) -> Result<String, ApiError> {
+        // The URL carries our API key, so keep it out of any errors that might get logged.
+        let resp = self
+            .http
+            .get(url)
+            .query(&[("key", self.config.api_key.as_str())])
+            .query(query)
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?;
+
+        let status = resp.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, Utc::now()));
+            return Err(ApiError::RateLimited(retry_after));
+        }
+        if !status.is_success() {
+            return Err(ApiError::Status(status.as_u16()));
+        }
+        Ok(resp.text().await.map_err(reqwest::Error::without_url)?)
+    }
+}
+
+#[async_trait]
+impl SteamApi for SteamClient {
+    async fn get_user_summary(&self, steamid: &str) -> Result<PlayerSummaryAccess, ApiError> {
+        if let Some(summary) = self.summaries.get(steamid) {
+            log::trace!("Steam summary cache hit for {steamid}");
+            return Ok(summary);
+        }
+        log::trace!("Checking steam summary for player with id {steamid}");
+
+        let body = self
+            .get(
+                "/ISteamUser/GetPlayerSummaries/v0002/",
+                &[("steamids", steamid)],
+            )
+            .await?;
+        log::debug!("Returned info from Steam: {}", body);
+
+        let summary = parse_user_summary(&body)?;
+        self.summaries.insert(steamid.to_owned(), summary.clone());
+        Ok(summary)
+    }
+
+    /// Always asks Steam, since this is what keeps our copies of profiles fresh.
+    async fn get_user_summaries(
+        &self,
+        steamids: &[&str],
+    ) -> Result<Vec<PubliclyAvailableSummary>, ApiError> {
+        if steamids.is_empty() {
+            return Ok(Vec::new());
+        }
+        if steamids.len() > MAX_SUMMARIES_PER_REQUEST {
+            log::error!(
+                "Tried to get {0} summaries in one request, the maximum is {MAX_SUMMARIES_PER_REQUEST}",
+                steamids.len()
+            );
+            return Err(ApiError::Handling);
+        }
+        log::trace!("Checking steam summaries for {0} players", steamids.len());
+
+        let body = self
+            .get(
+                "/ISteamUser/GetPlayerSummaries/v0002/",
+                &[("steamids", &steamids.join(","))],
+            )
+            .await?;
+        parse_user_summaries(&body)
+    }
+
+    async fn resolve_vanity_url(&self, vanity: &str) -> Result<SteamId, ApiError> {
+        // Custom URLs are case insensitive.
+        let key = vanity.to_lowercase();
+        if let Some(steamid) = self.vanities.get(&key) {
+            return Ok(steamid);
+        }
+        log::trace!("Resolving vanity URL {vanity}");
+
+        let body = self
+            .get(
+                "/ISteamUser/ResolveVanityURL/v0001/",
+                &[("vanityurl", vanity)],
+            )
+            .await?;
+
+        let steamid = parse_vanity_response(&body)?;
+        self.vanities.insert(key, steamid);
+        Ok(steamid)
+    }
+
+    async fn get_owned_games(&self, steamid: &str) -> Result<Option<Vec<OwnedGame>>, ApiError> {
+        log::trace!("Getting owned games of {steamid}");
+        let body = self
+            .get(
+                "/IPlayerService/GetOwnedGames/v0001/",
+                // TF2 is free to play, so it only shows up with include_played_free_games.
+                &[("steamid", steamid), ("include_played_free_games", "1")],
+            )
+            .await?;
+        parse_owned_games(&body)
+    }
+
+    async fn get_player_bans(&self, steamid: &str) -> Result<PlayerBans, ApiError> {
+        log::trace!("Getting bans of {steamid}");
+        let body = self
+            .get("/ISteamUser/GetPlayerBans/v1/", &[("steamids", steamid)])
+            .await?;
+        parse_player_bans(&body)
+    }
+
+    async fn verify_openid(&self, key_values: &HashMap<String, String>) -> Result<bool, ApiError> {
+        verify_authentication_with_steam(key_values).await
+    }
+}
+
+/// A token bucket: holds up to `capacity` tokens, refilled at `refill_per_sec`.
+/// Every request takes a token, waiting for one if the bucket is empty.
+struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, refill_per_sec: f64) -> Self {
+        let capacity = f64::from(capacity.max(1));
+        TokenBucket {
+            capacity,
+            // Never let the bucket stop refilling altogether.
+            refill_per_sec: refill_per_sec.max(0.01),
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    async fn acquire(&self) {
+        while let Some(wait) = self.take(Instant::now()) {
+            log::trace!("Steam rate limit reached, waiting {wait:?}");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take a token as of `now`, or say how long until there's one to take.
+    fn take(&self, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now
+            .saturating_duration_since(state.last_refill)
+            .as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        state.last_refill = state.last_refill.max(now);
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (1.0 - state.tokens) / self.refill_per_sec,
+        ))
+    }
+}
+
+/// An in-memory cache whose entries expire `ttl` after being inserted.
+struct TtlCache<V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, V)>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    fn new(ttl: Duration) -> Self {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn insert(&self, key: String, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        // Sweep expired entries as we go so the cache can't grow forever.
+        entries.retain(|_, (inserted, _)| inserted.elapsed() < self.ttl);
+        entries.insert(key, (Instant::now(), value));
+    }
+}
+
+/// A `Retry-After` header's wait, given either as seconds or as an HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    // A date in the past means we can go again right away.
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(max_retries: u32) -> SteamClient {
+        let mut config = SteamClientConfig::new("key".to_owned());
+        config.max_retries = max_retries;
+        config.retry_base_delay = Duration::from_millis(100);
+        SteamClient::new(config).unwrap()
+    }
+
+    #[test]
+    fn bucket_allows_a_burst_then_refills() {
+        let bucket = TokenBucket::new(3, 2.0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(bucket.take(start), None);
+        }
+        // Empty: the next token is half a second away at 2 per second.
+        let wait = bucket.take(start).unwrap();
+        assert!((wait.as_secs_f64() - 0.5).abs() < 1e-6, "{wait:?}");
+
+        assert_eq!(bucket.take(start + Duration::from_millis(500)), None);
+        assert!(bucket.take(start + Duration::from_millis(500)).is_some());
+
+        // It never holds more than its capacity, however long it's left alone.
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(bucket.take(later), None);
+        }
+        assert!(bucket.take(later).is_some());
+    }
+
+    #[test]
+    fn cache_entries_expire() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get("a"), None);
+        cache.insert("a".to_owned(), 1);
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("a".to_owned(), 2);
+        assert_eq!(cache.get("a"), Some(2));
+
+        let expired = TtlCache::new(Duration::ZERO);
+        expired.insert("a".to_owned(), 1);
+        assert_eq!(expired.get("a"), None);
+        // Inserting sweeps out whatever has expired.
+        expired.insert("b".to_owned(), 2);
+        assert_eq!(expired.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        let client = client(3);
+        let err = ApiError::Status(503);
+        assert_eq!(
+            client.retry_delay(0, &err),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            client.retry_delay(2, &err),
+            Some(Duration::from_millis(400))
+        );
+        assert_eq!(client.retry_delay(3, &err), None);
+
+        assert_eq!(client.retry_delay(0, &ApiError::Status(404)), None);
+        assert_eq!(client.retry_delay(0, &ApiError::NotFound), None);
+    }
+
+    #[test]
+    fn retries_honor_retry_after() {
+        let client = client(3);
+        let asked = ApiError::RateLimited(Some(Duration::from_secs(7)));
+        assert_eq!(client.retry_delay(0, &asked), Some(Duration::from_secs(7)));
+        assert_eq!(client.retry_delay(3, &asked), None);
+
+        let unsaid = ApiError::RateLimited(None);
+        assert_eq!(
+            client.retry_delay(1, &unsaid),
+            Some(Duration::from_millis(200))
+        );
+
+        let too_long = ApiError::RateLimited(Some(MAX_RETRY_AFTER + Duration::from_secs(1)));
+        assert_eq!(client.retry_delay(0, &too_long), None);
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-5", now), None);
+    }
+}
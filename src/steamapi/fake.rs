@@ -0,0 +1,169 @@
+// A stand-in for Steam's Web API, for tests.
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use super::{
+    ApiError, OwnedGame, PlayerBans, PlayerSummaryAccess, PrivatelyAvailableSummary,
+    PubliclyAvailableSummary, SteamApi,
+};
+use crate::steamid::SteamId;
+
+/// Answers from players added with [FakeSteam::add_player], and never touches the network.
+///
+/// OpenID logins are accepted for anyone unless [FakeSteam::reject_openid] is called.
+#[derive(Default)]
+pub struct FakeSteam {
+    state: Mutex<FakeSteamState>,
+}
+
+#[derive(Default)]
+struct FakeSteamState {
+    players: HashMap<String, FakePlayer>,
+    vanities: HashMap<String, SteamId>,
+    reject_openid: bool,
+}
+
+#[derive(Clone)]
+pub struct FakePlayer {
+    pub summary: PlayerSummaryAccess,
+    /// `None` if their game details are hidden.
+    pub games: Option<Vec<OwnedGame>>,
+    pub bans: PlayerBans,
+}
+
+impl FakePlayer {
+    /// A public, years old account with plenty of TF2 and no bans.
+    #[allow(deprecated)]
+    pub fn new(steamid: SteamId, name: &str) -> Self {
+        let public = PubliclyAvailableSummary {
+            steamid: steamid.to_string(),
+            personaname: name.to_owned(),
+            profileurl: steamid.profile_url(),
+            avatar: format!("https://avatars.example/{steamid}.jpg"),
+            avatarmedium: format!("https://avatars.example/{steamid}_medium.jpg"),
+            avatarfull: format!("https://avatars.example/{steamid}_full.jpg"),
+            personastate: Some(0),
+            profilestate: Some(1),
+            lastlogoff: None,
+            commentpermission: None,
+        };
+        let private = PrivatelyAvailableSummary {
+            realname: None,
+            primaryclanid: None,
+            // 2015-01-01
+            timecreated: Some(1_420_070_400),
+            gameid: None,
+            gameserverip: None,
+            gameextrainfo: None,
+            cityid: None,
+            loccountrycode: None,
+            locstatecode: None,
+            loccityid: None,
+        };
+        FakePlayer {
+            summary: PlayerSummaryAccess::All {
+                private: Box::new(private),
+                public: Box::new(public),
+            },
+            games: Some(vec![OwnedGame {
+                appid: 440,
+                playtime_forever: 1000 * 60,
+            }]),
+            bans: PlayerBans {
+                steamid: steamid.to_string(),
+                community_banned: false,
+                vac_banned: false,
+                number_of_vac_bans: 0,
+                days_since_last_ban: 0,
+                number_of_game_bans: 0,
+                economy_ban: "none".to_owned(),
+            },
+        }
+    }
+
+    /// Hide everything but the public summary, like a private profile does.
+    pub fn private(mut self) -> Self {
+        if let PlayerSummaryAccess::All { public, .. } = self.summary {
+            self.summary = PlayerSummaryAccess::Private { public };
+        }
+        self.games = None;
+        self
+    }
+}
+
+impl FakeSteam {
+    pub fn add_player(&self, player: FakePlayer) {
+        let (PlayerSummaryAccess::All { public, .. } | PlayerSummaryAccess::Private { public }) =
+            &player.summary;
+        let steamid = public.steamid.clone();
+        self.state.lock().unwrap().players.insert(steamid, player);
+    }
+
+    pub fn add_vanity(&self, vanity: &str, steamid: SteamId) {
+        self.state
+            .lock()
+            .unwrap()
+            .vanities
+            .insert(vanity.to_lowercase(), steamid);
+    }
+
+    pub fn reject_openid(&self) {
+        self.state.lock().unwrap().reject_openid = true;
+    }
+
+    fn player(&self, steamid: &str) -> Result<FakePlayer, ApiError> {
+        self.state
+            .lock()
+            .unwrap()
+            .players
+            .get(steamid)
+            .cloned()
+            .ok_or(ApiError::NotFound)
+    }
+}
+
+#[async_trait]
+impl SteamApi for FakeSteam {
+    async fn get_user_summary(&self, steamid: &str) -> Result<PlayerSummaryAccess, ApiError> {
+        Ok(self.player(steamid)?.summary)
+    }
+
+    async fn get_user_summaries(
+        &self,
+        steamids: &[&str],
+    ) -> Result<Vec<PubliclyAvailableSummary>, ApiError> {
+        let summaries = steamids
+            .iter()
+            .filter_map(|steamid| self.player(steamid).ok())
+            .map(|player| match player.summary {
+                PlayerSummaryAccess::All { public, .. }
+                | PlayerSummaryAccess::Private { public } => *public,
+            })
+            .collect();
+        Ok(summaries)
+    }
+
+    async fn resolve_vanity_url(&self, vanity: &str) -> Result<SteamId, ApiError> {
+        self.state
+            .lock()
+            .unwrap()
+            .vanities
+            .get(&vanity.to_lowercase())
+            .copied()
+            .ok_or(ApiError::NotFound)
+    }
+
+    async fn get_owned_games(&self, steamid: &str) -> Result<Option<Vec<OwnedGame>>, ApiError> {
+        Ok(self.player(steamid)?.games)
+    }
+
+    async fn get_player_bans(&self, steamid: &str) -> Result<PlayerBans, ApiError> {
+        Ok(self.player(steamid)?.bans)
+    }
+
+    async fn verify_openid(&self, _key_values: &HashMap<String, String>) -> Result<bool, ApiError> {
+        Ok(!self.state.lock().unwrap().reject_openid)
+    }
+}
@@ -1,8 +1,16 @@
+// Code that interacts with Steam's Web API.
 use std::collections::HashMap;
 
-// Code that interacts with Steam's Web API.
 use derive_more::{Display, Error, From};
 
+use crate::steamid::SteamId;
+
+mod client;
+#[cfg(test)]
+pub mod fake;
+
+pub use client::{SteamApi, SteamClient, SteamClientConfig};
+
 /// An error caused by our interacting with the steam API.
 #[derive(Debug, Display, Error, From)]
 pub enum ApiError {
@@ -11,6 +19,13 @@ pub enum ApiError {
     NotFound,
     KeyValues(steamopenid::kv::DecodeError),
     Serde(serde_json::Error),
+    /// Steam answered with a non-success status code.
+    #[from(ignore)]
+    Status(#[error(not(source))] u16),
+    /// Steam answered 429 Too Many Requests, with how long its `Retry-After` asked us to wait.
+    #[from(ignore)]
+    #[display("RateLimited({_0:?})")]
+    RateLimited(#[error(not(source))] Option<std::time::Duration>),
 }
 /// The level of access we have to the user's profile, and the according data.
 pub enum ReturnedAccessLevel {
@@ -20,7 +35,7 @@ pub enum ReturnedAccessLevel {
     Private = 1,
 }
 
-#[derive(serde::Deserialize, serde::Serialize, From)]
+#[derive(serde::Deserialize, serde::Serialize, From, Clone)]
 pub enum PlayerSummaryAccess {
     All {
         private: Box<PrivatelyAvailableSummary>,
@@ -31,7 +46,7 @@ pub enum PlayerSummaryAccess {
     },
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct PubliclyAvailableSummary {
     /// 64bit SteamID of the user.
     pub steamid: String,
@@ -55,7 +70,7 @@ pub struct PubliclyAvailableSummary {
     pub commentpermission: Option<i64>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 pub struct PrivatelyAvailableSummary {
     pub realname: Option<String>,
     pub primaryclanid: Option<String>,
@@ -152,39 +167,27 @@ impl PrivatelyAvailableSummary {
     }
 }
 
-pub async fn get_user_summary(
-    steam_api_key: &String,
-    steamid: &str,
-) -> Result<PlayerSummaryAccess, ApiError> {
-    log::trace!("Checking steam summary for player with id {steamid}");
-    let url = format!(
-        "http://api.steampowered.com/ISteamUser/GetPlayerSummaries/v0002/?key={0}&steamids={1}",
-        steam_api_key, steamid
-    );
-    let body = reqwest::get(url).await?.text().await?;
-    log::debug!("Returned info from Steam: {}", body);
-
-    let mut basic_info = serde_json::from_str::<SteamReturnInfo>(&body)?;
+/// Turn a `GetPlayerSummaries` response for a single player into what we can see of them.
+fn parse_user_summary(body: &str) -> Result<PlayerSummaryAccess, ApiError> {
+    let mut basic_info = serde_json::from_str::<SteamReturnInfo>(body)?;
 
     log::debug!("Steam basic return info: {basic_info:?}");
     let response_type = match basic_info
         .response
         .players
         .pop()
-        .ok_or(ApiError::Handling)?
+        .ok_or(ApiError::NotFound)?
         .communityvisibilitystate
     {
         3 => ReturnedAccessLevel::All,
-        1 => ReturnedAccessLevel::Private,
-        _ => {
-            return Err(ApiError::Handling);
-        }
+        // Friends only profiles (2) are just as hidden from us as private ones.
+        _ => ReturnedAccessLevel::Private,
     };
 
     // We can see the whole profile, therefore the response includes everything
     if let ReturnedAccessLevel::All = response_type {
         log::debug!("Steam response matched All ReturnedAccessLevel");
-        let needed_info = serde_json::from_str::<SteamResponseAllInfo>(&body)?
+        let needed_info = serde_json::from_str::<SteamResponseAllInfo>(body)?
             .response
             .players
             .pop()
@@ -197,12 +200,100 @@ pub async fn get_user_summary(
     // We can't see the whole profile, therefore the response includes only public information.
     else {
         log::debug!("Steam response matched Private ReturnedAccessLevel");
+        let public = serde_json::from_str::<SteamResponsePublicInfo>(body)?
+            .response
+            .players
+            .pop()
+            .ok_or(ApiError::NotFound)?;
         Ok(PlayerSummaryAccess::Private {
-            public: Box::new(serde_json::from_str(&body)?),
+            public: Box::new(public),
         })
     }
 }
 
+/// The most SteamIDs `GetPlayerSummaries` will take in one request.
+pub const MAX_SUMMARIES_PER_REQUEST: usize = 100;
+
+fn parse_user_summaries(body: &str) -> Result<Vec<PubliclyAvailableSummary>, ApiError> {
+    let summaries = serde_json::from_str::<SteamResponsePublicInfo>(body)?
+        .response
+        .players;
+    Ok(summaries)
+}
+
+/// One game from `GetOwnedGames`.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct OwnedGame {
+    pub appid: u32,
+    /// Total playtime, in minutes.
+    pub playtime_forever: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct OwnedGamesResponse {
+    response: OwnedGamesResponseInner,
+}
+
+#[derive(serde::Deserialize)]
+struct OwnedGamesResponseInner {
+    /// Missing if the player's game details are hidden from us.
+    games: Option<Vec<OwnedGame>>,
+}
+
+fn parse_owned_games(body: &str) -> Result<Option<Vec<OwnedGame>>, ApiError> {
+    Ok(serde_json::from_str::<OwnedGamesResponse>(body)?
+        .response
+        .games)
+}
+
+/// A player's bans, from `GetPlayerBans`.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerBans {
+    #[serde(rename = "SteamId")]
+    pub steamid: String,
+    pub community_banned: bool,
+    #[serde(rename = "VACBanned")]
+    pub vac_banned: bool,
+    #[serde(rename = "NumberOfVACBans")]
+    pub number_of_vac_bans: u32,
+    pub days_since_last_ban: u32,
+    pub number_of_game_bans: u32,
+    pub economy_ban: String,
+}
+
+#[derive(serde::Deserialize)]
+struct PlayerBansResponse {
+    players: Vec<PlayerBans>,
+}
+
+fn parse_player_bans(body: &str) -> Result<PlayerBans, ApiError> {
+    serde_json::from_str::<PlayerBansResponse>(body)?
+        .players
+        .pop()
+        .ok_or(ApiError::NotFound)
+}
+
+#[derive(serde::Deserialize)]
+struct ResolveVanityResponse {
+    response: ResolveVanityResponseInner,
+}
+
+#[derive(serde::Deserialize)]
+struct ResolveVanityResponseInner {
+    /// 1 if the vanity URL was found, 42 if not.
+    success: i32,
+    steamid: Option<String>,
+}
+
+fn parse_vanity_response(body: &str) -> Result<SteamId, ApiError> {
+    let response = serde_json::from_str::<ResolveVanityResponse>(body)?.response;
+    match (response.success, response.steamid) {
+        (1, Some(steamid)) => steamid.parse().map_err(|_| ApiError::Handling),
+        _ => Err(ApiError::NotFound),
+    }
+}
+
 impl From<steamopenid::ApiError> for ApiError {
     fn from(value: steamopenid::ApiError) -> Self {
         match value {
@@ -219,9 +310,3 @@ pub async fn verify_authentication_with_steam(
         .await
         .map_err(|err| err.into())
 }
-
-pub async fn verify_auth_underscores(key_values_map: &str) -> Result<bool, ApiError> {
-    let map: HashMap<String, String> =
-        serde_json::from_str(&key_values_map.replace("__", ".")).unwrap();
-    verify_authentication_with_steam(&map).await
-}
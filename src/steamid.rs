@@ -0,0 +1,299 @@
+// Parsing and converting the many ways of writing a SteamID.
+//
+// Steam accounts can be written as:
+// - SteamID64: `76561198025040446`
+// - SteamID2: `STEAM_0:0:32387359`
+// - SteamID3: `[U:1:64774718]`
+// - a profile link: `https://steamcommunity.com/profiles/76561198025040446`
+// - a vanity link: `https://steamcommunity.com/id/lucy`, which only Steam can turn into an id.
+//
+// We only ever store SteamID64s, so everything that takes a SteamID from a user
+// should go through [SteamIdInput] and [SteamIdInput::resolve] first.
+use std::fmt;
+use std::str::FromStr;
+
+use derive_more::{Display, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::steamapi::{ApiError, SteamApi};
+
+/// The SteamID64 of the individual account with account id 0.
+/// Universe 1 (public), type 1 (individual), instance 1 (desktop).
+const INDIVIDUAL_BASE: u64 = 0x0110_0001_0000_0000;
+
+/// A validated SteamID of an individual (i.e. player) account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SteamId(u64);
+
+#[derive(Debug, Display, Error, PartialEq, Eq)]
+pub enum SteamIdError {
+    #[display("not a recognised SteamID, SteamID3, SteamID64 or profile link")]
+    Unrecognised,
+    #[display("not the SteamID of a player account")]
+    NotIndividual,
+}
+
+impl SteamId {
+    pub fn from_steamid64(steamid64: u64) -> Result<Self, SteamIdError> {
+        if steamid64 >> 32 != INDIVIDUAL_BASE >> 32 || steamid64 == INDIVIDUAL_BASE {
+            return Err(SteamIdError::NotIndividual);
+        }
+        Ok(SteamId(steamid64))
+    }
+
+    pub fn from_account_id(account_id: u32) -> Result<Self, SteamIdError> {
+        Self::from_steamid64(INDIVIDUAL_BASE | u64::from(account_id))
+    }
+
+    /// The lower 32 bits of the SteamID64, which is what SteamID2 and SteamID3 are built from.
+    pub fn account_id(self) -> u32 {
+        (self.0 & 0xFFFF_FFFF) as u32
+    }
+
+    pub fn steamid64(self) -> u64 {
+        self.0
+    }
+
+    /// `STEAM_0:Y:Z`, as the Source engine writes it.
+    pub fn steam2(self) -> String {
+        let account_id = self.account_id();
+        format!("STEAM_0:{0}:{1}", account_id & 1, account_id >> 1)
+    }
+
+    /// `[U:1:W]`, as TF2's `status` and logs.tf write it.
+    pub fn steam3(self) -> String {
+        format!("[U:1:{0}]", self.account_id())
+    }
+
+    pub fn profile_url(self) -> String {
+        format!("https://steamcommunity.com/profiles/{0}", self.0)
+    }
+}
+
+/// Displays as a SteamID64, the format we store.
+impl fmt::Display for SteamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parses anything that can be turned into a SteamID without asking Steam.
+impl FromStr for SteamId {
+    type Err = SteamIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<SteamIdInput>()? {
+            SteamIdInput::Id(id) => Ok(id),
+            SteamIdInput::Vanity(_) => Err(SteamIdError::Unrecognised),
+        }
+    }
+}
+
+impl Serialize for SteamId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SteamId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A SteamID as a user typed it: either something we can parse ourselves,
+/// or a vanity name that has to be looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SteamIdInput {
+    Id(SteamId),
+    Vanity(String),
+}
+
+impl SteamIdInput {
+    /// Turn this into a [SteamId], asking Steam to resolve vanity names.
+    pub async fn resolve(self, steam: &dyn SteamApi) -> Result<SteamId, ApiError> {
+        match self {
+            SteamIdInput::Id(id) => Ok(id),
+            SteamIdInput::Vanity(vanity) => steam.resolve_vanity_url(&vanity).await,
+        }
+    }
+}
+
+impl FromStr for SteamIdInput {
+    type Err = SteamIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(path) = strip_community_host(s) {
+            let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+            return match (segments.next(), segments.next()) {
+                (Some("profiles"), Some(id)) => parse_id(id).map(SteamIdInput::Id),
+                (Some("id"), Some(vanity)) if is_vanity(vanity) => {
+                    Ok(SteamIdInput::Vanity(vanity.to_owned()))
+                }
+                _ => Err(SteamIdError::Unrecognised),
+            };
+        }
+        parse_id(s).map(SteamIdInput::Id)
+    }
+}
+
+/// If `s` is a steamcommunity.com link, the path after the host.
+fn strip_community_host(s: &str) -> Option<&str> {
+    let s = s
+        .strip_prefix("https://")
+        .or_else(|| s.strip_prefix("http://"))
+        .unwrap_or(s);
+    let s = s.strip_prefix("www.").unwrap_or(s);
+    s.strip_prefix("steamcommunity.com/")
+}
+
+fn parse_id(s: &str) -> Result<SteamId, SteamIdError> {
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        let steamid64 = s.parse().map_err(|_| SteamIdError::Unrecognised)?;
+        return SteamId::from_steamid64(steamid64);
+    }
+    if let Some(rest) = strip_prefix_ignore_case(s, "STEAM_") {
+        return parse_steam2(rest);
+    }
+    let steam3 = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .unwrap_or(s);
+    if let Some(account_id) = strip_prefix_ignore_case(steam3, "U:1:") {
+        let account_id = account_id.parse().map_err(|_| SteamIdError::Unrecognised)?;
+        return SteamId::from_account_id(account_id);
+    }
+    Err(SteamIdError::Unrecognised)
+}
+
+/// Parse the `X:Y:Z` of `STEAM_X:Y:Z`. X is the universe, which games disagree on,
+/// so it's checked to be sane but otherwise ignored.
+fn parse_steam2(s: &str) -> Result<SteamId, SteamIdError> {
+    let mut parts = s.split(':');
+    let (Some(universe), Some(y), Some(z), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(SteamIdError::Unrecognised);
+    };
+    let universe: u8 = universe.parse().map_err(|_| SteamIdError::Unrecognised)?;
+    let y: u32 = y.parse().map_err(|_| SteamIdError::Unrecognised)?;
+    let z: u32 = z.parse().map_err(|_| SteamIdError::Unrecognised)?;
+    if universe > 1 || y > 1 || z > u32::MAX >> 1 {
+        return Err(SteamIdError::Unrecognised);
+    }
+    SteamId::from_account_id(z << 1 | y)
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+/// Steam only allows these in custom URLs.
+fn is_vanity(s: &str) -> bool {
+    (2..=32).contains(&s.len())
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LUCY: u64 = 76561198025040446;
+
+    fn id(s: &str) -> Result<SteamId, SteamIdError> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_steamid64() {
+        assert_eq!(id("76561198025040446").unwrap().steamid64(), LUCY);
+        assert_eq!(id("  76561198025040446 ").unwrap().steamid64(), LUCY);
+        assert_eq!(
+            id("https://steamcommunity.com/profiles/76561198025040446/")
+                .unwrap()
+                .steamid64(),
+            LUCY
+        );
+    }
+
+    #[test]
+    fn parses_steam2() {
+        assert_eq!(id("STEAM_0:0:32387359").unwrap().steamid64(), LUCY);
+        // Some games write universe 1 instead of 0.
+        assert_eq!(id("STEAM_1:0:32387359").unwrap().steamid64(), LUCY);
+        assert_eq!(id("steam_0:0:32387359").unwrap().steamid64(), LUCY);
+    }
+
+    #[test]
+    fn parses_steam3() {
+        assert_eq!(id("[U:1:64774718]").unwrap().steamid64(), LUCY);
+        assert_eq!(id("U:1:64774718").unwrap().steamid64(), LUCY);
+        assert_eq!(id("[u:1:64774718]").unwrap().steamid64(), LUCY);
+    }
+
+    #[test]
+    fn converts_between_formats() {
+        let lucy = SteamId::from_steamid64(LUCY).unwrap();
+        assert_eq!(lucy.account_id(), 64774718);
+        assert_eq!(lucy.steam2(), "STEAM_0:0:32387359");
+        assert_eq!(lucy.steam3(), "[U:1:64774718]");
+        assert_eq!(lucy.to_string(), "76561198025040446");
+        for written in [lucy.steam2(), lucy.steam3(), lucy.profile_url()] {
+            assert_eq!(id(&written), Ok(lucy));
+        }
+
+        let odd = SteamId::from_account_id(64774719).unwrap();
+        assert_eq!(odd.steam2(), "STEAM_0:1:32387359");
+        assert_eq!(id(&odd.steam2()), Ok(odd));
+    }
+
+    #[test]
+    fn vanity_links_need_steam() {
+        assert_eq!(
+            "https://steamcommunity.com/id/lucy".parse::<SteamIdInput>(),
+            Ok(SteamIdInput::Vanity("lucy".to_owned()))
+        );
+        assert_eq!(
+            id("steamcommunity.com/id/lucy"),
+            Err(SteamIdError::Unrecognised)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for input in [
+            "",
+            "lucy",
+            "7656119802504044a",
+            "99999999999999999999999",
+            "STEAM_0:2:32387359",
+            "STEAM_2:0:32387359",
+            "STEAM_0:0",
+            "STEAM_0:0:1:2",
+            "STEAM_0:0:-1",
+            "[U:1:]",
+            "[U:1:4294967296]",
+            "[G:1:64774718]",
+            "https://steamcommunity.com/groups/tf2",
+            "https://steamcommunity.com/id/a",
+        ] {
+            assert_eq!(id(input), Err(SteamIdError::Unrecognised), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_non_individual_accounts() {
+        // A group's SteamID64.
+        assert_eq!(id("103582791429521412"), Err(SteamIdError::NotIndividual));
+        // Account id 0 is nobody.
+        assert_eq!(id("[U:1:0]"), Err(SteamIdError::NotIndividual));
+        assert_eq!(id("76561197960265728"), Err(SteamIdError::NotIndividual));
+    }
+}
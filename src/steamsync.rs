@@ -0,0 +1,112 @@
+// Background task that keeps users' Steam names and avatars up to date.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use deadpool_postgres::{Client, Pool};
+use tokio::time::{Duration, Instant};
+
+use crate::db;
+use crate::errors::MyError;
+use crate::models::User;
+use crate::steamapi::{SteamApi, MAX_SUMMARIES_PER_REQUEST};
+
+/// Resync every user's profile every `interval`, forever.
+///
+/// The first sync happens one `interval` after startup, not immediately.
+pub async fn run(pool: Pool, steam: Arc<dyn SteamApi>, interval: Duration) {
+    let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+    loop {
+        ticker.tick().await;
+        log::info!("Starting Steam profile sync");
+        match sync_all_profiles(&pool, steam.as_ref()).await {
+            Ok(updated) => log::info!("Steam profile sync finished, {updated} profiles changed"),
+            Err(err) => log::warn!("Steam profile sync failed: {err:?}"),
+        }
+    }
+}
+
+/// Fetch every user's Steam summary in batches, updating changed names and avatars
+/// and recording every name seen. Returns how many users were changed.
+///
+/// Each batch is written in its own transaction. A batch that fails is logged and skipped,
+/// so one bad batch doesn't stop the rest from syncing.
+pub async fn sync_all_profiles(pool: &Pool, steam: &dyn SteamApi) -> Result<usize, MyError> {
+    let mut client = pool.get().await?;
+    let batch_size = MAX_SUMMARIES_PER_REQUEST as i64;
+
+    let mut last_id = 0;
+    let mut updated = 0;
+    loop {
+        let users = db::users::get_users_after_id(&client, last_id, batch_size).await?;
+        let (Some(first_user), Some(last_user)) = (users.first(), users.last()) else {
+            break;
+        };
+        match sync_batch(&mut client, steam, &users).await {
+            Ok(changed) => updated += changed,
+            Err(err) => log::warn!(
+                "Steam profile sync of users {0} to {1} failed, skipping them: {err:?}",
+                first_user.id,
+                last_user.id
+            ),
+        }
+        last_id = last_user.id;
+
+        if (users.len() as i64) < batch_size {
+            break;
+        }
+    }
+    Ok(updated)
+}
+
+/// Sync one batch of `users`, all or nothing. Returns how many of them were changed.
+async fn sync_batch(
+    client: &mut Client,
+    steam: &dyn SteamApi,
+    users: &[User],
+) -> Result<usize, MyError> {
+    let steamids: Vec<&str> = users.iter().map(|user| user.steamid.as_str()).collect();
+    let summaries = steam.get_user_summaries(&steamids).await?;
+    log::debug!(
+        "Got {0} of {1} requested summaries from Steam",
+        summaries.len(),
+        users.len()
+    );
+
+    let users_by_steamid: HashMap<&str, _> = users
+        .iter()
+        .map(|user| (user.steamid.as_str(), user))
+        .collect();
+    let seen_at = chrono::offset::Utc::now();
+
+    let transaction = client.transaction().await?;
+    let mut updated = 0;
+    for summary in summaries {
+        let Some(user) = users_by_steamid.get(summary.steamid.as_str()) else {
+            log::warn!(
+                "Steam returned a summary we didn't ask for: {0}",
+                summary.steamid
+            );
+            continue;
+        };
+        db::users::record_username(&transaction, user.id, &summary.personaname, seen_at).await?;
+
+        if user.username != summary.personaname || user.avatarurl != summary.avatarfull {
+            log::debug!(
+                "Updating profile of user {0}: {1} -> {2}",
+                user.id,
+                user.username,
+                summary.personaname
+            );
+            db::users::update_user_profile(
+                &transaction,
+                user.id,
+                &summary.personaname,
+                &summary.avatarfull,
+            )
+            .await?;
+            updated += 1;
+        }
+    }
+    transaction.commit().await?;
+    Ok(updated)
+}